@@ -10,6 +10,15 @@
       setupOutput,
       updateMain,
       updateHeader,
+      updateUpper,
+      splitUpper,
+      eraseWindow,
+      updateTranscript,
+      updateCommands,
+      transcript = "",
+      commands = "",
+      upperLines = [],
+      upperTop = 1,
       RustyZ = this.RustyZ,
       document = this.document,
       content = document.getElementById("content"),
@@ -18,7 +27,10 @@
       input = document.getElementById("player_input"),
       left_header = document.querySelector("#header .left"),
       main = document.getElementById("main"),
-      right_header = document.querySelector("#header .right");
+      right_header = document.querySelector("#header .right"),
+      upper = document.getElementById("upper"),
+      transcript_link = document.getElementById("transcript"),
+      commands_link = document.getElementById("commands");
 
     setupInput = function setupInput() {
       form.addEventListener("submit", function() {
@@ -66,8 +78,24 @@
             updateHeader(update);
             break;
           case "input":
+            input.maxLength = parseInt(update.content, 10);
             focusInput();
             break;
+          case "upper":
+            updateUpper(update);
+            break;
+          case "split":
+            splitUpper(update);
+            break;
+          case "erase":
+            eraseWindow(update);
+            break;
+          case "transcript":
+            updateTranscript(update);
+            break;
+          case "command":
+            updateCommands(update);
+            break;
         }
       });
     };
@@ -83,6 +111,77 @@
       el.innerHTML = string;
     };
 
+    // the upper window is a grid of lines, written to at a line and column
+    // ( both start at 1, and count from the top of the screen )
+    updateUpper = function updateUpper(update) {
+      var index = update.line - upperTop;
+
+      if (index < 0 || index >= upperLines.length) {
+        return;
+      }
+
+      var line = upperLines[index];
+
+      while (line.length < update.column - 1) {
+        line += " ";
+      }
+
+      upperLines[index] =
+        line.slice(0, update.column - 1) +
+        update.content +
+        line.slice(update.column - 1 + update.content.length);
+
+      upper.textContent = upperLines.join("\n");
+    };
+
+    splitUpper = function splitUpper(update) {
+      var lines = parseInt(update.content, 10);
+
+      upperTop = update.line;
+      upperLines = upperLines.slice(0, lines);
+
+      while (upperLines.length < lines) {
+        upperLines.push("");
+      }
+
+      upper.textContent = upperLines.join("\n");
+    };
+
+    eraseWindow = function eraseWindow(update) {
+      if (update.content !== "lower") {
+        upperLines = upperLines.map(function() {
+          return "";
+        });
+        upper.textContent = upperLines.join("\n");
+      }
+
+      if (update.content !== "upper") {
+        content.innerHTML = "";
+      }
+    };
+
+    // the transcript is kept here, and downloaded as a text file
+    updateTranscript = function updateTranscript(update) {
+      transcript += update.content;
+      transcript_link.style.display = "block";
+    };
+
+    transcript_link.addEventListener("click", function() {
+      var blob = new Blob([transcript], { type: "text/plain" });
+      transcript_link.href = URL.createObjectURL(blob);
+    });
+
+    // the command record is the same, one command a line
+    updateCommands = function updateCommands(update) {
+      commands += update.content;
+      commands_link.style.display = "block";
+    };
+
+    commands_link.addEventListener("click", function() {
+      var blob = new Blob([commands], { type: "text/plain" });
+      commands_link.href = URL.createObjectURL(blob);
+    });
+
     setupInput();
     setupOutput();
   });
@@ -0,0 +1,69 @@
+use super::super::zmachine::sound::SoundEffect;
+
+use std::io;
+use std::io::Write;
+
+// interfaces pass sound effects on to one of these, so how sounds get played
+// ( or don't ) can be swapped out without touching the rest of the interface
+pub trait AudioBackend {
+    fn sound_effect(&mut self, effect: &SoundEffect);
+
+    // whether a sound that was started is still going - the machine asks so
+    // it can tell the story when it's done
+    fn is_playing(&self, number: u16) -> bool;
+}
+
+// all a terminal can do is ring its bell, so that's what the bleeps do -
+// sampled sounds are skipped, and finish straight away
+pub struct TerminalBell;
+
+impl AudioBackend for TerminalBell {
+    fn sound_effect(&mut self, effect: &SoundEffect) {
+        if let SoundEffect::Bleep { .. } = *effect {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+    }
+
+    fn is_playing(&self, _: u16) -> bool {
+        false
+    }
+}
+
+// writes every sound effect to out, a line each, instead of playing anything -
+// this is for checking what a story does with its sounds. like the bell,
+// nothing is ever still playing
+pub struct RecordingAudio {
+    out: Box<dyn Write>,
+}
+
+impl RecordingAudio {
+    pub fn new(out: Box<dyn Write>) -> RecordingAudio {
+        RecordingAudio { out }
+    }
+}
+
+impl AudioBackend for RecordingAudio {
+    fn sound_effect(&mut self, effect: &SoundEffect) {
+        let line = match *effect {
+            SoundEffect::Bleep { high: true } => "bleep high".to_string(),
+            SoundEffect::Bleep { high: false } => "bleep low".to_string(),
+            SoundEffect::Prepare { number } => format!("prepare {}", number),
+            SoundEffect::Start {
+                number,
+                volume,
+                repeats,
+            } => format!("start {} volume {} repeats {}", number, volume, repeats),
+            SoundEffect::Stop { number } => format!("stop {}", number),
+            SoundEffect::Unload { number } => format!("unload {}", number),
+        };
+
+        // a log that can't be written isn't worth stopping the game for
+        let _ = writeln!(self.out, "{}", line);
+        let _ = self.out.flush();
+    }
+
+    fn is_playing(&self, _: u16) -> bool {
+        false
+    }
+}
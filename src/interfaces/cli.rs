@@ -2,41 +2,451 @@ extern crate log;
 extern crate log4rs;
 extern crate termion;
 
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
 use std::process;
+use std::rc::Rc;
 
 use self::log::LogLevelFilter;
 use self::log4rs::append::file::*;
 use self::log4rs::config::{Appender, Config, Logger, Root};
 use self::termion::{clear, color, cursor, style};
 
+use super::super::zmachine::blorb::Blorb;
+use super::super::zmachine::graphics::DrawCommand;
+use super::super::zmachine::sound::SoundEffect;
+use super::audio::{AudioBackend, RecordingAudio, TerminalBell};
+use super::line_editor::LineEditor;
+use super::pager::Pager;
+use super::renderer::FrameRenderer;
 use super::zinterface::*;
 
-pub struct CliInterface {}
+// how wide plain output is, unless we're told otherwise
+const PLAIN_WIDTH: u16 = 80;
+
+pub struct CliInterface {
+    // the screen lines the upper window takes up, as ( top, lines ) - the
+    // lower window scrolls underneath them
+    upper_window: Cell<(u16, u16)>,
+
+    // where the transcript is going, if there is one
+    transcript: RefCell<Option<fs::File>>,
+
+    // output stream 4 and input stream 1
+    command_record: RefCell<Option<fs::File>>,
+    command_playback: RefCell<Option<BufReader<fs::File>>>,
+
+    // where version 6 draw commands go, if the player asked for frames
+    frames: RefCell<Option<FrameRenderer>>,
+
+    // what plays the story's sounds
+    audio: RefCell<Box<dyn AudioBackend>>,
+
+    // how the player types their commands, and what wraps ( and pages )
+    // what the story prints in the lower window
+    editor: RefCell<LineEditor>,
+    pager: RefCell<Pager>,
+
+    // plain output is for when there's no terminal to draw on ( or the
+    // player doesn't want one ) - it's the width of the screen, if it's on
+    //
+    // there are no escape codes at all, so nothing can be put anywhere but
+    // at the end of the text. the status line, and what's in the upper
+    // window, are printed as lines of their own before the player is asked
+    // for a command
+    plain: Cell<Option<u16>>,
+    status: RefCell<Option<String>>,
+    upper_text: RefCell<Vec<Vec<char>>>,
+    upper_shown: RefCell<Vec<String>>,
+}
+
+impl CliInterface {
+    pub fn new() -> CliInterface {
+        let interface = CliInterface {
+            upper_window: Cell::new((1, 0)),
+            transcript: RefCell::new(None),
+            command_record: RefCell::new(None),
+            command_playback: RefCell::new(None),
+            frames: RefCell::new(None),
+            audio: RefCell::new(Box::new(TerminalBell)),
+            editor: RefCell::new(LineEditor::new()),
+            pager: RefCell::new(Pager::new(is_terminal())),
+            plain: Cell::new(None),
+            status: RefCell::new(None),
+            upper_text: RefCell::new(Vec::new()),
+            upper_shown: RefCell::new(Vec::new()),
+        };
+
+        if !is_terminal() {
+            interface.use_plain_output(None);
+        }
+
+        interface
+    }
+
+    // plain output is on by itself when stdin or stdout aren't a terminal,
+    // this is for turning it on anyway ( and saying how wide it is )
+    pub fn use_plain_output(&self, width: Option<u16>) {
+        self.plain.set(Some(width.unwrap_or(PLAIN_WIDTH)));
+        self.pager.borrow_mut().hold_lines(true);
+    }
+
+    pub fn is_plain(&self) -> bool {
+        self.plain.get().is_some()
+    }
+
+    // the words tab can finish off ( the story's dictionary )
+    pub fn complete_words_from(&self, words: Vec<String>) {
+        self.editor.borrow_mut().set_words(words);
+    }
+
+    // every story gets a history of its own, kept in ~/.rusty_z/history - if
+    // there's nowhere to keep it, it only lasts until the game ends
+    pub fn keep_history_for(&self, story_id: &str) {
+        let home = match env::var_os("HOME") {
+            Some(x) => PathBuf::from(x),
+            None => return,
+        };
+
+        let path = home.join(".rusty_z").join("history").join(story_id);
+        let _ = self.editor.borrow_mut().keep_history_in(path);
+    }
+
+    // these are for the command line, which gives us the file up front
+
+    pub fn record_commands_to(&self, path: &str) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        *self.command_record.borrow_mut() = Some(file);
+
+        Ok(())
+    }
+
+    pub fn replay_commands_from(&self, path: &str) -> io::Result<()> {
+        let file = fs::File::open(path)?;
+        *self.command_playback.borrow_mut() = Some(BufReader::new(file));
+
+        Ok(())
+    }
+
+    pub fn set_audio(&self, backend: Box<dyn AudioBackend>) {
+        *self.audio.borrow_mut() = backend;
+    }
+
+    // instead of ringing the bell, every sound effect is written to path
+    pub fn log_sounds_to(&self, path: &str) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        self.set_audio(Box::new(RecordingAudio::new(Box::new(file))));
+
+        Ok(())
+    }
+
+    // version 6 stories get a screen of 8x16 pixel characters, the size of the
+    // terminal, which is drawn into frames written to directory
+    pub fn render_frames_to(&self, directory: &str) -> io::Result<()> {
+        let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+        let (width, height) = (cmp::min(width, 255), cmp::min(height, 254));
+
+        let renderer = FrameRenderer::new(directory, width * 8, height * 16, (8, 16))?;
+        *self.frames.borrow_mut() = Some(renderer);
+
+        Ok(())
+    }
+
+    // a frame is written whenever the story stops to wait for the player,
+    // so there's one for every screen they would have seen
+    fn write_frame(&self) {
+        let written = match *self.frames.borrow_mut() {
+            Some(ref mut renderer) => renderer.write_frame(),
+            None => return,
+        };
+
+        // frames aren't worth stopping the game for
+        if let Err(e) = written {
+            self.print_to_main(&format!("[could not write frame: {}]\n", e));
+            *self.frames.borrow_mut() = None;
+        }
+    }
+
+    // the next line of the playback file, if there is one - when it runs
+    // out, we go back to the keyboard
+    fn next_played_back_line(&self) -> Option<String> {
+        let mut playback = self.command_playback.borrow_mut();
+        let mut line = String::new();
+
+        let read = match *playback {
+            Some(ref mut file) => file.read_line(&mut line),
+            None => return None,
+        };
+
+        match read {
+            Ok(x) if x > 0 => Some(line),
+            _ => {
+                *playback = None;
+                None
+            }
+        }
+    }
+
+    // terminals can be told to only scroll some of their lines ( a "scrolling
+    // region" ), which is exactly what the lower window is. termion doesn't
+    // have this one, so it's the raw escape code
+    fn set_scrolling_region(&self, top: u16, bottom: u16) {
+        if !self.is_plain() {
+            print!("\x1b[{};{}r", top, bottom);
+        }
+    }
+
+    // the screen size, as ( width, height ) - plain output goes on forever
+    fn screen_size(&self) -> (u16, u16) {
+        match self.plain.get() {
+            Some(width) => (width, 255),
+            None => termion::terminal_size().unwrap_or((80, 24)),
+        }
+    }
+
+    // the lower window's width, and how many lines can go by before the
+    // player has to press a key to see more ( one less than it has, so the
+    // [MORE] can go under them )
+    fn lower_window_size(&self) -> (u16, u16) {
+        let (width, height) = self.screen_size();
+        let (top, lines) = self.upper_window.get();
+
+        // with nobody looking at a screen, there's nobody to wait for
+        if self.is_plain() {
+            return (width, 0);
+        }
+
+        let first_lower_line = cmp::max(top + lines, 1);
+        let page = (height + 1)
+            .saturating_sub(first_lower_line)
+            .saturating_sub(1);
+
+        (width, page)
+    }
+
+    // prints the word the pager is holding back, before something else
+    // moves the cursor
+    fn flush_lower_window(&self) {
+        let (width, _) = self.lower_window_size();
+        self.pager.borrow_mut().flush(width);
+    }
+
+    // give the terminal all of its lines back - plain output prints whatever
+    // the upper windows still have that wasn't shown, and finishes the line
+    // it's on, so whatever comes next starts on its own
+    fn reset_scrolling_region(&self) {
+        if !self.is_plain() {
+            print!("\x1b[r");
+        } else {
+            self.print_plain_windows();
+            self.pager.borrow_mut().release_line();
+
+            if self.pager.borrow().column() > 0 {
+                println!();
+                self.pager.borrow_mut().set_column(0);
+            }
+        }
+
+        let _ = io::stdout().flush();
+    }
+
+    // plain output can't draw the status line or the upper window where they
+    // go, so they go above the line the player types on instead - the upper
+    // window only when it's changed, since stories redraw it every turn
+    fn print_plain_windows(&self) {
+        let mut lines = String::new();
+
+        if let Some(status) = self.status.borrow_mut().take() {
+            lines.push_str(&status);
+            lines.push('\n');
+        }
+
+        let upper: Vec<String> = self
+            .upper_text
+            .borrow()
+            .iter()
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .collect();
+
+        if upper != *self.upper_shown.borrow() {
+            for line in upper.iter().filter(|line| !line.is_empty()) {
+                lines.push_str(line);
+                lines.push('\n');
+            }
+
+            *self.upper_shown.borrow_mut() = upper;
+        }
+
+        if !lines.is_empty() {
+            self.pager.borrow_mut().print_above(&lines);
+        }
+    }
+
+    // reads a line from the command record, if one's playing back, and then
+    // from the player - remember is whether it goes in their history
+    fn read_line(&self, buf: &mut String, max_length: usize, remember: bool) -> Option<usize> {
+        // the player is about to look at the screen, so the next [MORE] is
+        // a whole screen away
+        self.flush_lower_window();
+        self.pager.borrow_mut().reset_lines();
+
+        if self.is_plain() {
+            self.print_plain_windows();
+            self.pager.borrow_mut().release_line();
+        }
+
+        let read = match self.next_played_back_line() {
+            // the terminal only echoes what's actually typed
+            Some(line) => {
+                print!("{}", line);
+                buf.push_str(&line);
+                Some(line.len())
+            }
+            None => self.read_typed_line(buf, max_length, remember),
+        };
+
+        // a played back line can be too long, and so can one typed without
+        // the editor, since the terminal hands it to us all at once - we cut
+        // it down to size, so the command record has what the story actually got
+        let line = buf.trim_end_matches(|ch| ch == '\r' || ch == '\n');
+
+        if line.chars().count() > max_length {
+            *buf = line.chars().take(max_length).collect();
+        }
+
+        if let Some(ref mut file) = *self.command_record.borrow_mut() {
+            // a record that can't be written isn't worth stopping the game for
+            let _ = file.write_all(format!("{}\n", buf.trim_end()).as_bytes());
+        }
+
+        read
+    }
+
+    // the line editor needs a terminal to work with - without one ( or if it
+    // fails ), we take whatever comes in on stdin
+    fn read_typed_line(
+        &self,
+        buf: &mut String,
+        max_length: usize,
+        remember: bool,
+    ) -> Option<usize> {
+        if !self.is_plain() {
+            let (width, _) = self.screen_size();
+            let column = self.pager.borrow().column();
+
+            let read = self
+                .editor
+                .borrow_mut()
+                .read_line(column, width, max_length, remember);
+
+            match read {
+                Ok(Some(line)) => {
+                    buf.push_str(&line);
+                    buf.push('\n');
+                    self.pager.borrow_mut().set_column(0);
+
+                    return Some(buf.len());
+                }
+                // ctrl-c
+                Ok(None) => self.quit(),
+                Err(_) => (),
+            }
+        }
+
+        let read = match io::stdin().read_line(buf) {
+            // the end of a script - nobody's left to type anything, so
+            // there's no point waiting for them
+            Ok(0) => {
+                self.quit();
+                None
+            }
+            Ok(x) => Some(x),
+            //discard the error
+            Err(_) => None,
+        };
+
+        // nothing echoes what came in through a pipe, and the output reads
+        // better with the commands in it
+        if !termion::is_tty(&io::stdin()) {
+            println!("{}", buf.trim_end());
+            self.pager.borrow_mut().set_column(0);
+        }
+
+        read
+    }
+
+    // asks the player for a file name, falling back to the default
+    // if they just hit enter
+    fn prompt_for_file(&self, default: &str) -> Option<String> {
+        self.print_to_main(&format!("Enter a file name (default is \"{}\"): ", default));
+
+        if io::stdout().flush().is_err() {
+            return None;
+        }
+
+        // file names aren't going into the story's memory, so they can be
+        // as long as they like
+        let mut name = String::new();
+        self.read_line(&mut name, usize::MAX, false)?;
+
+        match name.trim() {
+            "" => Some(default.to_string()),
+            x => Some(x.to_string()),
+        }
+    }
+}
 
 impl ZInterface for CliInterface {
     fn clear(&self) {
+        if self.is_plain() {
+            return;
+        }
+
+        self.flush_lower_window();
         println!("{}", clear::All);
+        self.pager.borrow_mut().set_column(0);
     }
 
     fn print_to_main(&self, str: &str) {
-        print!("{}", str);
+        let (width, page) = self.lower_window_size();
+
+        // ctrl-c at a [MORE]
+        if !self.pager.borrow_mut().print(str, width, page) {
+            self.quit();
+        }
+    }
+
+    fn buffer_mode(&self, buffered: bool) {
+        let (width, _) = self.lower_window_size();
+        self.pager.borrow_mut().set_buffered(buffered, width);
     }
 
     fn print_to_header(&self, left_side: &str, right_side: &str) {
-        //terminals start at 1,1 so, keep that in mind
-        //this could panic... but if it can't get the terminal size,
-        //there's a good reason to
-        let (x, y) = termion::terminal_size().unwrap();
+        let (x, _) = self.screen_size();
+        let sides = left_side.chars().count() + right_side.chars().count();
 
+        // the sides as far apart as they go, with at least a couple of
+        // spaces between them
+        if self.is_plain() {
+            let padding = " ".repeat(cmp::max((x as usize).saturating_sub(sides), 2));
+            *self.status.borrow_mut() = Some(format!("{}{}{}", left_side, padding, right_side));
+            return;
+        }
+
+        //terminals start at 1,1 so, keep that in mind
         let top_left = cursor::Goto(1, 1);
         //padding is 4 chars
         let margin_padding = "    ";
-        let center_size = (x as usize) - left_side.len() - right_side.len() - 4 * 2;
+        let center_size = (x as usize).saturating_sub(sides + 4 * 2);
         let center_padding: String = (0..center_size).into_iter().map(|_| " ").collect();
-        let bottom = cursor::Goto(2, y);
 
         let header = format!(
-            "{}{}{}{}{}{}{}{}{}{}{}{}",
+            "{}{}{}{}{}{}{}{}{}{}{}",
             cursor::Goto(1, 1),
             color::Bg(color::LightWhite),
             color::Fg(color::Black),
@@ -47,22 +457,288 @@ impl ZInterface for CliInterface {
             center_padding,
             right_side,
             margin_padding,
-            style::Reset,
-            bottom
+            style::Reset
+        );
+
+        // the status line is outside of the lower window's scrolling region,
+        // so it stays put - and the cursor goes back to where the story's
+        // text had got to
+        self.flush_lower_window();
+        print!("{}{}{}", cursor::Save, header, cursor::Restore);
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        if let Some(ref renderer) = *self.frames.borrow() {
+            let (font_width, font_height) = renderer.font_size();
+
+            return Capabilities {
+                interpreter_number: 6,
+                status_line: true,
+                split_screen: true,
+                variable_pitch: false,
+                screen_width: (renderer.width() / font_width) as u8,
+                screen_height: (renderer.height() / font_height) as u8,
+                font_width: font_width as u8,
+                font_height: font_height as u8,
+                background: 2,
+                foreground: 9,
+                graphics: true,
+                sound: true,
+            };
+        }
+
+        let (width, height) = self.screen_size();
+
+        Capabilities {
+            interpreter_number: 6,
+            status_line: true,
+            split_screen: true,
+            variable_pitch: false,
+            // the header only has a byte for each, and a height of 255 means
+            // the screen never runs out
+            screen_width: cmp::min(width, 255) as u8,
+            screen_height: cmp::min(height, 255) as u8,
+            font_width: 1,
+            font_height: 1,
+            background: 2,
+            foreground: 9,
+            graphics: false,
+            sound: true,
+        }
+    }
+
+    fn split_window(&self, top: u16, lines: u16) {
+        self.upper_window.set((top, lines));
+
+        if self.is_plain() {
+            let (width, _) = self.screen_size();
+            self.upper_text
+                .borrow_mut()
+                .resize(lines as usize, vec![' '; width as usize]);
+            return;
+        }
+
+        let (_, height) = self.screen_size();
+
+        // setting the region moves the cursor to the top of the screen, so
+        // we put it back at the bottom, where the lower window prints
+        let first_lower_line = cmp::min(top + lines, height);
+        self.set_scrolling_region(first_lower_line, height);
+        self.flush_lower_window();
+        print!("{}", cursor::Goto(1, height));
+        self.pager.borrow_mut().set_column(0);
+    }
+
+    fn print_to_upper(&self, line: u16, column: u16, text: &str) {
+        if self.is_plain() {
+            let (top, _) = self.upper_window.get();
+            let mut upper = self.upper_text.borrow_mut();
+
+            // anything off the edge is lost, like it would be on a screen
+            if let Some(row) = line
+                .checked_sub(top)
+                .and_then(|i| upper.get_mut(i as usize))
+            {
+                let start = column.saturating_sub(1) as usize;
+
+                for (cell, ch) in row.iter_mut().skip(start).zip(text.chars()) {
+                    *cell = ch;
+                }
+            }
+
+            return;
+        }
+
+        self.flush_lower_window();
+
+        print!(
+            "{}{}{}{}",
+            cursor::Save,
+            cursor::Goto(column, line),
+            text,
+            cursor::Restore
         );
+    }
 
-        print!("{}", header);
+    fn erase_window(&self, window: Window) {
+        // there's no taking back text that's already been printed
+        if self.is_plain() {
+            if let Window::Upper = window {
+                for row in self.upper_text.borrow_mut().iter_mut() {
+                    row.iter_mut().for_each(|cell| *cell = ' ');
+                }
+            }
+
+            return;
+        }
+
+        let (top, lines) = self.upper_window.get();
+        let (_, height) = self.screen_size();
+
+        let (first, last) = match window {
+            Window::Upper => (top, top + lines),
+            Window::Lower => (top + lines, height + 1),
+        };
+
+        self.flush_lower_window();
+        print!("{}", cursor::Save);
+
+        for line in first..last {
+            print!("{}{}", cursor::Goto(1, line), clear::CurrentLine);
+        }
+
+        print!("{}", cursor::Restore);
+
+        // the lower window starts again from its first line
+        if let Window::Lower = window {
+            print!("{}", cursor::Goto(1, cmp::min(top + lines, height)));
+            self.pager.borrow_mut().set_column(0);
+        }
     }
 
-    fn read_next_line(&self, buf: &mut String) -> Option<usize> {
-        match std::io::stdin().read_line(buf) {
+    fn draw(&self, command: &DrawCommand) {
+        if let Some(ref mut renderer) = *self.frames.borrow_mut() {
+            renderer.draw(command);
+        }
+    }
+
+    // the terminal doesn't tell us about the mouse
+    fn read_mouse(&self) -> (u16, u16, u16) {
+        (0, 0, 0)
+    }
+
+    fn sound_effect(&self, effect: &SoundEffect) {
+        self.audio.borrow_mut().sound_effect(effect);
+    }
+
+    fn sound_playing(&self, number: u16) -> bool {
+        self.audio.borrow().is_playing(number)
+    }
+
+    // a terminal can't show pictures - the frames only need their sizes,
+    // which come with the draw commands
+    fn load_resources(&self, _: &Rc<Blorb>) {}
+
+    fn start_transcript(&self) -> bool {
+        let name = match self.prompt_for_file("transcript.txt") {
+            Some(x) => x,
+            None => return false,
+        };
+
+        // a transcript carries on from where the last one left off
+        let file = fs::OpenOptions::new().create(true).append(true).open(&name);
+
+        match file {
+            Ok(x) => {
+                *self.transcript.borrow_mut() = Some(x);
+                true
+            }
+            Err(e) => {
+                self.print_to_main(&format!("Could not open {}: {}\n", name, e));
+                false
+            }
+        }
+    }
+
+    fn print_to_transcript(&self, text: &str) {
+        let failed = match *self.transcript.borrow_mut() {
+            Some(ref mut file) => file.write_all(text.as_bytes()).is_err(),
+            None => false,
+        };
+
+        // if the file went away, there's no point trying again every line
+        if failed {
+            self.stop_transcript();
+        }
+    }
+
+    fn stop_transcript(&self) {
+        *self.transcript.borrow_mut() = None;
+    }
+
+    fn start_command_record(&self) -> bool {
+        // --record already gave us somewhere
+        if self.command_record.borrow().is_some() {
+            return true;
+        }
+
+        let name = match self.prompt_for_file("commands.rec") {
+            Some(x) => x,
+            None => return false,
+        };
+
+        match self.record_commands_to(&name) {
+            Ok(_) => true,
+            Err(e) => {
+                self.print_to_main(&format!("Could not write {}: {}\n", name, e));
+                false
+            }
+        }
+    }
+
+    fn stop_command_record(&self) {
+        *self.command_record.borrow_mut() = None;
+    }
+
+    fn start_command_playback(&self) -> bool {
+        if self.command_playback.borrow().is_some() {
+            return true;
+        }
+
+        let name = match self.prompt_for_file("commands.rec") {
+            Some(x) => x,
+            None => return false,
+        };
+
+        match self.replay_commands_from(&name) {
+            Ok(_) => true,
+            Err(e) => {
+                self.print_to_main(&format!("Could not read {}: {}\n", name, e));
+                false
+            }
+        }
+    }
+
+    fn stop_command_playback(&self) {
+        *self.command_playback.borrow_mut() = None;
+    }
+
+    fn read_next_line(&self, buf: &mut String, max_length: usize) -> Option<usize> {
+        self.write_frame();
+        self.read_line(buf, max_length, true)
+    }
+
+    fn save(&self, data: &[u8]) -> bool {
+        let name = match self.prompt_for_file("save.qzl") {
+            Some(x) => x,
+            None => return false,
+        };
+
+        match fs::write(&name, data) {
+            Ok(_) => true,
+            Err(e) => {
+                self.print_to_main(&format!("Could not write {}: {}\n", name, e));
+                false
+            }
+        }
+    }
+
+    fn restore(&self) -> Option<Vec<u8>> {
+        let name = self.prompt_for_file("save.qzl")?;
+
+        match fs::read(&name) {
             Ok(x) => Some(x),
-            //discard the error
-            Err(_) => None,
+            Err(e) => {
+                self.print_to_main(&format!("Could not read {}: {}\n", name, e));
+                None
+            }
         }
     }
 
     fn quit(&self) {
+        self.flush_lower_window();
+        self.write_frame();
+        self.reset_scrolling_region();
         process::exit(0);
     }
 
@@ -97,12 +773,21 @@ impl ZInterface for CliInterface {
 
             match result {
                 LoopState::Quitting => break,
-                LoopState::Restarting => break,
                 LoopState::Error => break,
                 LoopState::Running => (),
             }
         }
 
+        self.flush_lower_window();
+        self.reset_scrolling_region();
+
         return result;
     }
 }
+
+// both ends have to be a terminal for the line editor and the escape codes -
+// if either is a pipe ( or a file ), something other than a person is
+// probably on the other end
+fn is_terminal() -> bool {
+    termion::is_tty(&io::stdin()) && termion::is_tty(&io::stdout())
+}
@@ -0,0 +1,594 @@
+extern crate termion;
+
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use self::termion::event::Key;
+use self::termion::input::TermRead;
+use self::termion::raw::IntoRawMode;
+use self::termion::{clear, cursor};
+
+// how many commands the history keeps, in memory and in its file
+const HISTORY_LENGTH: usize = 1000;
+
+// reads a line from the terminal a key at a time ( in raw mode ), so the
+// player can move around in it and change it, go back through the commands
+// they've typed before, and have words finished off for them
+//
+// the keys are mostly the usual readline ones:
+//
+// left/right ( ctrl-b/ctrl-f ), home/end ( ctrl-a/ctrl-e ): move the cursor
+// backspace, delete ( ctrl-d ): delete a character
+// ctrl-k, ctrl-u, ctrl-w: delete to the end, to the start, or the word before
+// up/down ( ctrl-p/ctrl-n ): go through the history
+// ctrl-r: search back through the history for what's typed next ( ctrl-r
+//         again finds an older match, ctrl-g or escape gives up )
+// tab: finish off the word before the cursor from the story's dictionary -
+//      if there's more than one word it could be, pressing it again goes
+//      through them
+//
+// the line never wraps - if it's too long for the screen, it scrolls
+// sideways to keep the cursor in view
+pub struct LineEditor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+
+    // what tab can finish words off with, sorted
+    words: Vec<String>,
+}
+
+// ctrl-r: what's being searched for, which history entry matched it, and
+// the line from before the search, to go back to if it's given up on
+struct Search {
+    query: String,
+    found: Option<usize>,
+    saved: Vec<char>,
+}
+
+// tab being pressed over and over: where the word starts, and the words it
+// could be, with the one that's in the line now
+struct Completion {
+    start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor {
+            history: Vec::new(),
+            history_file: None,
+            words: Vec::new(),
+        }
+    }
+
+    // only proper words are worth finishing off - dictionaries also have
+    // punctuation, and the odd entry that can't be typed at all
+    pub fn set_words(&mut self, mut words: Vec<String>) {
+        words.retain(|word| {
+            word.len() > 1
+                && word.chars().next().map_or(false, |ch| ch.is_alphabetic())
+                && word.chars().all(|ch| ch.is_alphanumeric() || ch == '-')
+        });
+        words.sort();
+        words.dedup();
+
+        self.words = words;
+    }
+
+    // loads the history from path, and adds every command to it from now on
+    pub fn keep_history_in(&mut self, path: PathBuf) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let mut history = Vec::new();
+
+        if path.exists() {
+            for line in BufReader::new(fs::File::open(&path)?).lines() {
+                history.push(line?);
+            }
+        }
+
+        // the file only ever gets added to, so this is where it gets cut
+        // back down to size
+        if history.len() > HISTORY_LENGTH {
+            history.drain(..history.len() - HISTORY_LENGTH);
+
+            let mut contents = history.join("\n");
+            contents.push('\n');
+            fs::write(&path, contents)?;
+        }
+
+        self.history = history;
+        self.history_file = Some(path);
+
+        Ok(())
+    }
+
+    // reads a line, with column characters already on the screen line in
+    // front of it. remember is whether it goes in the history
+    //
+    // returns None if the player pressed ctrl-c
+    pub fn read_line(
+        &mut self,
+        column: u16,
+        width: u16,
+        max_length: usize,
+        remember: bool,
+    ) -> io::Result<Option<String>> {
+        let mut out = io::stdout().into_raw_mode()?;
+        let stdin = io::stdin();
+        let mut keys = stdin.lock().keys();
+
+        let (column, width) = (column as usize, width as usize);
+
+        let mut line: Vec<char> = Vec::new();
+        let mut position = 0;
+
+        // the first character on the screen, when the line is scrolled
+        let mut scroll = 0;
+
+        // which history entry we're on - one past the end is the line being
+        // typed, which is kept in draft while we're looking at the others
+        let mut entry = self.history.len();
+        let mut draft: Vec<char> = Vec::new();
+
+        let mut search: Option<Search> = None;
+        let mut completion: Option<Completion> = None;
+
+        self.draw(&mut out, column, width, &line, position, &mut scroll)?;
+
+        while let Some(key) = keys.next() {
+            let key = key?;
+
+            if let Some(mut current) = search.take() {
+                let still_searching = match key {
+                    Key::Char(ch) if !ch.is_control() => {
+                        current.query.push(ch);
+                        current.found = self.search_back(&current.query, self.history.len());
+                        true
+                    }
+                    Key::Backspace => {
+                        current.query.pop();
+                        current.found = self.search_back(&current.query, self.history.len());
+                        true
+                    }
+                    Key::Ctrl('r') => {
+                        let from = current.found.unwrap_or(self.history.len());
+                        current.found = self.search_back(&current.query, from).or(current.found);
+                        true
+                    }
+                    Key::Ctrl('g') | Key::Esc => {
+                        line = current.saved.clone();
+                        position = line.len();
+                        self.draw(&mut out, column, width, &line, position, &mut scroll)?;
+                        continue;
+                    }
+                    // anything else takes what was found, and carries on as normal
+                    _ => {
+                        draft = current.saved.clone();
+
+                        line = match current.found {
+                            Some(found) => {
+                                entry = found;
+                                self.history[found].chars().take(max_length).collect()
+                            }
+                            None => current.saved.clone(),
+                        };
+                        position = line.len();
+
+                        false
+                    }
+                };
+
+                if still_searching {
+                    self.draw_search(&mut out, column, width, &current)?;
+                    search = Some(current);
+                    continue;
+                }
+            }
+
+            // only tab again keeps going through the same words
+            let last_completion = completion.take();
+
+            match key {
+                Key::Char('\n') => break,
+                Key::Char('\t') => {
+                    completion =
+                        self.complete(&mut line, &mut position, last_completion, max_length);
+
+                    if completion.is_none() {
+                        write!(out, "\x07")?;
+                    }
+                }
+                Key::Char(ch) if !ch.is_control() => {
+                    if line.len() < max_length {
+                        line.insert(position, ch);
+                        position += 1;
+                    } else {
+                        write!(out, "\x07")?;
+                    }
+                }
+                Key::Backspace | Key::Ctrl('h') if position > 0 => {
+                    position -= 1;
+                    line.remove(position);
+                }
+                Key::Delete | Key::Ctrl('d') if position < line.len() => {
+                    line.remove(position);
+                }
+                Key::Left | Key::Ctrl('b') if position > 0 => position -= 1,
+                Key::Right | Key::Ctrl('f') if position < line.len() => position += 1,
+                Key::Home | Key::Ctrl('a') => position = 0,
+                Key::End | Key::Ctrl('e') => position = line.len(),
+                Key::Ctrl('k') => line.truncate(position),
+                Key::Ctrl('u') => {
+                    line.drain(..position);
+                    position = 0;
+                }
+                Key::Ctrl('w') => {
+                    let mut start = position;
+
+                    while start > 0 && line[start - 1] == ' ' {
+                        start -= 1;
+                    }
+
+                    while start > 0 && line[start - 1] != ' ' {
+                        start -= 1;
+                    }
+
+                    line.drain(start..position);
+                    position = start;
+                }
+                Key::Up | Key::Ctrl('p') if entry > 0 => {
+                    if entry == self.history.len() {
+                        draft = line.clone();
+                    }
+
+                    entry -= 1;
+                    line = self.history[entry].chars().take(max_length).collect();
+                    position = line.len();
+                }
+                Key::Down | Key::Ctrl('n') if entry < self.history.len() => {
+                    entry += 1;
+
+                    line = match self.history.get(entry) {
+                        Some(x) => x.chars().take(max_length).collect(),
+                        None => draft.clone(),
+                    };
+                    position = line.len();
+                }
+                Key::Ctrl('r') => {
+                    let current = Search {
+                        query: String::new(),
+                        found: None,
+                        saved: line.clone(),
+                    };
+
+                    self.draw_search(&mut out, column, width, &current)?;
+                    search = Some(current);
+                    continue;
+                }
+                Key::Ctrl('c') => {
+                    write!(out, "\r\n")?;
+                    return Ok(None);
+                }
+                _ => (),
+            }
+
+            self.draw(&mut out, column, width, &line, position, &mut scroll)?;
+        }
+
+        // the whole line goes back on the screen, as it's what the player
+        // will expect to see above the story's reply
+        self.draw(&mut out, column, width, &line, line.len(), &mut 0)?;
+        write!(out, "\r\n")?;
+        out.flush()?;
+
+        let line: String = line.into_iter().collect();
+
+        if remember {
+            self.remember(&line);
+        }
+
+        Ok(Some(line))
+    }
+
+    // puts the line on the screen after column, from the character at scroll,
+    // moving scroll along if it has to so the cursor ( at position ) can be seen
+    fn draw<W: Write>(
+        &self,
+        out: &mut W,
+        column: usize,
+        width: usize,
+        line: &[char],
+        position: usize,
+        scroll: &mut usize,
+    ) -> io::Result<()> {
+        // the last column is left free for the cursor
+        let room = match width.saturating_sub(column + 1) {
+            0 => 1,
+            x => x,
+        };
+
+        if position < *scroll {
+            *scroll = position;
+        } else if position >= *scroll + room {
+            *scroll = position + 1 - room;
+        }
+
+        let shown: String = line.iter().skip(*scroll).take(room).collect();
+
+        write!(out, "\r")?;
+        move_right(out, column)?;
+        write!(out, "{}{}\r", shown, clear::UntilNewline)?;
+        move_right(out, column + position - *scroll)?;
+
+        out.flush()
+    }
+
+    // what's being searched for, then what it matched
+    fn draw_search<W: Write>(
+        &self,
+        out: &mut W,
+        column: usize,
+        width: usize,
+        search: &Search,
+    ) -> io::Result<()> {
+        let prompt = format!("(search)'{}': ", search.query);
+        let found = search.found.map_or("", |x| self.history[x].as_str());
+        let shown: Vec<char> = prompt.chars().chain(found.chars()).collect();
+
+        self.draw(out, column, width, &shown, prompt.chars().count(), &mut 0)
+    }
+
+    // the newest history entry before from that has query in it
+    fn search_back(&self, query: &str, from: usize) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+
+        self.history[..from]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    // finishes off the word before the cursor, and says what it did if tab
+    // should go on to the next word it could be ( or None if it did nothing )
+    fn complete(
+        &self,
+        line: &mut Vec<char>,
+        position: &mut usize,
+        last: Option<Completion>,
+        max_length: usize,
+    ) -> Option<Completion> {
+        let replace = |line: &mut Vec<char>, position: &mut usize, start: usize, word: &str| {
+            let word: Vec<char> = word.chars().collect();
+
+            if line.len() - (*position - start) + word.len() > max_length {
+                return false;
+            }
+
+            line.splice(start..*position, word.iter().cloned());
+            *position = start + word.len();
+
+            true
+        };
+
+        // tab again goes on to the next word
+        if let Some(mut completion) = last {
+            completion.index = (completion.index + 1) % completion.candidates.len();
+            let word = completion.candidates[completion.index].clone();
+
+            return match replace(line, position, completion.start, &word) {
+                true => Some(completion),
+                false => None,
+            };
+        }
+
+        let mut start = *position;
+
+        while start > 0 && (line[start - 1].is_alphanumeric() || line[start - 1] == '-') {
+            start -= 1;
+        }
+
+        // every word in the dictionary isn't much help
+        if start == *position {
+            return None;
+        }
+
+        let prefix: String = line[start..*position]
+            .iter()
+            .collect::<String>()
+            .to_lowercase();
+
+        let candidates: Vec<String> = self
+            .words
+            .iter()
+            .filter(|word| word.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        match candidates.len() {
+            0 => None,
+            // there's only the one, so the next thing typed is a new word
+            1 => {
+                let word = format!("{} ", candidates[0]);
+
+                match replace(line, position, start, &word) {
+                    true => Some(Completion {
+                        start,
+                        candidates: vec![word],
+                        index: 0,
+                    }),
+                    false => None,
+                }
+            }
+            _ => {
+                // as much as the words all have in common, and then
+                // going through them if that's all that's typed already
+                let common = candidates[1..]
+                    .iter()
+                    .fold(candidates[0].clone(), |common, word| {
+                        common
+                            .chars()
+                            .zip(word.chars())
+                            .take_while(|&(a, b)| a == b)
+                            .map(|(a, _)| a)
+                            .collect()
+                    });
+
+                let word = match common.len() > prefix.len() {
+                    true => common,
+                    false => candidates[0].clone(),
+                };
+
+                // if what's in the line isn't one of the words, the next tab
+                // goes to the first of them
+                let index = candidates
+                    .iter()
+                    .position(|x| *x == word)
+                    .unwrap_or(candidates.len() - 1);
+
+                match replace(line, position, start, &word) {
+                    true => Some(Completion {
+                        start,
+                        candidates,
+                        index,
+                    }),
+                    false => None,
+                }
+            }
+        }
+    }
+
+    // adds a command to the history ( and its file ), unless it's the same
+    // as the last one
+    fn remember(&mut self, line: &str) {
+        let line = line.trim();
+
+        if line.is_empty() || self.history.last().map_or(false, |x| x == line) {
+            return;
+        }
+
+        self.history.push(line.to_string());
+
+        if self.history.len() > HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+
+        let file = match self.history_file {
+            Some(ref path) => fs::OpenOptions::new().create(true).append(true).open(path),
+            None => return,
+        };
+
+        // a history that can't be written isn't worth stopping the game for
+        if let Ok(mut file) = file {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+// termion's cursor::Right(0) still moves one column on most terminals
+fn move_right<W: Write>(out: &mut W, columns: usize) -> io::Result<()> {
+    match columns {
+        0 => Ok(()),
+        x => write!(out, "{}", cursor::Right(x as u16)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(words: &[&str], history: &[&str]) -> LineEditor {
+        let mut editor = LineEditor::new();
+        editor.set_words(words.iter().map(|x| x.to_string()).collect());
+        editor.history = history.iter().map(|x| x.to_string()).collect();
+        editor
+    }
+
+    // presses tab with the cursor at the end of line, tabs times over
+    fn tab(editor: &LineEditor, line: &str, tabs: usize) -> Option<String> {
+        let mut line: Vec<char> = line.chars().collect();
+        let mut position = line.len();
+        let mut completion = None;
+
+        for _ in 0..tabs {
+            completion = editor.complete(&mut line, &mut position, completion, 20);
+            completion.as_ref()?;
+        }
+
+        Some(line.into_iter().collect())
+    }
+
+    #[test]
+    fn only_proper_words_are_kept() {
+        let editor = editor(
+            &["take", ",", "a", "lamp", "take", "x-ray", "2nd", "t'bag"],
+            &[],
+        );
+
+        assert_eq!(editor.words, vec!["lamp", "take", "x-ray"]);
+    }
+
+    #[test]
+    fn one_word_is_finished_off_with_a_space() {
+        let editor = editor(&["lamp", "lantern", "sword"], &[]);
+
+        assert_eq!(tab(&editor, "get Sw", 1), Some("get sword ".to_string()));
+        assert_eq!(tab(&editor, "get x", 1), None);
+        assert_eq!(tab(&editor, "get ", 1), None);
+    }
+
+    #[test]
+    fn tab_again_goes_through_the_words() {
+        let editor = editor(&["lamp", "lantern", "sword"], &[]);
+
+        // "la" is all they have in common, so the first tab goes to "lamp"
+        assert_eq!(tab(&editor, "get la", 1), Some("get lamp".to_string()));
+        assert_eq!(tab(&editor, "get la", 2), Some("get lantern".to_string()));
+        assert_eq!(tab(&editor, "get la", 3), Some("get lamp".to_string()));
+    }
+
+    #[test]
+    fn tab_goes_as_far_as_the_words_have_in_common_first() {
+        // with nothing more in common, it's straight to the first word
+        let editor = editor(&["lantern", "lanterns", "leaflet"], &[]);
+
+        assert_eq!(tab(&editor, "l", 1), Some("lantern".to_string()));
+        assert_eq!(tab(&editor, "l", 2), Some("lanterns".to_string()));
+
+        // "lant" isn't a word, so the next tab is the first one
+        let editor = self::editor(&["lantern", "lanthanum"], &[]);
+
+        assert_eq!(tab(&editor, "la", 1), Some("lant".to_string()));
+        assert_eq!(tab(&editor, "la", 2), Some("lantern".to_string()));
+        assert_eq!(tab(&editor, "la", 3), Some("lanthanum".to_string()));
+    }
+
+    #[test]
+    fn words_that_dont_fit_arent_finished() {
+        let editor = editor(&["lantern"], &[]);
+        let mut line: Vec<char> = "get la".chars().collect();
+        let mut position = line.len();
+
+        assert!(editor
+            .complete(&mut line, &mut position, None, 10)
+            .is_none());
+        assert_eq!(position, 6);
+    }
+
+    #[test]
+    fn search_finds_the_newest_match_first() {
+        let editor = editor(&[], &["take lamp", "north", "drop lamp", "south"]);
+
+        assert_eq!(editor.search_back("lamp", 4), Some(2));
+        assert_eq!(editor.search_back("lamp", 2), Some(0));
+        assert_eq!(editor.search_back("lamp", 0), None);
+        assert_eq!(editor.search_back("xyzzy", 4), None);
+        assert_eq!(editor.search_back("", 4), None);
+    }
+}
@@ -1,7 +1,13 @@
+pub mod audio;
+pub mod renderer;
 pub mod zinterface;
 
 #[cfg(not(target_os = "emscripten"))]
 pub mod cli;
+#[cfg(not(target_os = "emscripten"))]
+pub mod line_editor;
+#[cfg(not(target_os = "emscripten"))]
+pub mod pager;
 
 #[cfg(target_os = "emscripten")]
 pub mod web;
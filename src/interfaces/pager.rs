@@ -0,0 +1,303 @@
+extern crate termion;
+
+use std::io;
+use std::io::Write;
+use std::mem;
+
+use self::termion::event::Key;
+use self::termion::input::TermRead;
+use self::termion::raw::IntoRawMode;
+use self::termion::{clear, style};
+
+// everything the cli prints to the lower window goes through here, to be
+// word wrapped to the width of the terminal and stopped with a [MORE] prompt
+// whenever a screenful has gone by since the player last looked
+//
+// buffer_mode says whether the story wants its text wrapped ( and it's on
+// unless the story says otherwise ) - while it's on, a word is held back
+// until we know whether it fits on the line. while it's off, characters go
+// straight out, and just carry on onto the next line at the edge
+pub struct Pager {
+    buffered: bool,
+
+    // the word being held back
+    word: String,
+
+    // how far along its line the cursor is
+    column: u16,
+
+    // lines printed since the player last typed ( or got past a [MORE] )
+    lines: u16,
+
+    // there's nobody to press a key without a terminal
+    paging: bool,
+
+    // with plain output, the line being written is held back until it's
+    // finished, so lines can still be printed before it ( see print_above )
+    holding: bool,
+    line: String,
+}
+
+impl Pager {
+    pub fn new(paging: bool) -> Pager {
+        Pager {
+            buffered: true,
+            word: String::new(),
+            column: 0,
+            lines: 0,
+            paging,
+            holding: false,
+            line: String::new(),
+        }
+    }
+
+    pub fn hold_lines(&mut self, holding: bool) {
+        self.holding = holding;
+    }
+
+    pub fn column(&self) -> u16 {
+        self.column
+    }
+
+    // for when the cursor has been moved somewhere else
+    pub fn set_column(&mut self, column: u16) {
+        self.column = column;
+    }
+
+    pub fn set_buffered(&mut self, buffered: bool, width: u16) {
+        self.flush(width);
+        self.buffered = buffered;
+    }
+
+    // the player has seen everything up to here
+    pub fn reset_lines(&mut self) {
+        self.lines = 0;
+    }
+
+    // prints text into a window width characters wide and page lines high -
+    // returns false if the player pressed ctrl-c at a [MORE]
+    pub fn print(&mut self, text: &str, width: u16, page: u16) -> bool {
+        for ch in text.chars() {
+            let carry_on = match ch {
+                '\n' => self.write_word(width, page) && self.new_line(page),
+                ' ' if self.buffered => {
+                    if !self.write_word(width, page) {
+                        return false;
+                    }
+
+                    // a space at the end of a line is as good as a new line
+                    if self.column >= width {
+                        self.new_line(page)
+                    } else {
+                        self.write(" ");
+                        self.column += 1;
+                        true
+                    }
+                }
+                _ if self.buffered => {
+                    self.word.push(ch);
+                    true
+                }
+                _ => {
+                    let carry_on = match self.column >= width {
+                        true => self.new_line(page),
+                        false => true,
+                    };
+
+                    self.write(&ch.to_string());
+                    self.column += 1;
+                    carry_on
+                }
+            };
+
+            if !carry_on {
+                return false;
+            }
+
+            // a word can't wrap if it's longer than the line, so it's broken
+            // wherever the edge is
+            if self.word.chars().count() >= width as usize && width > 0 {
+                if !self.write_word(width, page) {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    // prints whole lines before the one being held back ( without holding,
+    // anything already on the line pushes them under it instead )
+    pub fn print_above(&mut self, lines: &str) {
+        if !self.holding && self.column > 0 {
+            println!();
+            self.column = 0;
+        }
+
+        print!("{}", lines);
+    }
+
+    // prints the line being held back, for when the player is about to type
+    // on the end of it
+    pub fn release_line(&mut self) {
+        print!("{}", self.line);
+        self.line.clear();
+    }
+
+    // prints the word being held back, before something else goes on the
+    // screen ( or the player is asked for something )
+    pub fn flush(&mut self, width: u16) {
+        self.write_word(width, 0);
+    }
+
+    // prints the word being held back - on the next line, if there isn't
+    // room for it on this one
+    fn write_word(&mut self, width: u16, page: u16) -> bool {
+        if self.word.is_empty() {
+            return true;
+        }
+
+        let length = self.word.chars().count() as u16;
+
+        if self.column > 0 && self.column + length > width && !self.new_line(page) {
+            return false;
+        }
+
+        let word = mem::take(&mut self.word);
+        self.write(&word);
+        self.column += length;
+
+        true
+    }
+
+    fn write(&mut self, text: &str) {
+        match self.holding {
+            true => self.line.push_str(text),
+            false => print!("{}", text),
+        }
+    }
+
+    // a page of 0 never stops
+    fn new_line(&mut self, page: u16) -> bool {
+        self.release_line();
+        println!();
+        self.column = 0;
+        self.lines += 1;
+
+        if self.paging && page > 0 && self.lines >= page {
+            self.lines = 0;
+            return self.more();
+        }
+
+        true
+    }
+
+    // waits for a key, and then takes the prompt away again
+    fn more(&mut self) -> bool {
+        print!("{}[MORE]{}", style::Invert, style::Reset);
+
+        let key = match io::stdout().into_raw_mode() {
+            Ok(out) => {
+                let _ = io::stdout().flush();
+                let key = io::stdin().keys().next();
+                drop(out);
+                key
+            }
+            // if we can't wait for a key, we can't page at all
+            Err(_) => {
+                self.paging = false;
+                None
+            }
+        };
+
+        print!("\r{}", clear::CurrentLine);
+        let _ = io::stdout().flush();
+
+        match key {
+            Some(Ok(Key::Ctrl('c'))) => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // holding the line keeps what's on it where we can see it ( everything
+    // before it has gone out to stdout )
+    fn pager() -> Pager {
+        let mut pager = Pager::new(false);
+        pager.hold_lines(true);
+        pager
+    }
+
+    #[test]
+    fn words_that_dont_fit_go_on_the_next_line() {
+        let mut pager = pager();
+
+        assert!(pager.print("hello there", 8, 0));
+        assert_eq!(
+            (pager.line.as_str(), pager.word.as_str()),
+            ("hello ", "there")
+        );
+
+        pager.flush(8);
+        assert_eq!(pager.line, "there");
+        assert_eq!((pager.column, pager.lines), (5, 1));
+    }
+
+    #[test]
+    fn a_space_at_the_edge_is_the_new_line() {
+        let mut pager = pager();
+
+        assert!(pager.print("abc efg hij", 7, 0));
+        pager.flush(7);
+
+        // "abc efg" fills the first line - the space after it doesn't leave
+        // a blank line, or start the next one
+        assert_eq!(pager.line, "hij");
+        assert_eq!((pager.column, pager.lines), (3, 1));
+    }
+
+    #[test]
+    fn long_words_are_broken_at_the_edge() {
+        let mut pager = pager();
+
+        assert!(pager.print("abcdefghij", 4, 0));
+        assert_eq!((pager.line.as_str(), pager.word.as_str()), ("efgh", "ij"));
+
+        pager.flush(4);
+        assert_eq!(pager.line, "ij");
+        assert_eq!(pager.lines, 2);
+    }
+
+    #[test]
+    fn unbuffered_text_goes_straight_out() {
+        let mut pager = pager();
+        pager.set_buffered(false, 4);
+
+        assert!(pager.print("ab cdef", 4, 0));
+
+        // nothing is held back, it just carries on at the edge
+        assert!(pager.word.is_empty());
+        assert_eq!(pager.line, "def");
+        assert_eq!((pager.column, pager.lines), (3, 1));
+    }
+
+    #[test]
+    fn lines_are_counted_until_the_player_sees_them() {
+        let mut pager = pager();
+
+        assert!(pager.print("one\ntwo\nthree\n", 80, 2));
+        assert_eq!(pager.lines, 3);
+
+        // a page of 0 never stops, even with somebody to press a key
+        pager.paging = true;
+        assert!(pager.print("four\nfive\n", 80, 0));
+        assert_eq!(pager.lines, 5);
+
+        pager.reset_lines();
+        assert_eq!(pager.lines, 0);
+    }
+}
@@ -0,0 +1,346 @@
+use super::super::zmachine::graphics::DrawCommand;
+
+use std::cmp;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// draws version 6 draw commands into an image, and writes it out as a ppm
+// file ( about the simplest image format there is, and one most viewers can
+// open ) whenever something has changed - this is for checking what a story
+// puts on the screen without needing a display
+//
+// we don't have any fonts, so text is "greeked": each character is a block
+// in the text colour, on the background colour. pictures are boxes the size
+// of the picture, since we don't have the pictures themselves either
+pub struct FrameRenderer {
+    directory: PathBuf,
+
+    // in pixels
+    width: usize,
+    height: usize,
+
+    // ( width, height ) of a character
+    font_size: (usize, usize),
+
+    // three bytes ( red, green and blue ) for every pixel, row by row
+    pixels: Vec<u8>,
+
+    // the number of the next frame, and whether anything has been drawn since
+    // the last one was written
+    frame: usize,
+    dirty: bool,
+}
+
+impl FrameRenderer {
+    // the screen starts out black
+    pub fn new(
+        directory: &str,
+        width: u16,
+        height: u16,
+        font_size: (u16, u16),
+    ) -> io::Result<FrameRenderer> {
+        fs::create_dir_all(directory)?;
+
+        Ok(FrameRenderer {
+            directory: PathBuf::from(directory),
+            width: width as usize,
+            height: height as usize,
+            font_size: (font_size.0 as usize, font_size.1 as usize),
+            pixels: vec![0; width as usize * height as usize * 3],
+            frame: 0,
+            dirty: false,
+        })
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width as u16
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height as u16
+    }
+
+    pub fn font_size(&self) -> (u16, u16) {
+        (self.font_size.0 as u16, self.font_size.1 as u16)
+    }
+
+    pub fn draw(&mut self, command: &DrawCommand) {
+        match *command {
+            DrawCommand::Text {
+                x,
+                y,
+                ref text,
+                style,
+                foreground,
+                background,
+                ..
+            } => {
+                // style 1 is reverse video
+                let (foreground, background) = match style & 1 {
+                    0 => (foreground, background),
+                    _ => (background, foreground),
+                };
+
+                let (char_width, char_height) = self.font_size;
+                let (x, y) = (x as usize, y as usize);
+
+                for (i, ch) in text.chars().enumerate() {
+                    let left = x + i * char_width;
+
+                    self.fill(left, y, char_width, char_height, background);
+
+                    // a block for the character, leaving a gap around it so
+                    // words still look like words
+                    if !ch.is_whitespace() {
+                        self.fill(
+                            left + 1,
+                            y + char_height / 4,
+                            char_width.saturating_sub(2),
+                            char_height - char_height / 4 - 1,
+                            foreground,
+                        );
+                    }
+                }
+            }
+            DrawCommand::Picture {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => {
+                let (x, y, width, height) =
+                    (x as usize, y as usize, width as usize, height as usize);
+
+                // a grey box with a light outline
+                self.fill(x, y, width, height, 0x2108);
+                self.fill(x, y, width, 1, 0x5AD6);
+                self.fill(x, y + height.saturating_sub(1), width, 1, 0x5AD6);
+                self.fill(x, y, 1, height, 0x5AD6);
+                self.fill(x + width.saturating_sub(1), y, 1, height, 0x5AD6);
+            }
+            DrawCommand::Fill {
+                x,
+                y,
+                width,
+                height,
+                colour,
+            } => self.fill(
+                x as usize,
+                y as usize,
+                width as usize,
+                height as usize,
+                colour,
+            ),
+            DrawCommand::Scroll {
+                x,
+                y,
+                width,
+                height,
+                pixels,
+                colour,
+            } => self.scroll(
+                x as usize,
+                y as usize,
+                width as usize,
+                height as usize,
+                pixels,
+                colour,
+            ),
+        }
+
+        self.dirty = true;
+    }
+
+    // writes the screen out, if anything has changed since the last time -
+    // the frames are numbered, so they sort in the order they were drawn
+    pub fn write_frame(&mut self) -> io::Result<Option<PathBuf>> {
+        if !self.dirty {
+            return Ok(None);
+        }
+
+        let path = self.directory.join(format!("frame-{:04}.ppm", self.frame));
+
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend_from_slice(&self.pixels);
+
+        fs::write(&path, data)?;
+
+        self.frame += 1;
+        self.dirty = false;
+
+        Ok(Some(path))
+    }
+
+    // positions start at 1, and anything off the screen is clipped
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, colour: u16) {
+        let rgb = to_rgb(colour);
+        let (left, top) = (cmp::max(x, 1) - 1, cmp::max(y, 1) - 1);
+        let right = cmp::min(x.saturating_sub(1) + width, self.width);
+        let bottom = cmp::min(y.saturating_sub(1) + height, self.height);
+
+        for row in top..bottom {
+            for column in left..right {
+                let i = (row * self.width + column) * 3;
+                self.pixels[i..i + 3].copy_from_slice(&rgb);
+            }
+        }
+    }
+
+    // moves the rows of a rectangle up ( or down, for negative pixels ), and
+    // fills in the rows left behind
+    fn scroll(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        pixels: i16,
+        colour: u16,
+    ) {
+        let (left, top) = (cmp::max(x, 1) - 1, cmp::max(y, 1) - 1);
+        let right = cmp::min(x.saturating_sub(1) + width, self.width);
+        let bottom = cmp::min(y.saturating_sub(1) + height, self.height);
+
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        let distance = cmp::min(pixels.unsigned_abs() as usize, bottom - top);
+        let row_bytes = (right - left) * 3;
+
+        let mut move_row = |from: usize, to: usize| {
+            let (from, to) = ((from * self.width + left) * 3, (to * self.width + left) * 3);
+            self.pixels.copy_within(from..from + row_bytes, to);
+        };
+
+        if pixels > 0 {
+            for row in top..bottom - distance {
+                move_row(row + distance, row);
+            }
+
+            self.fill(
+                left + 1,
+                bottom - distance + 1,
+                right - left,
+                distance,
+                colour,
+            );
+        } else {
+            for row in (top + distance..bottom).rev() {
+                move_row(row - distance, row);
+            }
+
+            self.fill(left + 1, top + 1, right - left, distance, colour);
+        }
+    }
+}
+
+// true colours have 5 bits each of blue, green and red, from the top
+fn to_rgb(colour: u16) -> [u8; 3] {
+    let scale = |bits: u16| ((bits & 0x1F) * 255 / 31) as u8;
+
+    [scale(colour), scale(colour >> 5), scale(colour >> 10)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: u16 = 0x001F;
+    const BLUE: u16 = 0x7C00;
+
+    // a screen 4 pixels wide and 3 high, with nowhere to write frames to
+    fn renderer() -> FrameRenderer {
+        FrameRenderer {
+            directory: PathBuf::new(),
+            width: 4,
+            height: 3,
+            font_size: (4, 4),
+            pixels: vec![0; 4 * 3 * 3],
+            frame: 0,
+            dirty: false,
+        }
+    }
+
+    // the screen as one letter a pixel - r, b or . for black
+    fn screen(renderer: &FrameRenderer) -> Vec<String> {
+        renderer
+            .pixels
+            .chunks(renderer.width * 3)
+            .map(|row| {
+                row.chunks(3)
+                    .map(|pixel| match pixel {
+                        [255, 0, 0] => 'r',
+                        [0, 0, 255] => 'b',
+                        [0, 0, 0] => '.',
+                        _ => '?',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn fill(x: u16, y: u16, width: u16, height: u16, colour: u16) -> DrawCommand {
+        DrawCommand::Fill {
+            x,
+            y,
+            width,
+            height,
+            colour,
+        }
+    }
+
+    fn scroll(pixels: i16) -> DrawCommand {
+        DrawCommand::Scroll {
+            x: 1,
+            y: 1,
+            width: 4,
+            height: 3,
+            pixels,
+            colour: BLUE,
+        }
+    }
+
+    #[test]
+    fn fills_are_clipped_to_the_screen() {
+        let mut renderer = renderer();
+
+        renderer.draw(&fill(2, 2, 2, 1, RED));
+        assert_eq!(screen(&renderer), vec!["....", ".rr.", "...."]);
+
+        renderer.draw(&fill(3, 3, 10, 10, BLUE));
+        assert_eq!(screen(&renderer), vec!["....", ".rr.", "..bb"]);
+        assert!(renderer.dirty);
+    }
+
+    #[test]
+    fn scrolling_up_fills_in_the_bottom() {
+        let mut renderer = renderer();
+        renderer.draw(&fill(1, 1, 4, 1, RED));
+        renderer.draw(&fill(2, 2, 1, 1, RED));
+
+        renderer.draw(&scroll(1));
+        assert_eq!(screen(&renderer), vec![".r..", "....", "bbbb"]);
+    }
+
+    #[test]
+    fn scrolling_down_fills_in_the_top() {
+        let mut renderer = renderer();
+        renderer.draw(&fill(1, 1, 4, 1, RED));
+
+        renderer.draw(&scroll(-2));
+        assert_eq!(screen(&renderer), vec!["bbbb", "bbbb", "rrrr"]);
+
+        // scrolling further than the rectangle just fills it
+        renderer.draw(&scroll(10));
+        assert_eq!(screen(&renderer), vec!["bbbb", "bbbb", "bbbb"]);
+    }
+
+    #[test]
+    fn frames_are_only_written_after_a_change() {
+        let mut renderer = renderer();
+        assert_eq!(renderer.write_frame().unwrap(), None);
+    }
+}
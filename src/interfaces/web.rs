@@ -1,9 +1,9 @@
 extern crate futures;
 extern crate serde_derive;
 
-use std::{cell::*, pin::Pin, rc::*};
+use std::{cell::*, collections::HashSet, pin::Pin, rc::*};
 
-use stdweb::{unstable::TryInto, *};
+use stdweb::{unstable::TryInto, web::TypedArray, *};
 
 use self::{
     futures::task::*,
@@ -11,6 +11,10 @@ use self::{
     serde_derive::{Deserialize, Serialize},
 };
 
+use super::super::zmachine::blorb::Blorb;
+use super::super::zmachine::graphics::DrawCommand;
+use super::super::zmachine::sound::SoundEffect;
+use super::audio::AudioBackend;
 use super::zinterface::*;
 
 pub struct WebInputIndicator {
@@ -28,6 +32,17 @@ pub struct WebInputIndicator {
 pub struct WebInterface {
     pub indicator: Rc<RefCell<WebInputIndicator>>,
     pub publisher: Rc<RefCell<WebPublisher>>,
+    // are we sending the page the player's commands to keep?
+    pub recording: Cell<bool>,
+    pub audio: RefCell<WebAudio>,
+}
+
+// plays the story's sounds ( from its blorb file ) with the web audio api -
+// the page keeps the decoded sounds, and what's playing, in window.RustyZ
+pub struct WebAudio {
+    resources: Option<Rc<Blorb>>,
+    // the sounds the page has been handed
+    loaded: HashSet<u16>,
 }
 
 pub struct WebPublisher {
@@ -40,10 +55,34 @@ pub struct WebStream {
     stream: Weak<RefCell<WebPublisher>>,
 }
 
+// source says what the update is for:
+//
+// "main", "left", "right": text for the lower window and the status line
+// "input": the machine is waiting on the player, for at most content characters
+// "upper": text for the upper window, at line and column
+// "split": the upper window is now content lines, starting at screen line line
+// "erase": clear the window named in content ( "upper", "lower" or "all" )
+// "transcript": text for the transcript ( the page keeps it to download )
+// "command": a line the player typed, for the command record ( ditto )
+//
+// line and column are 0 for anything they don't apply to
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct WebUpdate {
     pub source: String,
     pub content: String,
+    pub line: u16,
+    pub column: u16,
+}
+
+impl WebUpdate {
+    pub fn new(source: &str, content: &str) -> WebUpdate {
+        WebUpdate {
+            source: source.to_string(),
+            content: content.to_string(),
+            line: 0,
+            column: 0,
+        }
+    }
 }
 
 impl WebInterface {
@@ -54,6 +93,8 @@ impl WebInterface {
                 input: "".to_string(),
             })),
             publisher: Rc::new(RefCell::new(WebPublisher::new())),
+            recording: Cell::new(false),
+            audio: RefCell::new(WebAudio::new()),
         };
 
         let callback_indicator = Rc::clone(&interface.indicator);
@@ -72,6 +113,196 @@ impl WebInterface {
     }
 }
 
+impl WebAudio {
+    pub fn new() -> WebAudio {
+        js! { @(no_return)
+            window.RustyZ = window.RustyZ || {};
+
+            var z = window.RustyZ;
+            z.sounds = {};
+            z.playing = {};
+
+            // browsers only allow audio once the player has done something,
+            // so the context isn't made until the first sound
+            z.audioContext = function() {
+                z.audio = z.audio || new (window.AudioContext || window.webkitAudioContext)();
+                return z.audio;
+            };
+
+            z.bleep = function(frequency) {
+                var audio = z.audioContext();
+                var oscillator = audio.createOscillator();
+
+                oscillator.frequency.value = frequency;
+                oscillator.connect(audio.destination);
+                oscillator.start();
+                oscillator.stop(audio.currentTime + 0.1);
+            };
+
+            // decoding happens in the background, so playing waits on it
+            z.loadSound = function(number, bytes) {
+                var data = bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength);
+                z.sounds[number] = z.audioContext().decodeAudioData(data);
+            };
+
+            z.playSound = function(number, volume, repeats) {
+                var audio = z.audioContext();
+                var playing = { stopped: false, source: null };
+
+                // one sound at a time
+                z.stopSound(0);
+                z.playing[number] = playing;
+
+                z.sounds[number].then(function(buffer) {
+                    if (playing.stopped) {
+                        return;
+                    }
+
+                    var gain = audio.createGain();
+                    gain.gain.value = volume / 8;
+                    gain.connect(audio.destination);
+
+                    var left = repeats == 0 ? 1 : repeats;
+
+                    function play() {
+                        var source = audio.createBufferSource();
+                        source.buffer = buffer;
+                        source.loop = repeats == 255;
+                        source.connect(gain);
+
+                        source.onended = function() {
+                            left -= 1;
+
+                            if (playing.stopped) {
+                                return;
+                            } else if (left > 0) {
+                                play();
+                            } else if (z.playing[number] === playing) {
+                                delete z.playing[number];
+                            }
+                        };
+
+                        playing.source = source;
+                        source.start();
+                    }
+
+                    play();
+                }, function() {
+                    delete z.playing[number];
+                });
+            };
+
+            z.stopSound = function(number) {
+                Object.keys(z.playing).forEach(function(key) {
+                    if (number == 0 || key == number) {
+                        var playing = z.playing[key];
+                        playing.stopped = true;
+
+                        if (playing.source) {
+                            playing.source.stop();
+                        }
+
+                        delete z.playing[key];
+                    }
+                });
+            };
+
+            z.unloadSound = function(number) {
+                if (number == 0) {
+                    z.sounds = {};
+                } else {
+                    delete z.sounds[number];
+                }
+            };
+        };
+
+        WebAudio {
+            resources: None,
+            loaded: HashSet::new(),
+        }
+    }
+
+    pub fn load_resources(&mut self, resources: &Rc<Blorb>) {
+        self.resources = Some(Rc::clone(resources));
+    }
+
+    // hands a sound to the page, if it hasn't been already - false if
+    // there's no such sound
+    fn load(&mut self, number: u16) -> bool {
+        if self.loaded.contains(&number) {
+            return true;
+        }
+
+        let sound = match self.resources {
+            Some(ref resources) => match resources.sounds.get(&(number as u32)) {
+                Some(x) => x,
+                None => return false,
+            },
+            None => return false,
+        };
+
+        js! { @(no_return)
+            window.RustyZ.loadSound(@{number}, @{TypedArray::<u8>::from(&sound.data[..])});
+        };
+
+        self.loaded.insert(number);
+        true
+    }
+}
+
+impl AudioBackend for WebAudio {
+    fn sound_effect(&mut self, effect: &SoundEffect) {
+        match *effect {
+            SoundEffect::Bleep { high } => {
+                let frequency: u32 = if high { 880 } else { 220 };
+
+                js! { @(no_return)
+                    window.RustyZ.bleep(@{frequency});
+                };
+            }
+            SoundEffect::Prepare { number } => {
+                self.load(number);
+            }
+            SoundEffect::Start {
+                number,
+                volume,
+                repeats,
+            } => {
+                if self.load(number) {
+                    js! { @(no_return)
+                        window.RustyZ.playSound(@{number}, @{volume}, @{repeats});
+                    };
+                }
+            }
+            SoundEffect::Stop { number } => {
+                js! { @(no_return)
+                    window.RustyZ.stopSound(@{number});
+                };
+            }
+            SoundEffect::Unload { number } => {
+                js! { @(no_return)
+                    window.RustyZ.unloadSound(@{number});
+                };
+
+                match number {
+                    0 => self.loaded.clear(),
+                    x => {
+                        self.loaded.remove(&x);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_playing(&self, number: u16) -> bool {
+        let playing = js! {
+            return !!window.RustyZ.playing[@{number}];
+        };
+
+        playing.try_into().unwrap_or(false)
+    }
+}
+
 impl Drop for WebInterface {
     fn drop(&mut self) {
         js! {
@@ -83,32 +314,134 @@ impl Drop for WebInterface {
 }
 
 impl ZInterface for WebInterface {
-    fn clear(&self) {}
+    fn clear(&self) {
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("erase", "all"));
+    }
 
     fn print_to_main(&self, str: &str) {
-        self.publisher.borrow_mut().send(WebUpdate {
-            source: "main".to_string(),
-            content: str.to_string(),
-        });
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("main", str));
     }
 
+    // the browser wraps the text itself
+    fn buffer_mode(&self, _: bool) {}
+
     fn print_to_header(&self, left_side: &str, right_side: &str) {
-        self.publisher.borrow_mut().send(WebUpdate {
-            source: "left".to_string(),
-            content: left_side.to_string(),
-        });
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("left", left_side));
 
-        self.publisher.borrow_mut().send(WebUpdate {
-            source: "right".to_string(),
-            content: right_side.to_string(),
-        });
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("right", right_side));
     }
 
-    fn read_next_line(&self, buf: &mut String) -> Option<usize> {
-        self.publisher.borrow_mut().send(WebUpdate {
-            source: "input".to_string(),
-            content: "".to_string()
-        });
+    // the page is sized for an 80 column terminal, and scrolls rather than
+    // running out of lines
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            interpreter_number: 6,
+            status_line: true,
+            split_screen: true,
+            variable_pitch: false,
+            screen_width: 80,
+            screen_height: 255,
+            font_width: 1,
+            font_height: 1,
+            background: 2,
+            foreground: 9,
+            graphics: false,
+            sound: true,
+        }
+    }
+
+    fn split_window(&self, top: u16, lines: u16) {
+        let mut update = WebUpdate::new("split", &lines.to_string());
+        update.line = top;
+
+        self.publisher.borrow_mut().send(update);
+    }
+
+    fn print_to_upper(&self, line: u16, column: u16, text: &str) {
+        let mut update = WebUpdate::new("upper", text);
+        update.line = line;
+        update.column = column;
+
+        self.publisher.borrow_mut().send(update);
+    }
+
+    fn erase_window(&self, window: Window) {
+        let content = match window {
+            Window::Upper => "upper",
+            Window::Lower => "lower",
+        };
+
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("erase", content));
+    }
+
+    // the page only does text, so version 6 stories only get window 0
+    fn draw(&self, _: &DrawCommand) {}
+
+    fn read_mouse(&self) -> (u16, u16, u16) {
+        (0, 0, 0)
+    }
+
+    fn sound_effect(&self, effect: &SoundEffect) {
+        self.audio.borrow_mut().sound_effect(effect);
+    }
+
+    fn sound_playing(&self, number: u16) -> bool {
+        self.audio.borrow().is_playing(number)
+    }
+
+    fn load_resources(&self, resources: &Rc<Blorb>) {
+        self.audio.borrow_mut().load_resources(resources);
+    }
+
+    // the page collects the transcript, and offers it as a download
+    fn start_transcript(&self) -> bool {
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("transcript", ""));
+
+        true
+    }
+
+    fn print_to_transcript(&self, text: &str) {
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("transcript", text));
+    }
+
+    fn stop_transcript(&self) {}
+
+    // the command record works like the transcript
+    fn start_command_record(&self) -> bool {
+        self.recording.set(true);
+        true
+    }
+
+    fn stop_command_record(&self) {
+        self.recording.set(false);
+    }
+
+    // there's no file to play back from in the page
+    fn start_command_playback(&self) -> bool {
+        false
+    }
+
+    fn stop_command_playback(&self) {}
+
+    fn read_next_line(&self, buf: &mut String, max_length: usize) -> Option<usize> {
+        // the page uses this as the input field's maxlength
+        self.publisher
+            .borrow_mut()
+            .send(WebUpdate::new("input", &max_length.to_string()));
 
         let input_sent = self.indicator.borrow().input_sent;
 
@@ -116,12 +449,52 @@ impl ZInterface for WebInterface {
             return None;
         }
 
-        *buf = self.indicator.borrow().input.clone();
+        // the field's maxlength counts utf-16, not characters, so we make
+        // sure of it here
+        *buf = self
+            .indicator
+            .borrow()
+            .input
+            .chars()
+            .take(max_length)
+            .collect();
         self.indicator.borrow_mut().input_sent = false;
 
+        if self.recording.get() {
+            self.publisher
+                .borrow_mut()
+                .send(WebUpdate::new("command", &format!("{}\n", buf)));
+        }
+
         Some(buf.len())
     }
 
+    // there's no file system to speak of, so saves go to local storage
+    // as a json array of bytes - one slot per page
+    fn save(&self, data: &[u8]) -> bool {
+        let saved = js! {
+            try {
+                var bytes = Array.prototype.slice.call(@{TypedArray::<u8>::from(data)});
+                window.localStorage.setItem("RustyZ.save", JSON.stringify(bytes));
+                return true;
+            } catch (e) {
+                return false;
+            }
+        };
+
+        saved.try_into().unwrap_or(false)
+    }
+
+    fn restore(&self) -> Option<Vec<u8>> {
+        let restored = js! {
+            var saved = window.localStorage.getItem("RustyZ.save");
+            return saved ? new Uint8Array(JSON.parse(saved)) : null;
+        };
+
+        let restored: Option<TypedArray<u8>> = restored.try_into().ok();
+        restored.map(|x| x.to_vec())
+    }
+
     fn quit(&self) {}
 
     fn setup_logging(&self) {}
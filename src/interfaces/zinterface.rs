@@ -2,11 +2,18 @@ extern crate serde_derive;
 
 use self::serde_derive::Serialize;
 
+use super::super::zmachine::blorb::Blorb;
+use super::super::zmachine::graphics::DrawCommand;
+use super::super::zmachine::sound::SoundEffect;
+
+use std::rc::Rc;
+
 #[derive(Copy, Clone, Serialize)]
 #[repr(u8)]
+// the machine restarts itself, so there's no restarting state - 1 used
+// to be it, and is left alone so the numbers don't change under javascript
 pub enum LoopState {
     Running = 0,
-    Restarting = 1,
     Quitting = 2,
     Error = 3,
 }
@@ -15,7 +22,6 @@ impl From<u8> for LoopState {
     fn from(orig: u8) -> Self {
         match orig {
             0 => return LoopState::Running,
-            1 => return LoopState::Restarting,
             2 => return LoopState::Quitting,
             3 => return LoopState::Error,
             _ => return LoopState::Error,
@@ -23,12 +29,93 @@ impl From<u8> for LoopState {
     }
 }
 
+// the two windows of the screen model - the lower window is the one that
+// scrolls, and the upper window is a number of lines at the top of the
+// screen that don't ( games use it for things like maps and quote boxes )
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Window {
+    Lower,
+    Upper,
+}
+
+// what the interface can do, which the machine writes into the header for the
+// story to read ( games use the screen size to lay out things like the status
+// line and menus )
+pub struct Capabilities {
+    // the machine the original interpreters ran on - this only matters to a
+    // few old infocom games, which use it to tweak their layout ( 6 is an ibm pc )
+    pub interpreter_number: u8,
+
+    // whether there's a status line, and an upper window, at all
+    pub status_line: bool,
+    pub split_screen: bool,
+
+    // whether the font is variable pitch unless the story asks otherwise
+    pub variable_pitch: bool,
+
+    // the size of the screen in characters ( a height of 255 means it never
+    // needs to pause )
+    pub screen_width: u8,
+    pub screen_height: u8,
+
+    // how big a character is, in whatever units the interface measures
+    // the screen in - for a terminal these are just 1
+    pub font_width: u8,
+    pub font_height: u8,
+
+    // the colours text starts out in, as colour numbers ( 2 is black, 9 is white )
+    pub background: u8,
+    pub foreground: u8,
+
+    // whether draw commands go anywhere - version 6 stories check for
+    // this before they try to show pictures
+    pub graphics: bool,
+
+    // whether sound_effect does anything - the bleeps at least, whether or
+    // not there's a blorb file with the story's own sounds
+    pub sound: bool,
+}
+
+// a plain stream of text - no status line, no upper window, no pictures,
+// and a screen that never needs to pause. this is what an interface that
+// only overrides the required methods gets
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities {
+            interpreter_number: 6,
+            status_line: false,
+            split_screen: false,
+            variable_pitch: false,
+            screen_width: 80,
+            screen_height: 255,
+            font_width: 1,
+            font_height: 1,
+            background: 2,
+            foreground: 9,
+            graphics: false,
+            sound: false,
+        }
+    }
+}
+
+// only quit, read_next_line and print_to_main have to be written - everything
+// else has a body that does nothing ( or says it can't ), so an interface that
+// just wants to read and write text doesn't have to care about windows,
+// sounds, transcripts and the rest. new hooks get a body like this too, so
+// adding one doesn't break anyone's interface
 pub trait ZInterface: Sized {
     fn quit(&self);
-    fn clear(&self);
-    fn read_next_line(&self, buf: &mut String) -> Option<usize>;
+    fn clear(&self) {}
+
+    // max_length is how many characters the story has room for - anything
+    // past it gets cut off, so the interface should stop the player there
+    fn read_next_line(&self, buf: &mut String, max_length: usize) -> Option<usize>;
     fn print_to_main(&self, main: &str);
 
+    // whether text in the lower window is buffered, so it can be word
+    // wrapped - it starts out on ( and goes back on when the story restarts )
+    fn buffer_mode(&self, _buffered: bool) {}
+
     // It's interesting, the ZMachine actually has this concept embedded in the opcodes;
     // show_status points to two objects that each must be displayed on the top left
     // and top right.
@@ -36,9 +123,89 @@ pub trait ZInterface: Sized {
     // This is interesting be cause it ALSO has opcodes for creating split/secondary
     // screens, and doesn't use this for that purpose; it's possible implementation
     // wasn't ready for Zork I/II/III (but other version 3 games make heavy use of it)
-    fn print_to_header(&self, left_side: &str, right_side: &str);
-    fn setup_logging(&self);
-    fn setup_loop<F>(&self, main_loop: F) -> LoopState
+    fn print_to_header(&self, _left_side: &str, _right_side: &str) {}
+
+    // asked when the machine starts ( and restarts, and restores )
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    // the machine keeps track of which window is selected and where the cursor
+    // is, so the interface only has to draw. the upper window is lines lines,
+    // starting at screen line top ( under the status line in versions 1-3 ) -
+    // the lower window is everything below that, and 0 lines gives it the
+    // whole screen back
+    fn split_window(&self, _top: u16, _lines: u16) {}
+
+    // prints to the upper window, at a screen position ( line and column start at 1 )
+    fn print_to_upper(&self, _line: u16, _column: u16, _text: &str) {}
+    fn erase_window(&self, _window: Window) {}
+
+    // version 6 has no upper and lower window - everything the story puts on
+    // the screen comes here as draw commands, in pixels. text printed to
+    // window 0 still goes to print_to_main as well, so an interface that
+    // can't draw anything can ignore these and still be played
+    fn draw(&self, _command: &DrawCommand) {}
+
+    // where the mouse was last clicked, as ( y, x, buttons ) - buttons has a
+    // bit set for each button that was down, and is 0 if nothing was clicked
+    fn read_mouse(&self) -> (u16, u16, u16) {
+        (0, 0, 0)
+    }
+
+    // sound_effect, which the interface hands to its audio backend ( see
+    // audio.rs ) - sound_playing is how the machine finds out a sound it
+    // started has finished
+    fn sound_effect(&self, _effect: &SoundEffect) {}
+    fn sound_playing(&self, _number: u16) -> bool {
+        false
+    }
+
+    // the pictures and sounds ( and metadata ) that came with the story, when
+    // it was loaded from a blorb file - this happens once, before it starts
+    fn load_resources(&self, _resources: &Rc<Blorb>) {}
+
+    // output stream 2, a transcript of the game - start_transcript is where the
+    // interface finds somewhere to put it, and can say no
+    fn start_transcript(&self) -> bool {
+        false
+    }
+    fn print_to_transcript(&self, _text: &str) {}
+    fn stop_transcript(&self) {}
+
+    // output stream 4, a record of every line the player types, and input
+    // stream 1, which reads lines from a record instead of the keyboard ( until
+    // it runs out ) - both happen in read_next_line, so they are the interface's
+    fn start_command_record(&self) -> bool {
+        false
+    }
+    fn stop_command_record(&self) {}
+    fn start_command_playback(&self) -> bool {
+        false
+    }
+    fn stop_command_playback(&self) {}
+
+    // save files are just bytes as far as the machine is concerned; where
+    // they end up ( and what they are called ) is up to the interface
+    fn save(&self, _data: &[u8]) -> bool {
+        false
+    }
+    fn restore(&self) -> Option<Vec<u8>> {
+        None
+    }
+    fn setup_logging(&self) {}
+
+    // runs the main loop until it says to stop - interfaces that can't block
+    // ( like the web one ) hand it off to something else instead
+    fn setup_loop<F>(&self, mut main_loop: F) -> LoopState
     where
-        F: 'static + FnMut() -> u8;
+        F: 'static + FnMut() -> u8,
+    {
+        loop {
+            match LoopState::from(main_loop()) {
+                LoopState::Running => (),
+                result => return result,
+            }
+        }
+    }
 }
@@ -0,0 +1,69 @@
+// rusty_z as a library
+//
+// the binary is just one front end for the machine; anything else that
+// wants to run a story ( a test harness, a bot, a different ui ) can build
+// a machine out of story bytes and a ZInterface, and drive it from here:
+//
+//     let mut machine = ZMachine::new(data, interface)?;
+//
+//     while let Ok(MachineState::TakingInput { .. }) = machine.run() {
+//         machine.provide_input("open mailbox");
+//     }
+//
+// the interface gets everything the machine prints, and the machine's state
+// ( memory, globals, where it's up to ) can be read between steps. if the
+// story breaks, run stops the machine and hands back a ZMachineError
+//
+// everything else is internal - only what's exported below is the api
+
+mod interfaces;
+mod zmachine;
+
+extern crate rand;
+#[cfg(target_os = "emscripten")]
+#[macro_use]
+extern crate stdweb;
+
+pub use interfaces::audio::AudioBackend;
+pub use interfaces::zinterface::{Capabilities, LoopState, Window, ZInterface};
+pub use zmachine::blorb::Blorb;
+pub use zmachine::error::{ErrorKind, ZMachineError};
+pub use zmachine::graphics::DrawCommand;
+pub use zmachine::header::Checksum;
+pub use zmachine::sound::SoundEffect;
+pub use zmachine::zmachine::{validate_story, verify_story, MachineState, ZMachine};
+
+// the interfaces this crate comes with
+#[cfg(not(target_os = "emscripten"))]
+pub use interfaces::cli::CliInterface;
+#[cfg(target_os = "emscripten")]
+pub use interfaces::web::{WebInterface, WebPublisher, WebStream};
+
+#[cfg(target_os = "emscripten")]
+js_serializable!(interfaces::web::WebUpdate);
+
+// one "tick" of the machine for the interfaces' main loops - run until we
+// can't anymore, then either wait for input or tell the loop to stop
+//
+// the result is a LoopState as a u8, so it can cross over into javascript
+pub fn main_loop<T: ZInterface>(machina: &mut ZMachine<T>) -> u8 {
+    match machina.run() {
+        Ok(MachineState::Stopped) => {
+            return LoopState::Quitting as u8;
+        }
+        Ok(MachineState::TakingInput { .. }) => {
+            machina.wait_for_input();
+        }
+        Ok(_) => (),
+        // the machine has already stopped itself, all that's left is to
+        // tell the player why
+        Err(e) => {
+            machina
+                .interface()
+                .print_to_main(&format!("\n[fatal error: {}]\n", e));
+            return LoopState::Error as u8;
+        }
+    };
+
+    return LoopState::Running as u8;
+}
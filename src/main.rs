@@ -1,45 +1,67 @@
-pub mod interfaces;
-pub mod zmachine;
+#[cfg(not(target_os = "emscripten"))]
+mod options;
 
-extern crate rand;
+extern crate rusty_z;
 #[cfg(target_os = "emscripten")]
 extern crate stdweb;
 
 use std::rc::*;
 
-use interfaces::zinterface::*;
-use zmachine::zmachine::*;
-
-#[cfg(target_os = "emscripten")]
-use {
-    interfaces::web::{WebInterface, WebPublisher, WebStream, WebUpdate},
-    stdweb::*,
-};
+use rusty_z::*;
 
 #[cfg(target_os = "emscripten")]
-js_serializable!(WebUpdate);
+use stdweb::*;
 
 #[cfg(not(target_os = "emscripten"))]
-use interfaces::cli::CliInterface;
+use {options::*, std::env, std::fs, std::process};
+
+// the web build has nowhere to load a story from, so it
+// can only run a story that was compiled into it
+#[cfg(all(target_os = "emscripten", not(feature = "embedded_story")))]
+compile_error!("the web build needs a story compiled in: build with --features embedded_story");
 
 fn main() {
     // machine now takes ownership of the cloned data buffer
     // its mut, because next_instruction can change the
     // state of the machine. which makes complete sense
-    let data = get_program();
+    let options = get_options();
+    let data = get_program(&options);
+
+    // releases come packaged up as blorb files, with the story inside
+    // alongside its pictures and sounds
+    let (data, resources) = unpack_story(data);
+
+    // we check the story before building anything, since --verify can
+    // only add up a well formed story
+    if let Err(e) = validate_story(&data) {
+        exit_with_error(&e);
+    }
+
+    verify_and_exit(&options, &data);
 
-    let interface = get_interface();
+    let interface = get_interface(&options);
     interface.clear();
 
-    let mut machine = ZMachine::new(data, interface);
-    let interface = Rc::clone(&machine.zinterface);
+    let mut machine = match ZMachine::new(data, interface) {
+        Ok(x) => x,
+        Err(e) => exit_with_error(&e),
+    };
+
+    if let Some(resources) = resources {
+        machine.load_resources(resources);
+    }
+
+    configure_machine(&mut machine, &options);
+    configure_interface(&machine);
+    let interface = Rc::clone(machine.interface());
 
     // the loop setup has to happen in main() or a function called from main()
     // if we are using a closure, because of the static lifetime requirement
     // when we use a reference, which is automatic when passing a function
     //
     // note: cli blocks here for now
-    interface.setup_loop(move || main_loop(&mut machine));
+    let result = interface.setup_loop(move || main_loop(&mut machine));
+    exit_on_error(result);
 
     #[cfg(target_os = "emscripten")]
     spawn_local(async move {
@@ -49,55 +71,203 @@ fn main() {
 }
 
 #[cfg(not(target_os = "emscripten"))]
-pub fn get_interface() -> CliInterface {
-    CliInterface {}
+pub fn get_options() -> Options {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(x) => x,
+        Err(e) => exit_with_error(&format!("{}\n\n{}", e, USAGE)),
+    };
+
+    if options.show_help {
+        print!("{}", USAGE);
+        process::exit(0);
+    }
+
+    options
 }
 
+// there's no command line in the browser
 #[cfg(target_os = "emscripten")]
-pub fn get_interface() -> WebInterface {
-    WebInterface::new()
+pub struct Options {}
+
+#[cfg(target_os = "emscripten")]
+pub fn get_options() -> Options {
+    Options {}
 }
 
-pub fn get_program() -> Vec<u8> {
-    // we use the include_bytes! macro because it is cross-compatible
-    // with asm.js - this embeds the bytes in the js file.
-    //
-    // we could probably split this out later using CFG to
-    // lower the size of the desktop binary,
-    // and maybe look at loading a file remotely in the future
+#[cfg(not(target_os = "emscripten"))]
+pub fn configure_machine<T: ZInterface>(machine: &mut ZMachine<T>, options: &Options) {
+    if let Some(depth) = options.undo_depth {
+        machine.set_undo_depth(depth);
+    }
 
-    let data = include_bytes!("../Zork1.dat");
+    machine.set_force_verify(options.force_verify);
+}
 
-    // we then get a reference to that static array of bytes as a slice
-    let data_ref: &[u8] = &data[..];
+#[cfg(target_os = "emscripten")]
+pub fn configure_machine<T: ZInterface>(_: &mut ZMachine<T>, _: &Options) {}
+
+// the cli's line editor finishes words off from the story's dictionary, and
+// keeps a history of commands for each story
+#[cfg(not(target_os = "emscripten"))]
+pub fn configure_interface(machine: &ZMachine<CliInterface>) {
+    machine
+        .interface()
+        .complete_words_from(machine.dictionary_words());
+    machine.interface().keep_history_for(&machine.story_id());
+}
+
+#[cfg(target_os = "emscripten")]
+pub fn configure_interface(_: &ZMachine<WebInterface>) {}
+
+// --verify just reports the checksum, the exit code saying whether it matched
+#[cfg(not(target_os = "emscripten"))]
+pub fn verify_and_exit(options: &Options, data: &[u8]) {
+    if !options.verify {
+        return;
+    }
 
-    //we then copy it, using to_vec
-    let data_vec = data_ref.to_vec();
+    let checksum = match verify_story(data) {
+        Ok(x) => x,
+        Err(e) => exit_with_error(&e),
+    };
 
-    if data_vec.len() <= 0 {
-        panic!("Could not read file!");
+    if checksum.matches() {
+        println!("checksum ok ({:04x})", checksum.actual);
+        process::exit(0);
     }
 
-    data_vec
+    println!(
+        "checksum mismatch: header says {:04x}, story adds up to {:04x}",
+        checksum.expected, checksum.actual
+    );
+    process::exit(1);
 }
 
-pub fn main_loop<T: ZInterface>(machina: &mut ZMachine<T>) -> u8 {
-    while let MachineState::Running = machina.state.clone() {
-        machina.next_instruction();
+#[cfg(target_os = "emscripten")]
+pub fn verify_and_exit(_: &Options, _: &[u8]) {}
+
+#[cfg(not(target_os = "emscripten"))]
+pub fn get_interface(options: &Options) -> CliInterface {
+    let interface = CliInterface::new();
+
+    // it's already plain if there's no terminal, but it might need a width
+    if options.plain || interface.is_plain() {
+        interface.use_plain_output(options.width);
     }
 
-    match machina.state.clone() {
-        MachineState::Restarting => {
-            return LoopState::Restarting as u8;
+    if let Some(ref path) = options.record_path {
+        if let Err(e) = interface.record_commands_to(path) {
+            exit_with_error(&format!("could not record to {}: {}", path, e));
         }
-        MachineState::Stopped => {
-            return LoopState::Quitting as u8;
+    }
+
+    if let Some(ref path) = options.replay_path {
+        if let Err(e) = interface.replay_commands_from(path) {
+            exit_with_error(&format!("could not replay {}: {}", path, e));
         }
-        MachineState::TakingInput { ref callback } => {
-            machina.wait_for_input(callback.clone());
+    }
+
+    if let Some(ref path) = options.sound_log_path {
+        if let Err(e) = interface.log_sounds_to(path) {
+            exit_with_error(&format!("could not write sounds to {}: {}", path, e));
         }
-        _ => (),
+    }
+
+    if let Some(ref path) = options.frames_path {
+        if let Err(e) = interface.render_frames_to(path) {
+            exit_with_error(&format!("could not write frames to {}: {}", path, e));
+        }
+    }
+
+    interface
+}
+
+#[cfg(target_os = "emscripten")]
+pub fn get_interface(_: &Options) -> WebInterface {
+    WebInterface::new()
+}
+
+#[cfg(not(target_os = "emscripten"))]
+pub fn get_program(options: &Options) -> Vec<u8> {
+    let path = match (options.story_path.clone(), get_embedded_program()) {
+        (Some(path), _) => path,
+        // no path given, but we have a story baked in
+        (None, Some(data)) => return data,
+        (None, None) => exit_with_error(&format!("no story file given\n\n{}", USAGE)),
+    };
+
+    match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => exit_with_error(&format!("could not read {}: {}", path, e)),
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+pub fn get_program(_: &Options) -> Vec<u8> {
+    match get_embedded_program() {
+        Some(data) => data,
+        None => exit_with_error("no story was compiled in"),
+    }
+}
+
+// takes the story out of a blorb file, and keeps the rest for the machine -
+// anything else is taken to be a story already
+pub fn unpack_story(data: Vec<u8>) -> (Vec<u8>, Option<Blorb>) {
+    if !Blorb::is_blorb(&data) {
+        return (data, None);
+    }
+
+    let mut blorb = match Blorb::read(&data) {
+        Ok(x) => x,
+        Err(e) => exit_with_error(&e),
     };
 
-    return LoopState::Running as u8;
+    match blorb.story.take() {
+        Some(story) => (story, Some(blorb)),
+        None => exit_with_error("blorb file has no story in it"),
+    }
+}
+
+#[cfg(feature = "embedded_story")]
+pub fn get_embedded_program() -> Option<Vec<u8>> {
+    // we use the include_bytes! macro because it is cross-compatible
+    // with asm.js - this embeds the bytes in the js file.
+    //
+    // this is opt in ( see the embedded_story feature ), so the desktop
+    // binary doesn't carry a story around unless it's asked to
+    let data = include_bytes!("../Zork1.dat");
+
+    // we then get a reference to that static array of bytes as a slice,
+    // and copy it, using to_vec
+    Some(data[..].to_vec())
+}
+
+#[cfg(not(feature = "embedded_story"))]
+pub fn get_embedded_program() -> Option<Vec<u8>> {
+    None
+}
+
+// the error has already been shown to the player, but anything
+// running us should know it didn't end well
+#[cfg(not(target_os = "emscripten"))]
+fn exit_on_error(result: LoopState) {
+    if let LoopState::Error = result {
+        process::exit(1);
+    }
+}
+
+// the web loop carries on in the background, so there's nothing to exit
+#[cfg(target_os = "emscripten")]
+fn exit_on_error(_: LoopState) {}
+
+#[cfg(not(target_os = "emscripten"))]
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("rusty_z: {}", message);
+    process::exit(1);
+}
+
+#[cfg(target_os = "emscripten")]
+fn exit_with_error(message: &str) -> ! {
+    // there's no stderr to speak of, so this ends up in the console
+    panic!("rusty_z: {}", message);
 }
@@ -0,0 +1,127 @@
+// command line options for the cli build
+//
+// there's nothing fancy here - we don't pull in a whole argument parsing
+// crate for a handful of flags
+
+pub const USAGE: &str = "\
+usage: rusty_z [options] <story file>
+
+options:
+  -h, --help           show this message and exit
+  --record <file>      write every command typed to file
+  --replay <file>      read commands from file ( like one made by --record )
+                       before reading them from the keyboard
+  --undo <turns>       how many turns undo can go back ( 0 turns it off )
+  --sound-log <file>   write the story's sound effects to file instead of
+                       ringing the terminal bell
+  --frames <dir>       draw version 6 stories' screens into image files
+                       in dir, one every time the story waits for input
+  --plain              print plain text, with no colours or cursor movement
+                       ( this is the default when stdin or stdout is a pipe )
+  --width <columns>    how wide plain text is wrapped ( 80 by default )
+  --verify             check the story against its checksum and exit
+  --force-verify       have the story's own verify check always pass
+                       ( for patched story files )
+";
+
+pub struct Options {
+    // the story file to load; this is only optional when a story
+    // has been embedded in the binary
+    pub story_path: Option<String>,
+    pub show_help: bool,
+
+    // where to record commands to, and play them back from
+    pub record_path: Option<String>,
+    pub replay_path: Option<String>,
+
+    // how deep the undo history goes, if not the machine's default
+    pub undo_depth: Option<usize>,
+
+    // where to write version 6 screens to
+    pub frames_path: Option<String>,
+
+    // where to write sound effects to, instead of playing them
+    pub sound_log_path: Option<String>,
+
+    // plain text output, and how wide it is
+    pub plain: bool,
+    pub width: Option<u16>,
+
+    // check the story's checksum instead of playing it
+    pub verify: bool,
+
+    // tell the story its checksum is fine, whatever it is
+    pub force_verify: bool,
+}
+
+impl Options {
+    // expects the arguments without the program name
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options {
+            story_path: None,
+            show_help: false,
+            record_path: None,
+            replay_path: None,
+            undo_depth: None,
+            frames_path: None,
+            sound_log_path: None,
+            plain: false,
+            width: None,
+            verify: false,
+            force_verify: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.show_help = true,
+                "--plain" => options.plain = true,
+                "--verify" => options.verify = true,
+                "--force-verify" => options.force_verify = true,
+                "--record" => options.record_path = Some(Options::value_for(&arg, &mut args)?),
+                "--replay" => options.replay_path = Some(Options::value_for(&arg, &mut args)?),
+                "--frames" => options.frames_path = Some(Options::value_for(&arg, &mut args)?),
+                "--sound-log" => {
+                    options.sound_log_path = Some(Options::value_for(&arg, &mut args)?)
+                }
+                "--undo" => {
+                    let value = Options::value_for(&arg, &mut args)?;
+
+                    match value.parse::<usize>() {
+                        Ok(x) => options.undo_depth = Some(x),
+                        Err(_) => return Err(format!("--undo needs a number, not {}", value)),
+                    }
+                }
+                "--width" => {
+                    let value = Options::value_for(&arg, &mut args)?;
+
+                    match value.parse::<u16>() {
+                        Ok(x) if x > 0 => options.width = Some(x),
+                        _ => {
+                            return Err(format!("--width needs a number of columns, not {}", value))
+                        }
+                    }
+                }
+                x if x.starts_with('-') && x.len() > 1 => {
+                    return Err(format!("unknown option: {}", x));
+                }
+                _ => {
+                    if options.story_path.is_some() {
+                        return Err("only one story file can be given".to_string());
+                    }
+
+                    options.story_path = Some(arg);
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    // the argument after an option that needs one
+    fn value_for<I: Iterator<Item = String>>(option: &str, args: &mut I) -> Result<String, String> {
+        match args.next() {
+            Some(x) => Ok(x),
+            None => Err(format!("{} needs a value", option)),
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use super::iff::*;
+
+use std::collections::BTreeMap;
+
+// blorb is how stories are packaged up with their pictures and sounds - it's
+// an IFF file ( see iff.rs ) of type "IFRS", where the story itself is just
+// another chunk
+//
+// http://eblong.com/zarf/blorb/blorb.html
+//
+// the first chunk is always the resource index ( RIdx ), which lists every
+// resource as what it's for ( "Exec" for the story, "Pict" and "Snd " ), its
+// number, and the offset of its chunk in the file. the chunk's id says what
+// kind of data it is ( "ZCOD" for a z-machine story, "PNG ", "JPEG", "AIFF",
+// "OGGV" and so on )
+//
+// a few other chunks tell us about the story rather than being resources:
+//
+// IFmd: the story's iFiction metadata ( an xml record of its title, author, etc )
+// Fspc: the number of the picture to show as a cover
+// RelN: the release number of the resources, for version 6 stories to check
+
+pub struct Resource {
+    // the id of the resource's chunk, which is what kind of data it is - aiff
+    // sounds are FORMs of their own, so they get their form type instead
+    pub format: [u8; 4],
+
+    // the whole of an aiff sound, FORM header and all, since that's what
+    // anything playing it will expect - otherwise just the chunk's data
+    pub data: Vec<u8>,
+}
+
+impl Resource {
+    // the ( width, height ) of a picture, in pixels - this is as much of the
+    // picture as the machine ever needs to know about
+    pub fn picture_size(&self) -> Option<(u32, u32)> {
+        let data = &self.data;
+
+        match &self.format {
+            // the header chunk comes first, after the 8 byte signature
+            b"PNG " if data.len() >= 24 && &data[12..16] == b"IHDR" => {
+                Some((read_u32(data, 16), read_u32(data, 20)))
+            }
+            b"JPEG" => jpeg_size(data),
+            // a placeholder with no picture data, just the size
+            b"Rect" if data.len() >= 8 => Some((read_u32(data, 0), read_u32(data, 4))),
+            _ => None,
+        }
+    }
+}
+
+pub struct Blorb {
+    // the story file, if there is one
+    pub story: Option<Vec<u8>>,
+
+    pub pictures: BTreeMap<u32, Resource>,
+    pub sounds: BTreeMap<u32, Resource>,
+
+    // the iFiction xml, as it was in the file
+    pub metadata: Option<String>,
+    pub frontispiece: Option<u32>,
+    pub release: Option<u16>,
+}
+
+impl Blorb {
+    pub fn is_blorb(data: &[u8]) -> bool {
+        data.len() >= 12 && &data[0..4] == b"FORM" && &data[8..12] == b"IFRS"
+    }
+
+    pub fn read(data: &[u8]) -> Result<Blorb, String> {
+        let form = FormReader::read(data)?;
+
+        if &form.form_type != b"IFRS" {
+            return Err("not a blorb file".to_string());
+        }
+
+        let index = match form.find(b"RIdx") {
+            Some(chunk) if chunk.data.len() >= 4 => chunk.data,
+            _ => return Err("blorb file has no resource index".to_string()),
+        };
+
+        let mut blorb = Blorb {
+            story: None,
+            pictures: BTreeMap::new(),
+            sounds: BTreeMap::new(),
+            metadata: form
+                .find(b"IFmd")
+                .map(|chunk| String::from_utf8_lossy(chunk.data).into_owned()),
+            frontispiece: match form.find(b"Fspc") {
+                Some(chunk) if chunk.data.len() >= 4 => Some(read_u32(chunk.data, 0)),
+                _ => None,
+            },
+            release: match form.find(b"RelN") {
+                Some(chunk) if chunk.data.len() >= 2 => Some(read_u16(chunk.data, 0)),
+                _ => None,
+            },
+        };
+
+        // every entry is the usage, the number and where the chunk starts,
+        // 4 bytes each
+        let count = read_u32(index, 0) as usize;
+
+        if index.len() < 4 + count * 12 {
+            return Err("blorb resource index is truncated".to_string());
+        }
+
+        for entry in index[4..4 + count * 12].chunks(12) {
+            let (usage, number, offset) = (
+                &entry[0..4],
+                read_u32(entry, 4),
+                read_u32(entry, 8) as usize,
+            );
+
+            let chunk = match form.find_at(offset) {
+                Some(x) => x,
+                None => {
+                    return Err(format!(
+                        "blorb resource {} {} points at a chunk that isn't there",
+                        String::from_utf8_lossy(usage),
+                        number
+                    ))
+                }
+            };
+
+            let resource = match &chunk.id {
+                b"FORM" if chunk.data.len() >= 4 => {
+                    let mut format = [0; 4];
+                    format.copy_from_slice(&chunk.data[0..4]);
+
+                    Resource {
+                        format,
+                        data: data[offset..offset + 8 + chunk.data.len()].to_vec(),
+                    }
+                }
+                _ => Resource {
+                    format: chunk.id,
+                    data: chunk.data.to_vec(),
+                },
+            };
+
+            match usage {
+                b"Exec" => match &resource.format {
+                    b"ZCOD" => blorb.story = Some(resource.data),
+                    format => {
+                        return Err(format!(
+                            "the story in this blorb file is {}, not a z-machine story",
+                            String::from_utf8_lossy(format)
+                        ))
+                    }
+                },
+                b"Pict" => {
+                    blorb.pictures.insert(number, resource);
+                }
+                b"Snd " => {
+                    blorb.sounds.insert(number, resource);
+                }
+                // anything else ( like "Data" ) isn't anything we can use
+                _ => (),
+            }
+        }
+
+        Ok(blorb)
+    }
+}
+
+// jpegs are a run of segments, each a marker and ( mostly ) a length - the
+// size is in the "start of frame" segment, as height then width
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut cursor = 2;
+
+    while cursor + 9 <= data.len() {
+        if data[cursor] != 0xFF {
+            return None;
+        }
+
+        let marker = data[cursor + 1];
+
+        // 0xc4, 0xc8 and 0xcc share the range, but aren't frames
+        if (0xC0..=0xCF).contains(&marker) && marker != 0xC4 && marker != 0xC8 && marker != 0xCC {
+            return Some((
+                read_u16(data, cursor + 7) as u32,
+                read_u16(data, cursor + 5) as u32,
+            ));
+        }
+
+        cursor += 2 + read_u16(data, cursor + 2) as usize;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a blorb file with the resources in it, one chunk each, and then the
+    // extra chunks - the index gets the offsets right for us
+    fn blorb(
+        resources: &[(&[u8; 4], u32, &[u8; 4], &[u8])],
+        extra: &[(&[u8; 4], &[u8])],
+    ) -> Vec<u8> {
+        let mut form = FormWriter::new(b"IFRS");
+        let mut index = Vec::new();
+        push_u32(&mut index, resources.len() as u32);
+
+        // the index is the first chunk, after the 12 bytes of FORM header
+        let mut offset = 12 + 8 + 4 + resources.len() * 12;
+
+        for &(usage, number, _, data) in resources {
+            index.extend_from_slice(usage);
+            push_u32(&mut index, number);
+            push_u32(&mut index, offset as u32);
+
+            offset += 8 + data.len() + data.len() % 2;
+        }
+
+        form.write_chunk(b"RIdx", &index);
+
+        for &(_, _, id, data) in resources {
+            form.write_chunk(id, data);
+        }
+
+        for &(id, data) in extra {
+            form.write_chunk(id, data);
+        }
+
+        form.finish()
+    }
+
+    // the start of a png, as far as its size
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        push_u32(&mut data, width);
+        push_u32(&mut data, height);
+        data
+    }
+
+    #[test]
+    fn resources_are_found_through_the_index() {
+        // an aiff sound is a FORM of its own, type and all
+        let aiff = b"AIFFCOMM\x00\x00\x00\x02\x01\x02";
+
+        let data = blorb(
+            &[
+                (b"Exec", 0, b"ZCOD", b"\x05story"),
+                (b"Pict", 1, b"PNG ", &png(320, 200)),
+                (b"Pict", 2, b"Rect", b"\x00\x00\x00\x10\x00\x00\x00\x20"),
+                (b"Snd ", 3, b"FORM", aiff),
+                (b"Data", 4, b"BINA", b"\x00"),
+            ],
+            &[
+                (b"IFmd", b"<ifindex/>"),
+                (b"Fspc", b"\x00\x00\x00\x01"),
+                (b"RelN", b"\x00\x07"),
+            ],
+        );
+
+        assert!(Blorb::is_blorb(&data));
+
+        let blorb = Blorb::read(&data).unwrap();
+
+        assert_eq!(blorb.story, Some(b"\x05story".to_vec()));
+        assert_eq!(blorb.metadata, Some("<ifindex/>".to_string()));
+        assert_eq!((blorb.frontispiece, blorb.release), (Some(1), Some(7)));
+
+        assert_eq!(blorb.pictures.len(), 2);
+        assert_eq!(blorb.pictures[&1].picture_size(), Some((320, 200)));
+        assert_eq!(blorb.pictures[&2].picture_size(), Some((16, 32)));
+
+        let sound = &blorb.sounds[&3];
+        assert_eq!(&sound.format, b"AIFF");
+        assert_eq!(&sound.data[0..4], b"FORM");
+        assert_eq!(&sound.data[8..], &aiff[..]);
+    }
+
+    #[test]
+    fn stories_have_to_be_zcode() {
+        let data = blorb(&[(b"Exec", 0, b"GLUL", b"\x00")], &[]);
+
+        assert!(Blorb::read(&data).is_err());
+
+        // and pictures and sounds on their own are fine
+        let data = blorb(
+            &[(b"Pict", 1, b"Rect", b"\x00\x00\x00\x01\x00\x00\x00\x01")],
+            &[],
+        );
+
+        assert_eq!(Blorb::read(&data).unwrap().story, None);
+    }
+
+    #[test]
+    fn broken_indexes_are_errors() {
+        let mut data = blorb(&[(b"Exec", 0, b"ZCOD", b"\x05story")], &[]);
+
+        // an index with room for one entry says there are two
+        data[23] = 2;
+        assert!(Blorb::read(&data).is_err());
+
+        // an entry that points in the middle of a chunk
+        data[23] = 1;
+        data[35] += 2;
+        assert!(Blorb::read(&data).is_err());
+
+        let mut form = FormWriter::new(b"IFRS");
+        form.write_chunk(b"ZCOD", b"\x05story");
+        assert!(Blorb::read(&form.finish()).is_err());
+
+        assert!(Blorb::read(b"FORM\x00\x00\x00\x04IFZS").is_err());
+    }
+
+    #[test]
+    fn jpeg_sizes_come_from_the_frame() {
+        let mut jpeg = b"\xFF\xD8".to_vec();
+
+        // a segment to skip over, and a huffman table ( 0xc4 ) that looks
+        // like a frame but isn't one
+        jpeg.extend_from_slice(b"\xFF\xE0\x00\x04\x00\x00");
+        jpeg.extend_from_slice(b"\xFF\xC4\x00\x03\x00");
+        jpeg.extend_from_slice(b"\xFF\xC2\x00\x11\x08\x01\x2C\x02\x80\x03");
+
+        let picture = Resource {
+            format: *b"JPEG",
+            data: jpeg.clone(),
+        };
+        assert_eq!(picture.picture_size(), Some((640, 300)));
+
+        // without the frame, there's no size
+        assert_eq!(jpeg_size(&jpeg[..11]), None);
+        assert_eq!(
+            jpeg_size(b"\xFF\xD8\x00\x00\x00\x00\x00\x00\x00\x00\x00"),
+            None
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+
+// anything that can go wrong while the machine is running a story
+//
+// most of these mean the story file is broken ( or is doing something
+// "clever" that only worked on its original interpreter ), so there's
+// no real recovering from them, but they should never take the whole
+// process ( or page ) down with them
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    // the stack was popped with nothing on it, or a routine returned
+    // without a frame to return to
+    StackUnderflow,
+    // a local variable outside of the current routine's locals
+    InvalidLocal {
+        index: u8,
+    },
+    // a read or write outside of memory
+    MemoryOutOfRange {
+        address: u32,
+    },
+    // an operand the instruction needed wasn't given
+    OmittedOperand,
+    // the opcode doesn't exist ( or doesn't exist in this version )
+    IllegalInstruction {
+        form: String,
+        code: u8,
+    },
+    InvalidObject {
+        object: u16,
+    },
+    InvalidAttribute {
+        attribute: u16,
+    },
+    // a property the object doesn't have
+    UnknownProperty {
+        object: u16,
+        property: u8,
+    },
+    // get_prop/put_prop on a property longer than a word
+    InvalidPropertyLength {
+        object: u16,
+        property: u8,
+        length: u8,
+    },
+    MalformedObjectTree {
+        object: u16,
+    },
+    DivisionByZero,
+    // output stream 3 was opened more than 16 deep
+    StreamOverflow,
+    // version 6 only has windows 0 to 7, and properties 0 to 17
+    InvalidWindow {
+        window: u16,
+    },
+    InvalidWindowProperty {
+        property: u16,
+    },
+    // something the spec allows, but we don't do yet
+    Unimplemented {
+        feature: &'static str,
+    },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            &ErrorKind::InvalidLocal { index } => write!(f, "invalid local variable {}", index),
+            &ErrorKind::MemoryOutOfRange { address } => {
+                write!(f, "memory access out of range at {:x}", address)
+            }
+            &ErrorKind::OmittedOperand => write!(f, "a required operand was omitted"),
+            &ErrorKind::IllegalInstruction { ref form, code } => {
+                write!(
+                    f,
+                    "illegal instruction (form: {}, opcode: {:x})",
+                    form, code
+                )
+            }
+            &ErrorKind::InvalidObject { object } => write!(f, "invalid object {}", object),
+            &ErrorKind::InvalidAttribute { attribute } => {
+                write!(f, "invalid attribute {}", attribute)
+            }
+            &ErrorKind::UnknownProperty { object, property } => {
+                write!(f, "object {} has no property {}", object, property)
+            }
+            &ErrorKind::InvalidPropertyLength {
+                object,
+                property,
+                length,
+            } => write!(
+                f,
+                "property {} of object {} is {} bytes long, it can't be read as a word",
+                property, object, length
+            ),
+            &ErrorKind::MalformedObjectTree { object } => {
+                write!(f, "object tree is badly formed around object {}", object)
+            }
+            &ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            &ErrorKind::StreamOverflow => {
+                write!(f, "output stream 3 is nested too deep")
+            }
+            &ErrorKind::InvalidWindow { window } => write!(f, "invalid window {}", window),
+            &ErrorKind::InvalidWindowProperty { property } => {
+                write!(f, "invalid window property {}", property)
+            }
+            &ErrorKind::Unimplemented { feature } => write!(f, "{} is not supported", feature),
+        }
+    }
+}
+
+// an error, along with where it happened
+#[derive(Clone, Debug)]
+pub struct ZMachineError {
+    pub kind: ErrorKind,
+    // the address of the instruction that failed
+    pub pc: u32,
+    // the name of the instruction that failed
+    pub opcode: &'static str,
+}
+
+impl fmt::Display for ZMachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (pc: {:x}, opcode: {})",
+            self.kind, self.pc, self.opcode
+        )
+    }
+}
+
+impl std::error::Error for ZMachineError {}
+
+// what every instruction ( and anything they call into ) hands back
+pub type InstructionResult<T> = Result<T, ErrorKind>;
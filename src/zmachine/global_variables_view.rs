@@ -1,3 +1,4 @@
+use super::error::*;
 use super::memory_view::*;
 
 pub struct GlobalVariablesView {
@@ -7,18 +8,18 @@ pub struct GlobalVariablesView {
 
 impl GlobalVariablesView {
     // keep in mind this has to be 0..240, not 10..255
-    pub fn read_global(&self, index: u16) -> u16 {
+    pub fn read_global(&self, index: u16) -> InstructionResult<u16> {
         // each global is 2 bytes, so we multiply the offset by 2
         // after subtracting one
         let offset = index * 2;
 
-        let result = self.view.read_u16_at_head(offset as u32);
+        let result = self.view.read_u16_at_head(offset as u32)?;
 
-        result
+        Ok(result)
     }
 
-    pub fn write_global(&self, index: u16, value: u16) {
+    pub fn write_global(&self, index: u16, value: u16) -> InstructionResult<()> {
         let offset = index * 2;
-        self.view.write_u16_at_head(offset as u32, value);
+        self.view.write_u16_at_head(offset as u32, value)
     }
 }
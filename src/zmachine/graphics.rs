@@ -0,0 +1,811 @@
+use super::super::interfaces::zinterface::Capabilities;
+use super::blorb::Blorb;
+
+use std::cmp;
+use std::collections::BTreeMap;
+
+// version 6 throws out the upper and lower windows for eight windows of its
+// own, each placed on the screen in pixels, and adds pictures and the mouse
+//
+// we don't draw anything ourselves - this keeps track of where the windows
+// and their cursors are, and turns what the story does into draw commands,
+// which the interface can render however it likes ( see interfaces/renderer.rs
+// for one that writes frames out to image files )
+//
+// positions are in pixels, and start at 1 like the story's do - window
+// positions are on the screen, and cursors are inside their window
+
+pub const WINDOW_COUNT: usize = 8;
+
+// the bits of a window's attributes ( see window_style )
+pub const WRAPPING: u16 = 0x1;
+pub const SCROLLING: u16 = 0x2;
+pub const TRANSCRIPT: u16 = 0x4;
+pub const BUFFERED: u16 = 0x8;
+
+// the colour numbers 2 to 12 as true colours ( 5 bits each of blue,
+// green and red, from the top )
+const TRUE_COLOURS: [u16; 11] = [
+    0x0000, 0x001D, 0x0340, 0x03BD, 0x59A0, 0x7C1F, 0x77A0, 0x7FFF, 0x5AD6, 0x4631, 0x2D6B,
+];
+
+// colours in draw commands are true colours
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    // text with the top left of its first character at x, y
+    Text {
+        x: u16,
+        y: u16,
+        text: String,
+        font: u16,
+        style: u16,
+        foreground: u16,
+        background: u16,
+    },
+    // a picture from the story's resources, with its top left at x, y
+    Picture {
+        number: u16,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+    },
+    // fills a rectangle with a colour ( erasing windows, lines and pictures )
+    Fill {
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        colour: u16,
+    },
+    // moves what's in a rectangle up by pixels ( down if it's negative ),
+    // filling the gap with colour
+    Scroll {
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        pixels: i16,
+        colour: u16,
+    },
+}
+
+#[derive(Clone)]
+pub struct GraphicsWindow {
+    // where the window is on the screen, and how big it is
+    pub y: u16,
+    pub x: u16,
+    pub height: u16,
+    pub width: u16,
+
+    // ( y, x ) inside the window
+    pub cursor: (u16, u16),
+
+    // text is kept this far in from the left and right edges
+    pub margins: (u16, u16),
+
+    // a routine to call after some number of lines - we keep these for
+    // the story to read back, but don't call it
+    pub newline_interrupt: u16,
+    pub interrupt_countdown: u16,
+
+    pub style: u16,
+    pub font: u16,
+    // ( height, width ) of the font
+    pub font_size: (u16, u16),
+    pub attributes: u16,
+    pub line_count: u16,
+
+    // ( foreground, background ), as colour numbers and as true colours
+    pub colours: (u16, u16),
+    pub true_colours: (u16, u16),
+}
+
+impl GraphicsWindow {
+    fn new(capabilities: &Capabilities) -> GraphicsWindow {
+        let colours = (
+            capabilities.foreground as u16,
+            capabilities.background as u16,
+        );
+
+        GraphicsWindow {
+            y: 1,
+            x: 1,
+            height: 0,
+            width: 0,
+            cursor: (1, 1),
+            margins: (0, 0),
+            newline_interrupt: 0,
+            interrupt_countdown: 0,
+            style: 0,
+            font: 1,
+            font_size: (
+                capabilities.font_height as u16,
+                capabilities.font_width as u16,
+            ),
+            attributes: 0,
+            line_count: 0,
+            colours,
+            true_colours: (true_colour(colours.0), true_colour(colours.1)),
+        }
+    }
+
+    // the window properties, as get_wind_prop and put_wind_prop number them
+    pub fn get_property(&self, property: u16) -> Option<u16> {
+        Some(match property {
+            0 => self.y,
+            1 => self.x,
+            2 => self.height,
+            3 => self.width,
+            4 => self.cursor.0,
+            5 => self.cursor.1,
+            6 => self.margins.0,
+            7 => self.margins.1,
+            8 => self.newline_interrupt,
+            9 => self.interrupt_countdown,
+            10 => self.style,
+            11 => self.colours.0 | (self.colours.1 << 8),
+            12 => self.font,
+            13 => (self.font_size.0 << 8) | self.font_size.1,
+            14 => self.attributes,
+            15 => self.line_count,
+            16 => self.true_colours.0,
+            17 => self.true_colours.1,
+            _ => return None,
+        })
+    }
+
+    pub fn set_property(&mut self, property: u16, value: u16) -> bool {
+        match property {
+            0 => self.y = value,
+            1 => self.x = value,
+            2 => self.height = value,
+            3 => self.width = value,
+            4 => self.cursor.0 = value,
+            5 => self.cursor.1 = value,
+            6 => self.margins.0 = value,
+            7 => self.margins.1 = value,
+            8 => self.newline_interrupt = value,
+            9 => self.interrupt_countdown = value,
+            10 => self.style = value,
+            11 => self.colours = (value & 0xFF, value >> 8),
+            12 => self.font = value,
+            13 => self.font_size = (value >> 8, value & 0xFF),
+            14 => self.attributes = value,
+            15 => self.line_count = value,
+            16 => self.true_colours.0 = value,
+            17 => self.true_colours.1 = value,
+            _ => return false,
+        }
+
+        true
+    }
+
+    // the whole window, as a fill
+    fn fill(&self) -> DrawCommand {
+        DrawCommand::Fill {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            colour: self.true_colours.1,
+        }
+    }
+
+    // the cursor on the screen
+    fn cursor_on_screen(&self) -> (u16, u16) {
+        (
+            self.y.saturating_add(self.cursor.0.saturating_sub(1)),
+            self.x.saturating_add(self.cursor.1.saturating_sub(1)),
+        )
+    }
+
+    // the cursor can't be left outside of the window
+    fn keep_cursor_inside(&mut self) {
+        if self.cursor.0 > self.height || self.cursor.1 > self.width {
+            self.cursor = (1, self.margins.0.saturating_add(1));
+        }
+    }
+}
+
+pub struct Graphics {
+    pub windows: Vec<GraphicsWindow>,
+    pub current: usize,
+
+    // ( width, height ) of the screen
+    pub screen: (u16, u16),
+
+    // the colours the interface starts text in, as colour numbers - set_colour
+    // goes back to these for colour 1
+    pub default_colours: (u16, u16),
+
+    // the window mouse clicks count in, 0xFFFF for anywhere
+    pub mouse_window: u16,
+
+    // ( width, height ) of each picture the story has, and the release
+    // number of the pictures
+    pub pictures: BTreeMap<u16, (u16, u16)>,
+    pub pictures_release: u16,
+}
+
+impl Graphics {
+    // window 0 starts out as the whole screen, and everything else
+    // is empty in the top left corner
+    pub fn new(capabilities: &Capabilities) -> Graphics {
+        let screen = (
+            capabilities.screen_width as u16 * capabilities.font_width as u16,
+            capabilities.screen_height as u16 * capabilities.font_height as u16,
+        );
+
+        let mut windows = vec![GraphicsWindow::new(capabilities); WINDOW_COUNT];
+
+        windows[0].width = screen.0;
+        windows[0].height = screen.1;
+        windows[0].attributes = WRAPPING | SCROLLING | TRANSCRIPT | BUFFERED;
+
+        Graphics {
+            windows,
+            current: 0,
+            screen,
+            default_colours: (
+                capabilities.foreground as u16,
+                capabilities.background as u16,
+            ),
+            mouse_window: 1,
+            pictures: BTreeMap::new(),
+            pictures_release: 0,
+        }
+    }
+
+    // the pictures, and their sizes, come from the story's blorb file
+    pub fn load_pictures(&mut self, resources: &Blorb) {
+        self.pictures = resources
+            .pictures
+            .iter()
+            .filter_map(|(&number, picture)| {
+                let (width, height) = picture.picture_size()?;
+                Some((number as u16, (width as u16, height as u16)))
+            })
+            .collect();
+
+        self.pictures_release = resources.release.unwrap_or(0);
+    }
+
+    // the story's number for a window - -3 means the current one
+    pub fn window_index(&self, window: u16) -> Option<usize> {
+        match window as i16 {
+            -3 => Some(self.current),
+            w @ 0..=7 => Some(w as usize),
+            _ => None,
+        }
+    }
+
+    pub fn current_window(&mut self) -> &mut GraphicsWindow {
+        &mut self.windows[self.current]
+    }
+
+    // prints to the current window, wrapping ( and scrolling ) if the window
+    // does - text that doesn't fit is lost, like on a real screen
+    pub fn print(&mut self, text: &str) -> Vec<DrawCommand> {
+        let mut commands = Vec::new();
+
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                commands.extend(self.new_line());
+            }
+
+            let mut rest: Vec<char> = line.chars().collect();
+
+            while !rest.is_empty() {
+                let window = &self.windows[self.current];
+                let (font_height, font_width) = window.font_size;
+                let wrapping = window.attributes & WRAPPING != 0;
+
+                let right_edge = window.width.saturating_sub(window.margins.1);
+                let room = match wrapping {
+                    true => {
+                        (right_edge.saturating_sub(window.cursor.1.saturating_sub(1))
+                            / cmp::max(font_width, 1)) as usize
+                    }
+                    false => rest.len(),
+                };
+
+                // nothing fits on this line, so we start another - if we
+                // are already at the start of one, it never will
+                if room == 0 {
+                    if window.cursor.1 <= window.margins.0.saturating_add(1) {
+                        break;
+                    }
+
+                    commands.extend(self.new_line());
+                    continue;
+                }
+
+                // we wrap at the last space that fits, if there is one
+                let mut count = cmp::min(room, rest.len());
+
+                if count < rest.len() {
+                    if let Some(space) = rest[..count].iter().rposition(|&c| c == ' ') {
+                        count = space + 1;
+                    }
+                }
+
+                let piece: String = rest[..count].iter().collect();
+                let (y, x) = window.cursor_on_screen();
+
+                // text that falls out of the bottom of the window is lost
+                if window
+                    .cursor
+                    .0
+                    .saturating_add(font_height)
+                    .saturating_sub(1)
+                    <= window.height
+                {
+                    commands.push(DrawCommand::Text {
+                        x,
+                        y,
+                        text: piece,
+                        font: window.font,
+                        style: window.style,
+                        foreground: window.true_colours.0,
+                        background: window.true_colours.1,
+                    });
+                }
+
+                let cursor = &mut self.current_window().cursor;
+                cursor.1 = cursor.1.saturating_add(count as u16 * font_width);
+                rest.drain(..count);
+
+                if !rest.is_empty() {
+                    commands.extend(self.new_line());
+
+                    // the space we wrapped at doesn't start the next line
+                    while rest.first() == Some(&' ') {
+                        rest.remove(0);
+                    }
+                }
+            }
+        }
+
+        commands
+    }
+
+    // moves the cursor to the start of the next line, scrolling the window
+    // if it does and the line doesn't fit
+    fn new_line(&mut self) -> Vec<DrawCommand> {
+        let window = self.current_window();
+        let font_height = window.font_size.0;
+
+        window.cursor = (
+            window.cursor.0.saturating_add(font_height),
+            window.margins.0.saturating_add(1),
+        );
+        window.line_count = window.line_count.wrapping_add(1);
+
+        let bottom = window
+            .cursor
+            .0
+            .saturating_add(font_height)
+            .saturating_sub(1);
+
+        if bottom <= window.height || window.attributes & SCROLLING == 0 {
+            return Vec::new();
+        }
+
+        // a window shorter than a line keeps its cursor at the top
+        let overflow = bottom - window.height;
+        window.cursor.0 = cmp::max(window.cursor.0.saturating_sub(overflow), 1);
+
+        vec![DrawCommand::Scroll {
+            x: window.x,
+            y: window.y,
+            width: window.width,
+            height: window.height,
+            pixels: overflow as i16,
+            colour: window.true_colours.1,
+        }]
+    }
+
+    // window 1 gets lines pixels at the top of the screen, and window 0
+    // gets the rest
+    pub fn split(&mut self, lines: u16) {
+        let (width, height) = self.screen;
+        let lines = cmp::min(lines, height);
+
+        {
+            let upper = &mut self.windows[1];
+            upper.y = 1;
+            upper.x = 1;
+            upper.width = width;
+            upper.height = lines;
+            upper.keep_cursor_inside();
+        }
+
+        let lower = &mut self.windows[0];
+        lower.y = lines + 1;
+        lower.x = 1;
+        lower.width = width;
+        lower.height = height - lines;
+        lower.keep_cursor_inside();
+    }
+
+    pub fn erase_window(&mut self, window: usize) -> DrawCommand {
+        let window = &mut self.windows[window];
+        window.cursor = (1, window.margins.0.saturating_add(1));
+
+        window.fill()
+    }
+
+    // clears the whole screen in window 0's background ( and, with unsplit,
+    // gives window 0 the whole screen back )
+    pub fn erase_screen(&mut self, unsplit: bool) -> DrawCommand {
+        if unsplit {
+            self.split(0);
+            self.current = 0;
+        }
+
+        for window in self.windows.iter_mut() {
+            window.cursor = (1, window.margins.0.saturating_add(1));
+        }
+
+        DrawCommand::Fill {
+            x: 1,
+            y: 1,
+            width: self.screen.0,
+            height: self.screen.1,
+            colour: self.windows[0].true_colours.1,
+        }
+    }
+
+    // erases from the cursor to the end of the line ( 1 ), or that many pixels
+    pub fn erase_line(&mut self, pixels: u16) -> DrawCommand {
+        let window = &self.windows[self.current];
+        let (y, x) = window.cursor_on_screen();
+
+        let to_end = window
+            .width
+            .saturating_sub(window.margins.1)
+            .saturating_add(1)
+            .saturating_sub(window.cursor.1);
+
+        DrawCommand::Fill {
+            x,
+            y,
+            width: match pixels {
+                1 => to_end,
+                p => cmp::min(p, to_end),
+            },
+            height: window.font_size.0,
+            colour: window.true_colours.1,
+        }
+    }
+
+    pub fn scroll(&mut self, window: usize, pixels: i16) -> DrawCommand {
+        let window = &self.windows[window];
+
+        DrawCommand::Scroll {
+            x: window.x,
+            y: window.y,
+            width: window.width,
+            height: window.height,
+            pixels,
+            colour: window.true_colours.1,
+        }
+    }
+
+    // moving or resizing a window doesn't redraw anything, the story
+    // does that itself
+    pub fn move_window(&mut self, window: usize, y: u16, x: u16) {
+        let window = &mut self.windows[window];
+        window.y = y;
+        window.x = x;
+    }
+
+    pub fn resize_window(&mut self, window: usize, height: u16, width: u16) {
+        let window = &mut self.windows[window];
+        window.height = height;
+        window.width = width;
+        window.keep_cursor_inside();
+    }
+
+    // a cursor left inside a margin moves out to the left one
+    pub fn set_margins(&mut self, window: usize, left: u16, right: u16) {
+        let window = &mut self.windows[window];
+        window.margins = (left, right);
+
+        if window.cursor.1 <= left || window.cursor.1 > window.width.saturating_sub(right) {
+            window.cursor.1 = left.saturating_add(1);
+        }
+    }
+
+    // operation 0 sets the attributes to flags, 1 turns those bits on,
+    // 2 turns them off and 3 flips them
+    pub fn set_attributes(&mut self, window: usize, flags: u16, operation: u16) {
+        let window = &mut self.windows[window];
+
+        window.attributes = match operation {
+            1 => window.attributes | flags,
+            2 => window.attributes & !flags,
+            3 => window.attributes ^ flags,
+            _ => flags,
+        };
+    }
+
+    // where a picture goes - y and x are inside the current window, and 0
+    // means wherever the cursor is
+    fn picture_position(&self, y: u16, x: u16) -> (u16, u16) {
+        let window = &self.windows[self.current];
+        let (cursor_y, cursor_x) = window.cursor_on_screen();
+
+        (
+            match y {
+                0 => cursor_y,
+                y => window.y.saturating_add(y - 1),
+            },
+            match x {
+                0 => cursor_x,
+                x => window.x.saturating_add(x - 1),
+            },
+        )
+    }
+
+    pub fn draw_picture(&self, number: u16, y: u16, x: u16) -> Option<DrawCommand> {
+        let &(width, height) = self.pictures.get(&number)?;
+        let (y, x) = self.picture_position(y, x);
+
+        Some(DrawCommand::Picture {
+            number,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    pub fn erase_picture(&self, number: u16, y: u16, x: u16) -> Option<DrawCommand> {
+        let &(width, height) = self.pictures.get(&number)?;
+        let (y, x) = self.picture_position(y, x);
+
+        Some(DrawCommand::Fill {
+            x,
+            y,
+            width,
+            height,
+            colour: self.windows[self.current].true_colours.1,
+        })
+    }
+
+    // 0 leaves a colour alone, and 1 goes back to the default
+    pub fn set_colours(&mut self, window: usize, foreground: u16, background: u16) {
+        let defaults = self.default_colours;
+        let window = &mut self.windows[window];
+
+        let pick = |colour: u16, current: u16, default: u16| match colour {
+            1 => default,
+            2..=12 => colour,
+            _ => current,
+        };
+
+        window.colours = (
+            pick(foreground, window.colours.0, defaults.0),
+            pick(background, window.colours.1, defaults.1),
+        );
+
+        window.true_colours = (true_colour(window.colours.0), true_colour(window.colours.1));
+    }
+
+    // -1 goes back to the default, and anything else that isn't a colour
+    // leaves it alone
+    pub fn set_true_colours(&mut self, window: usize, foreground: u16, background: u16) {
+        let defaults = self.default_colours;
+        let window = &mut self.windows[window];
+
+        let pick = |colour: u16, current: u16, default: u16| match colour {
+            0xFFFF => true_colour(default),
+            c if c <= 0x7FFF => c,
+            _ => current,
+        };
+
+        window.true_colours = (
+            pick(foreground, window.true_colours.0, defaults.0),
+            pick(background, window.true_colours.1, defaults.1),
+        );
+    }
+}
+
+pub fn true_colour(colour: u16) -> u16 {
+    match colour {
+        2..=12 => TRUE_COLOURS[colour as usize - 2],
+        // anything else is the default foreground, white
+        _ => 0x7FFF,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a screen of 10 by 5 characters, 8 pixels wide and 10 high - so 80 by 50
+    fn graphics() -> Graphics {
+        Graphics::new(&Capabilities {
+            screen_width: 10,
+            screen_height: 5,
+            font_width: 8,
+            font_height: 10,
+            ..Capabilities::default()
+        })
+    }
+
+    // white on black, the colours the interface starts with
+    fn text(x: u16, y: u16, text: &str) -> DrawCommand {
+        DrawCommand::Text {
+            x,
+            y,
+            text: text.to_string(),
+            font: 1,
+            style: 0,
+            foreground: 0x7FFF,
+            background: 0x0000,
+        }
+    }
+
+    #[test]
+    fn window_0_starts_as_the_whole_screen() {
+        let graphics = graphics();
+        let window = &graphics.windows[0];
+
+        assert_eq!(graphics.screen, (80, 50));
+        assert_eq!(
+            (window.y, window.x, window.height, window.width),
+            (1, 1, 50, 80)
+        );
+        assert_eq!(
+            window.attributes,
+            WRAPPING | SCROLLING | TRANSCRIPT | BUFFERED
+        );
+        assert_eq!(graphics.windows[1].height, 0);
+
+        assert_eq!(graphics.window_index(0xFFFD), Some(0));
+        assert_eq!(graphics.window_index(7), Some(7));
+        assert_eq!(graphics.window_index(8), None);
+    }
+
+    #[test]
+    fn properties_round_trip() {
+        let mut graphics = graphics();
+        let window = graphics.current_window();
+
+        // colours are packed background first, and font sizes height first
+        assert_eq!(window.get_property(11), Some(9 | (2 << 8)));
+        assert_eq!(window.get_property(13), Some((10 << 8) | 8));
+
+        for property in 0..18 {
+            assert!(window.set_property(property, 0x0304));
+            assert_eq!(window.get_property(property), Some(0x0304));
+        }
+
+        assert!(!window.set_property(18, 1));
+        assert_eq!(window.get_property(18), None);
+    }
+
+    #[test]
+    fn splitting_gives_the_top_to_window_1() {
+        let mut graphics = graphics();
+        graphics.windows[0].cursor = (41, 9);
+
+        graphics.split(20);
+
+        let (upper, lower) = (&graphics.windows[1], &graphics.windows[0]);
+        assert_eq!((upper.y, upper.height, upper.width), (1, 20, 80));
+        assert_eq!((lower.y, lower.height, lower.width), (21, 30, 80));
+
+        // the cursor was left below the bottom of window 0
+        assert_eq!(lower.cursor, (1, 1));
+
+        // and there's only so much screen to give
+        graphics.split(60);
+        assert_eq!(
+            (graphics.windows[1].height, graphics.windows[0].height),
+            (50, 0)
+        );
+    }
+
+    #[test]
+    fn text_wraps_at_spaces() {
+        let mut graphics = graphics();
+
+        assert_eq!(
+            graphics.print("hello there world"),
+            vec![
+                text(1, 1, "hello "),
+                text(1, 11, "there "),
+                text(1, 21, "world"),
+            ]
+        );
+        assert_eq!(graphics.windows[0].cursor, (21, 41));
+        assert_eq!(graphics.windows[0].line_count, 2);
+    }
+
+    #[test]
+    fn full_windows_scroll() {
+        let mut graphics = graphics();
+        let commands = graphics.print("a\nb\nc\nd\ne\nf");
+
+        // the sixth line doesn't fit, so everything goes up a line for it
+        assert_eq!(
+            commands[5..].to_vec(),
+            vec![
+                DrawCommand::Scroll {
+                    x: 1,
+                    y: 1,
+                    width: 80,
+                    height: 50,
+                    pixels: 10,
+                    colour: 0,
+                },
+                text(1, 41, "f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_below_windows_that_dont_scroll_is_lost() {
+        let mut graphics = graphics();
+        graphics.set_attributes(0, SCROLLING, 2);
+
+        let commands = graphics.print("a\nb\nc\nd\ne\nf");
+
+        assert_eq!(commands.len(), 5);
+        assert_eq!(commands[4], text(1, 41, "e"));
+    }
+
+    #[test]
+    fn erasing_and_scrolling_cover_the_whole_window() {
+        let mut graphics = graphics();
+        graphics.split(20);
+        graphics.set_margins(0, 8, 0);
+        graphics.windows[0].cursor = (11, 33);
+
+        assert_eq!(
+            graphics.erase_window(0),
+            DrawCommand::Fill {
+                x: 1,
+                y: 21,
+                width: 80,
+                height: 30,
+                colour: 0,
+            }
+        );
+
+        // the cursor goes back to the top, inside the margin
+        assert_eq!(graphics.windows[0].cursor, (1, 9));
+
+        assert_eq!(
+            graphics.scroll(1, -4),
+            DrawCommand::Scroll {
+                x: 1,
+                y: 1,
+                width: 80,
+                height: 20,
+                pixels: -4,
+                colour: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn colours_can_be_left_alone_or_reset() {
+        let mut graphics = graphics();
+
+        graphics.set_colours(0, 3, 4);
+        assert_eq!(graphics.windows[0].colours, (3, 4));
+        assert_eq!(graphics.windows[0].true_colours, (0x001D, 0x0340));
+
+        // 0 keeps the current colour, 1 goes back to the default
+        graphics.set_colours(0, 0, 1);
+        assert_eq!(graphics.windows[0].colours, (3, 2));
+
+        graphics.set_true_colours(0, 0x1234, 0xFFFF);
+        assert_eq!(graphics.windows[0].true_colours, (0x1234, 0x0000));
+    }
+}
@@ -1,4 +1,4 @@
-use super::memory_view::*;
+use super::super::interfaces::zinterface::Capabilities;
 
 use std::borrow::Borrow;
 use std::cell::Ref;
@@ -11,9 +11,38 @@ use std::rc::*;
 //
 // http://inform-fiction.org/zmachine/standards/z1point1/sect04.html
 
+// what the header says the story adds up to, and what it actually does
+pub struct Checksum {
+    pub expected: u16,
+    pub actual: u16,
+}
+
+impl Checksum {
+    pub fn matches(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+// the header is always all there ( validate and checksum make sure of that
+// first ), so unlike the rest of memory it's read without checking
+fn read_u16(memory: &[u8], address: usize) -> u16 {
+    ((memory[address] as u16) << 8) | memory[address + 1] as u16
+}
+
+fn write_u16(memory: &mut [u8], address: usize, value: u16) {
+    memory[address] = (value >> 8) as u8;
+    memory[address + 1] = value as u8;
+}
+
+// the interpreter version we tell stories we are ( a letter, except in version 6 ),
+// and the revision of the standard we follow
+pub const INTERPRETER_VERSION: u8 = b'A';
+pub const STANDARD_REVISION: (u8, u8) = (1, 1);
+
 pub struct Header {
     memory: Rc<RefCell<Vec<u8>>>,
-    // this is the version of the game, we support up to 3 so far
+    // this is the version of the game, we support all of them ( 6 is
+    // the graphical version, and is a different beast altogether - see graphics.rs )
     pub version: u8,
     // these are flags set for the game;
     pub flags: HeaderFlags,
@@ -31,6 +60,15 @@ pub struct Header {
     pub static_memory_start_location: u16,
     // where are the abbreviations?
     pub abbreviations_location: u16,
+    // versions 6 and 7 can have more routines and strings than fit in a packed
+    // address, so packed addresses are offset by these ( in units of 8 bytes )
+    pub routines_offset: u16,
+    pub strings_offset: u16,
+    // from version 5, where the story's own alphabet table is ( 0 if it uses
+    // the default one )
+    pub alphabet_location: u16,
+    // from version 5, where the header extension table is ( 0 if there isn't one )
+    pub extension_location: u16,
 }
 
 impl Header {
@@ -41,14 +79,110 @@ impl Header {
     // interpreter settable attributes", such as options,
     // can be set directly to the struct
 
-    pub fn fixed_font(&self) -> bool {
-        let memory = self.get_memory();
-        (memory[0x10] | 1 << 1) > 0
-    }
+    //
+    // flags 2 is a word at 0x10, so its bottom bits are in the
+    // second byte
 
     pub fn transcripting(&self) -> bool {
         let memory = self.get_memory();
-        (memory[0x10] | 1 << 0) > 0
+        memory[0x11] & (1 << 0) > 0
+    }
+
+    // the transcript bit is the one both the game and the interpreter
+    // get to change
+    pub fn set_transcripting(&self, on: bool) {
+        let header_reference: &RefCell<Vec<u8>> = self.memory.borrow();
+        let mut memory = header_reference.borrow_mut();
+
+        memory[0x11] = match on {
+            true => memory[0x11] | 1,
+            false => memory[0x11] & !1,
+        };
+    }
+
+    // parts of the header are the interpreter's to fill in, for the story to
+    // read - the story file has whatever the original interpreter put there,
+    // so this has to happen again whenever dynamic memory is replaced ( by a
+    // restart or a restore )
+    pub fn write_capabilities(&mut self, capabilities: &Capabilities) {
+        let header_reference: &RefCell<Vec<u8>> = self.memory.borrow();
+        let mut memory = header_reference.borrow_mut();
+
+        match self.version {
+            1..=3 => {
+                // the status line bit is set when there *isn't* one
+                let mut flag_byte = memory[0x1] & !0b0111_0000;
+
+                if !capabilities.status_line {
+                    flag_byte |= 0x10;
+                }
+
+                if capabilities.split_screen {
+                    flag_byte |= 0x20;
+                }
+
+                if capabilities.variable_pitch {
+                    flag_byte |= 0x40;
+                }
+
+                memory[0x1] = flag_byte;
+            }
+            _ => {
+                // colours, pictures, styles, sound and timed input - we don't
+                // do most of them ( yet ), except that version 6 gets colours
+                // and pictures when there's somewhere to draw them, and
+                // anything gets sound when the interface can make some
+                let mut flag_byte = match self.version {
+                    6 if capabilities.graphics => 0b11,
+                    _ => 0,
+                };
+
+                if capabilities.sound {
+                    flag_byte |= 0x20;
+                }
+
+                memory[0x1] = flag_byte;
+
+                memory[0x1E] = capabilities.interpreter_number;
+                memory[0x1F] = match self.version {
+                    6 => 1,
+                    _ => INTERPRETER_VERSION,
+                };
+                memory[0x20] = capabilities.screen_height;
+                memory[0x21] = capabilities.screen_width;
+            }
+        }
+
+        if self.version >= 5 {
+            // the screen size again, but in units rather than characters
+            write_u16(
+                &mut memory,
+                0x22,
+                capabilities.screen_width as u16 * capabilities.font_width as u16,
+            );
+            write_u16(
+                &mut memory,
+                0x24,
+                capabilities.screen_height as u16 * capabilities.font_height as u16,
+            );
+
+            // version 6 has these the other way around
+            let (first, second) = match self.version {
+                6 => (capabilities.font_height, capabilities.font_width),
+                _ => (capabilities.font_width, capabilities.font_height),
+            };
+
+            memory[0x26] = first;
+            memory[0x27] = second;
+
+            memory[0x2C] = capabilities.background;
+            memory[0x2D] = capabilities.foreground;
+        }
+
+        memory[0x32] = STANDARD_REVISION.0;
+        memory[0x33] = STANDARD_REVISION.1;
+
+        self.flags = HeaderFlags::process_header(memory[0x1], self.version);
     }
 
     fn get_memory(&self) -> Ref<Vec<u8>> {
@@ -57,18 +191,109 @@ impl Header {
         header_memory
     }
 
+    // sanity checks a story file before we try to build a machine out of it,
+    // so a bad file gets a readable error instead of a panic deep in the vm
+    pub fn validate(data: &[u8]) -> Result<(), String> {
+        // the header alone is 64 bytes
+        if data.len() < 64 {
+            return Err(format!(
+                "story file is too small to be a story file ({} bytes)",
+                data.len()
+            ));
+        }
+
+        let version = data[0x0];
+
+        match version {
+            1..=8 => (),
+            _ => {
+                return Err(format!(
+                    "story file is version {}, only versions 1 to 8 are supported",
+                    version
+                ))
+            }
+        }
+
+        // the file length is stored divided by a constant that depends
+        // on the version, and some very early files just leave it as 0
+        let file_length = read_u16(data, 0x1A) as usize * Header::file_length_scale(version);
+
+        if file_length > data.len() {
+            return Err(format!(
+                "story file is truncated (expected {} bytes, found {})",
+                file_length,
+                data.len()
+            ));
+        }
+
+        let static_start = read_u16(data, 0xE) as usize;
+        let pc_start = read_u16(data, 0x6) as usize;
+
+        // in version 6 the start is the packed address of a routine instead
+        let pc_start = match version {
+            6 => pc_start * 4 + read_u16(data, 0x28) as usize * 8,
+            _ => pc_start,
+        };
+
+        if static_start > data.len() || pc_start >= data.len() {
+            return Err("story file header points past the end of the file".to_string());
+        }
+
+        Ok(())
+    }
+
+    // adds up every byte of the story after the header, up to the file length
+    // it claims to have ( which is how the original interpreters checked for a
+    // bad disk ) - the total should match the checksum stored at 0x1C
+    //
+    // this needs the story as it was loaded, since the game will have written
+    // all over dynamic memory by the time it asks
+    pub fn checksum(data: &[u8]) -> Result<Checksum, String> {
+        // the length and the checksum are both in the header
+        if data.len() < 64 {
+            return Err(format!(
+                "story file is too small to have a checksum ({} bytes)",
+                data.len()
+            ));
+        }
+
+        let version = data[0x0];
+
+        // padding past the file length doesn't count, and very early files
+        // leave the length as 0, so we just use all of it
+        let file_length = match read_u16(data, 0x1A) as usize * Header::file_length_scale(version) {
+            0 => data.len(),
+            x => x.min(data.len()).max(0x40),
+        };
+
+        let actual = data[0x40..file_length]
+            .iter()
+            .fold(0u16, |sum, &x| sum.wrapping_add(x as u16));
+
+        Ok(Checksum {
+            expected: read_u16(data, 0x1C),
+            actual,
+        })
+    }
+
+    pub fn file_length_scale(version: u8) -> usize {
+        match version {
+            1..=3 => 2,
+            4..=5 => 4,
+            _ => 8,
+        }
+    }
+
     pub fn create(memory: Rc<RefCell<Vec<u8>>>) -> Header {
         // make two clones - one will be dropped by the end of the scope
         let memory_for_struct = memory.clone();
         let memory_for_header = memory.clone();
 
-        let view = MemoryView {
-            memory: memory_for_struct,
-            // the header pointer is the top of the memory
-            pointer: 0,
-        };
+        let header_reference: &RefCell<Vec<u8>> = memory_for_struct.borrow();
+        let header_memory = header_reference.borrow();
+        let data: &[u8] = &header_memory;
 
-        let version = view.read_at(0x0);
+        let version = data[0x0];
 
         let obj = Header {
             // header needs its own reference to build views, and also mutate values
@@ -77,43 +302,57 @@ impl Header {
             // itself
             memory: memory_for_header,
             version: version,
-            flags: HeaderFlags::process_header(&view, version),
-            hi_memory_start: view.read_u16_at(0x4),
-            pc_start: view.read_u16_at(0x6),
-            dictionary_location: view.read_u16_at(0x8),
-            object_table_location: view.read_u16_at(0xA),
-            global_vars_table_location: view.read_u16_at(0xC),
-            static_memory_start_location: view.read_u16_at(0xE),
-            abbreviations_location: view.read_u16_at(0x18),
+            flags: HeaderFlags::process_header(data[0x1], version),
+            hi_memory_start: read_u16(data, 0x4),
+            pc_start: read_u16(data, 0x6),
+            dictionary_location: read_u16(data, 0x8),
+            object_table_location: read_u16(data, 0xA),
+            global_vars_table_location: read_u16(data, 0xC),
+            static_memory_start_location: read_u16(data, 0xE),
+            abbreviations_location: read_u16(data, 0x18),
+            routines_offset: read_u16(data, 0x28),
+            strings_offset: read_u16(data, 0x2A),
+            alphabet_location: read_u16(data, 0x34),
+            extension_location: read_u16(data, 0x36),
         };
 
         obj
     }
 
     pub fn get_status(&self) -> String {
-        let header_flags = match self.flags {
-            HeaderFlags::V1 { ref flags } => flags,
-            HeaderFlags::NotImplemented => panic!("not implemented!"),
-        };
+        let flags_string: String = match self.flags {
+            HeaderFlags::V1 { ref flags } => {
+                // we are actually copying the status line value from the reference
+                // here - unit enums implement Copy by default
 
-        // we are actually copying the status line value from the reference
-        // here - unit enums implement Copy by default
+                let status_type: &str = match flags.status_line {
+                    StatusLineType::Hours => "Show hours and minutes in status line",
+                    StatusLineType::Score => "Show score and moves in status line",
+                };
 
-        let status_type: &str = match header_flags.status_line {
-            StatusLineType::Hours => "Show hours and minutes in status line",
-            StatusLineType::Score => "Show score and moves in status line",
+                format!(
+                    "status: {}\nsplit_story: {}\nshow_status_line: \
+                     {}\nsplit_screen: {}\n\
+                     variable_pitch_font: {}\n",
+                    status_type,
+                    flags.split_story,
+                    flags.split_screen,
+                    flags.show_status_line,
+                    flags.variable_pitch_font
+                )
+            }
+            HeaderFlags::V4 { ref flags } => format!(
+                "colours: {}\npictures: {}\nbold: {}\nitalic: {}\n\
+                 fixed_space: {}\nsound: {}\ntimed_input: {}\n",
+                flags.colours,
+                flags.pictures,
+                flags.bold,
+                flags.italic,
+                flags.fixed_space,
+                flags.sound,
+                flags.timed_input
+            ),
         };
-
-        let flags_string: String = format!(
-            "status: {}\nsplit_story: {}\nshow_status_line: \
-             {}\nsplit_screen: {}\n\
-             variable_pitch_font: {}\n",
-            status_type,
-            header_flags.split_story,
-            header_flags.split_screen,
-            header_flags.show_status_line,
-            header_flags.variable_pitch_font
-        );
         let debug: String = format!(
             "version: {}\n{}\nhi_memory_start: {:x}\npc_start: \
              {:x}\ndictionary_location:{:x}\nobject_table_location: \
@@ -133,45 +372,57 @@ impl Header {
     }
 }
 
-// just one enum for now
+// flags 1 means something completely different before and after version 4
 pub enum HeaderFlags {
     V1 { flags: HeaderFlagsV1 },
-    //we add a stub header flag so that matches against this header
-    //flag are not considered irrefutable - we are guarding for the
-    //future
-    NotImplemented,
+    V4 { flags: HeaderFlagsV4 },
 }
 
 impl HeaderFlags {
-    fn process_header(view: &MemoryView, version: u8) -> HeaderFlags {
+    fn process_header(flag_byte: u8, version: u8) -> HeaderFlags {
         match version {
-            1..=3 => HeaderFlags::process_v1_header(view),
-            _ => panic!("This interpreter only supports up to ZMachine version 3 at this time."),
+            1..=3 => HeaderFlags::process_v1_header(flag_byte),
+            _ => HeaderFlags::process_v4_header(flag_byte),
         }
     }
 
-    fn process_v1_header(view: &MemoryView) -> HeaderFlags {
-        let flag_byte = view.read_at(0x1);
+    fn process_v4_header(flag_byte: u8) -> HeaderFlags {
+        // these are all things the interpreter says it can do - the story
+        // only reads them
+        HeaderFlags::V4 {
+            flags: HeaderFlagsV4 {
+                colours: flag_byte & 0x1 > 0,
+                pictures: flag_byte & 0x2 > 0,
+                bold: flag_byte & 0x4 > 0,
+                italic: flag_byte & 0x8 > 0,
+                fixed_space: flag_byte & 0x10 > 0,
+                sound: flag_byte & 0x20 > 0,
+                // bit 6 isn't used
+                timed_input: flag_byte & 0x80 > 0,
+            },
+        }
+    }
 
+    fn process_v1_header(flag_byte: u8) -> HeaderFlags {
         HeaderFlags::V1 {
             flags: HeaderFlagsV1 {
-                // first bit, if set, means we use the
+                // bit 1 of byte 1, if set, means we use the
                 // hours status line instead of score
-                status_line: match flag_byte & 0x1 {
-                    0x1 => StatusLineType::Hours,
+                status_line: match flag_byte & 0x2 {
+                    0x2 => StatusLineType::Hours,
                     // this is 0 and the default
                     _ => StatusLineType::Score,
                 },
 
                 // bit 2 of byte 1
-                split_story: flag_byte & 0x2 > 0,
+                split_story: flag_byte & 0x4 > 0,
                 // go ahead, skip a bit and fuck with everyones head
-                // bit 4 of byte 1
-                show_status_line: flag_byte & 0x4 > 0,
+                // bit 4 of byte 1, which is set if the status line isn't there
+                show_status_line: flag_byte & 0x10 == 0,
                 // bit 5 of byte 1
-                split_screen: flag_byte & 0x5 > 0,
+                split_screen: flag_byte & 0x20 > 0,
                 // bit 6 of byte 1
-                variable_pitch_font: flag_byte & 0x6 > 0,
+                variable_pitch_font: flag_byte & 0x40 > 0,
             },
         }
     }
@@ -201,7 +452,47 @@ pub struct HeaderFlagsV1 {
     pub variable_pitch_font: bool,
 }
 
+// in 4+ there's no status line to speak of ( the game draws its own ),
+// and flags 1 is all about what the interpreter can display
+pub struct HeaderFlagsV4 {
+    pub colours: bool,
+    pub pictures: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub fixed_space: bool,
+    pub sound: bool,
+    pub timed_input: bool,
+}
+
 pub enum StatusLineType {
     Score,
     Hours,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_files_have_no_checksum() {
+        assert!(Header::checksum(&[3; 63]).is_err());
+        assert!(Header::checksum(&[]).is_err());
+        assert!(Header::validate(&[3; 63]).is_err());
+    }
+
+    #[test]
+    fn checksums_add_up_past_the_header() {
+        let mut data = vec![0; 0x48];
+        data[0] = 3;
+        data[0x1B] = 0x24;
+        data[0x1D] = 0x0F;
+
+        for byte in data[0x40..].iter_mut() {
+            *byte = 2;
+        }
+
+        let checksum = Header::checksum(&data).unwrap();
+        assert_eq!((checksum.expected, checksum.actual), (0x0F, 16));
+        assert!(!checksum.matches());
+    }
+}
@@ -0,0 +1,195 @@
+// a (very) small reader/writer for the "interchange file format", or IFF
+//
+// quetzal save files are IFF files, and so are blorb containers, so both
+// of them get built on top of this. the format itself is dead simple:
+//
+// 4 bytes  "FORM"
+// 4 bytes  big-endian length of everything that follows
+// 4 bytes  the form type ( "IFZS" for quetzal, "IFRS" for blorb )
+// ...      chunks
+//
+// and each chunk is a 4 byte id, a 4 byte big-endian length, the data,
+// and a pad byte if the length was odd
+//
+// http://inform-fiction.org/zmachine/standards/quetzal/index.html
+
+pub struct Chunk<'a> {
+    pub id: [u8; 4],
+    pub data: &'a [u8],
+    // where the chunk ( its id, not its data ) starts in the file - blorb
+    // finds its chunks this way
+    pub offset: usize,
+}
+
+pub struct FormReader<'a> {
+    pub form_type: [u8; 4],
+    pub chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> FormReader<'a> {
+    // reads every chunk out of a FORM, or returns an error if the data
+    // isn't a FORM or is truncated
+    pub fn read(data: &'a [u8]) -> Result<FormReader<'a>, String> {
+        if data.len() < 12 || &data[0..4] != b"FORM" {
+            return Err("not an IFF file".to_string());
+        }
+
+        let form_length = read_u32(data, 4) as usize;
+
+        // some writers get this wrong by a pad byte, so we just clamp it
+        // instead of giving up entirely
+        let end = std::cmp::min(form_length + 8, data.len());
+
+        let mut form_type = [0; 4];
+        form_type.copy_from_slice(&data[8..12]);
+
+        let mut chunks = Vec::new();
+        let mut cursor = 12;
+
+        while cursor + 8 <= end {
+            let mut id = [0; 4];
+            id.copy_from_slice(&data[cursor..cursor + 4]);
+
+            let length = read_u32(data, cursor + 4) as usize;
+            let start = cursor + 8;
+
+            if start + length > end {
+                return Err(format!(
+                    "chunk {} runs past the end of the file",
+                    String::from_utf8_lossy(&id)
+                ));
+            }
+
+            chunks.push(Chunk {
+                id,
+                data: &data[start..start + length],
+                offset: cursor,
+            });
+
+            // chunks are always padded out to an even length
+            cursor = start + length + (length & 1);
+        }
+
+        Ok(FormReader { form_type, chunks })
+    }
+
+    pub fn find(&self, id: &[u8; 4]) -> Option<&Chunk<'a>> {
+        self.chunks.iter().find(|chunk| &chunk.id == id)
+    }
+
+    // the chunk that starts at offset in the file
+    pub fn find_at(&self, offset: usize) -> Option<&Chunk<'a>> {
+        self.chunks.iter().find(|chunk| chunk.offset == offset)
+    }
+}
+
+pub struct FormWriter {
+    form_type: [u8; 4],
+    body: Vec<u8>,
+}
+
+impl FormWriter {
+    pub fn new(form_type: &[u8; 4]) -> FormWriter {
+        FormWriter {
+            form_type: *form_type,
+            body: Vec::new(),
+        }
+    }
+
+    pub fn write_chunk(&mut self, id: &[u8; 4], data: &[u8]) {
+        self.body.extend_from_slice(id);
+        push_u32(&mut self.body, data.len() as u32);
+        self.body.extend_from_slice(data);
+
+        if data.len() & 1 == 1 {
+            self.body.push(0);
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.body.len() + 12);
+
+        out.extend_from_slice(b"FORM");
+        // the length includes the form type, but not "FORM" or itself
+        push_u32(&mut out, (self.body.len() + 4) as u32);
+        out.extend_from_slice(&self.form_type);
+        out.extend_from_slice(&self.body);
+
+        out
+    }
+}
+
+pub fn read_u16(data: &[u8], at: usize) -> u16 {
+    ((data[at] as u16) << 8) | (data[at + 1] as u16)
+}
+
+pub fn read_u24(data: &[u8], at: usize) -> u32 {
+    ((data[at] as u32) << 16) | ((data[at + 1] as u32) << 8) | (data[at + 2] as u32)
+}
+
+pub fn read_u32(data: &[u8], at: usize) -> u32 {
+    ((read_u16(data, at) as u32) << 16) | (read_u16(data, at + 2) as u32)
+}
+
+pub fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+pub fn push_u24(out: &mut Vec<u8>, value: u32) {
+    out.push((value >> 16) as u8);
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+pub fn push_u32(out: &mut Vec<u8>, value: u32) {
+    push_u16(out, (value >> 16) as u16);
+    push_u16(out, value as u16);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_chunks_are_padded() {
+        let mut form = FormWriter::new(b"TEST");
+        form.write_chunk(b"ODD ", &[1, 2, 3]);
+        form.write_chunk(b"EVEN", &[4, 5]);
+        let data = form.finish();
+
+        // 4 for the type, 8 + 3 + 1 pad for the first chunk, 8 + 2 for the second
+        assert_eq!(read_u32(&data, 4), 4 + 12 + 10);
+        assert_eq!(data.len(), 8 + 4 + 12 + 10);
+        assert_eq!(&data[12..24], b"ODD \x00\x00\x00\x03\x01\x02\x03\x00");
+
+        let read = FormReader::read(&data).unwrap();
+        assert_eq!(&read.form_type, b"TEST");
+        assert_eq!(read.chunks.len(), 2);
+        assert_eq!(read.find(b"ODD ").unwrap().data, &[1, 2, 3]);
+        assert_eq!(read.find(b"EVEN").unwrap().data, &[4, 5]);
+        assert_eq!(read.find_at(24).unwrap().id, *b"EVEN");
+    }
+
+    #[test]
+    fn truncated_chunks_are_errors() {
+        let mut form = FormWriter::new(b"TEST");
+        form.write_chunk(b"DATA", &[1, 2, 3, 4]);
+        let data = form.finish();
+
+        assert!(FormReader::read(&data[..data.len() - 1]).is_err());
+        assert!(FormReader::read(b"LIST").is_err());
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        let mut out = Vec::new();
+        push_u16(&mut out, 0xBEEF);
+        push_u24(&mut out, 0x12_3456);
+        push_u32(&mut out, 0xDEAD_BEEF);
+
+        assert_eq!(read_u16(&out, 0), 0xBEEF);
+        assert_eq!(read_u24(&out, 2), 0x12_3456);
+        assert_eq!(read_u32(&out, 5), 0xDEAD_BEEF);
+    }
+}
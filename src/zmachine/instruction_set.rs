@@ -1,11 +1,16 @@
 extern crate rand;
 
-use super::super::interfaces::zinterface::ZInterface;
+use super::super::interfaces::zinterface::{Window, ZInterface};
+use super::error::*;
 use super::header::*;
 use super::memory_view::MemoryView;
 use super::object_properties_view::*;
 use super::object_view::ObjectView;
 use super::opcode::*;
+use super::sound::*;
+use super::streams::*;
+use super::tokenizer;
+use super::zmachine::FrameInfo;
 use super::zmachine::MachineState;
 use super::zmachine::ZMachine;
 use super::zstring::*;
@@ -15,7 +20,6 @@ use std::cmp;
 // for input flushing
 use std::io;
 use std::io::Write;
-use std::str::SplitWhitespace;
 
 use std::rc::*;
 
@@ -40,10 +44,12 @@ use std::rc::*;
 // part of zmachine, but its really still an op
 // code, albiet a really effing powerful one,
 // and that would get awkward abstraction-wise
-pub fn and<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn and<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.store = true;
-    code.result = code.operands[0].get_value() & code.operands[1].get_value();
+    code.result = code.operands[0].get_value()? & code.operands[1].get_value()?;
     // done
+
+    Ok(())
 }
 
 // maaaybe the most self explanatory jam in the book
@@ -51,18 +57,91 @@ pub fn and<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
 //
 // zmachine takes care of the 'storing' part
 
-pub fn add<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn add<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.store = true;
-    code.result =
-        ((code.operands[0].get_value() as i16) + (code.operands[1].get_value() as i16)) as u16;
+    // numbers past 32767 go round to -32768, rather than stopping the game
+    code.result = (code.operands[0].get_value()? as i16)
+        .wrapping_add(code.operands[1].get_value()? as i16) as u16;
     // yay!
+
+    Ok(())
+}
+
+// art_shift is a signed shift - negative places shift right, and keep the sign
+pub fn art_shift<T: ZInterface>(
+    code: &mut OpCode<T>,
+    _: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+
+    let (number, places) = (
+        code.operands[0].get_value()? as i16,
+        code.operands[1].get_value()? as i16,
+    );
+
+    // the spec only allows -15..15, anything past that has shifted
+    // everything out anyway
+    code.result = match places {
+        p if p >= 16 => 0,
+        p if p >= 0 => (number << p) as u16,
+        p if p > -16 => (number >> -p) as u16,
+        _ => (number >> 15) as u16,
+    };
+
+    Ok(())
+}
+
+// turns the buffering of text ( for word wrapping ) in the lower window on or
+// off - the wrapping is the interface's, so it's the one that needs to know
+pub fn buffer_mode<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let buffered = code.operands[0].get_value()? != 0;
+    machine.zinterface.buffer_mode(buffered);
+
+    Ok(())
+}
+
+// version 6 can have the screen drawn off screen and shown all at once -
+// we draw straight away, so the answer is always that we weren't buffering
+pub fn buffer_screen<T: ZInterface>(
+    code: &mut OpCode<T>,
+    _: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+    code.result = 0;
+
+    Ok(())
+}
+
+// there is only one routine we can call ( the calls differ in how many
+// operands they can take, and if they keep the result ), so call, call_vs,
+// call_vs2, call_2s and call_1s all land here
+pub fn call<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    call_routine(code, machine, false)
+}
+
+// call_vn, call_vn2, call_2n and call_1n - the same as call, but the
+// result of the routine is thrown away
+pub fn call_vn<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    call_routine(code, machine, true)
 }
 
-pub fn call<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+fn call_routine<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+    discard_result: bool,
+) -> InstructionResult<()> {
     // move program counter
     // address is actually multiplied by a constant, depending on the version #
-    // we just support 3 here, so it is always 2
-    let address = (code.operands[0].get_value() as u32) * 2;
+    let address = machine.unpack_routine_address(code.operands[0].get_value()?);
 
     // println!("////////////// calling {:x}", address);
 
@@ -70,12 +149,16 @@ pub fn call<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     // i mean the opcode then stores 0 at the value on branch
     // so we fake the end of ret and return
     // we also don't touch read bytes or anything like that at all
+    //
+    // ( unless the result is thrown away, then there's nothing to do at all )
     if address == 0 {
         code.result = 0;
-        code.store = true;
-        return;
+        code.store = !discard_result;
+        return Ok(());
     }
 
+    machine.check_address(address)?;
+
     // push this current address
     // we split it in two, because the stack holds words
     // note there is more than one way to do this  we could
@@ -94,9 +177,45 @@ pub fn call<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     // push the current offset to the stack
     // note its highly unlikely the conversion will be a problem here;
     // offset is not likely to be greater than 100
+    //
+    // when the result is thrown away, there is no store byte, so
+    // this is just where the next instruction starts
     // println!("pushing offset: {}", code.read_bytes);
     machine.call_stack.stack.push(code.read_bytes as u16);
 
+    // its important to note here that the function
+    // makes space for its arguments and those
+    // are also considered "locals". locals left
+    // over are initialized to the values specified in
+    // those bytes
+    //
+    // this has a slightly different deconstruction than the vast majority
+    // of opcodes because its variable ( can have any number of operands, truly ),
+    // and this variable # effects how the call stack is formed
+    //
+    // note here that we are not evaluating the variables, so we push
+    // the value
+    let mut args = Vec::with_capacity(7);
+
+    for op in code.operands[1..].iter() {
+        match op {
+            &Operand::Omitted => break,
+            _ => args.push(op.get_value()?),
+        }
+    }
+
+    let num_locals = machine.get_memory_view().read_at(address)?;
+
+    // and what we know about the frame itself - the routine header
+    // gives us the # of locals, and the operands tell us the args
+    let frame_info = FrameInfo {
+        locals: num_locals,
+        args_supplied: ((1u16 << args.len()) - 1) as u8,
+        discard_result,
+    };
+
+    machine.call_stack.stack.push(frame_info.to_word());
+
     // change the instruction pointer
     machine.ip = address;
 
@@ -109,81 +228,176 @@ pub fn call<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
 
     code.read_bytes = 0;
 
-    // its important to note here that the function
-    // makes space for its arguments and those
-    // are also considered "locals". locals left
-    // over are initialized to the values specified in
-    // those bytes
+    // push local variables onto the call stack - any arguments past
+    // the # of locals have nowhere to go, so they are dropped
+    let num_args = cmp::min(args.len(), num_locals as usize);
 
-    let mut num_locals = machine.get_frame_view().read_at_head(0);
-    let mut num_args = 0;
+    for value in args[..num_args].iter() {
+        machine.call_stack.stack.push(*value);
+    }
 
-    // push local variables onto the call stack
-    //
-    // this has a slightly different deconstruction than the vast majority
-    // of opcodes because its variable ( can have any number of operands, truly ),
-    // and this variable # effects how the call stack is formed
+    // first advance the pointer by the header of the function
+    machine.ip += 1;
 
-    for op in code.operands[1..].iter() {
-        match op {
-            &Operand::Omitted => break,
-            &Operand::SmallConstant { value } => {
-                num_args += 1;
-                machine.call_stack.stack.push(value as u16);
+    match machine.get_version() {
+        // advance the pointer. note that if there are
+        // any default values for operands, these
+        // will automatically be included in the locals
+        // below ( we only read when we know we have no arg )
+        //
+        // also note these are words, so we skip two bytes
+        // for each local
+        1..=4 => {
+            machine.ip += num_args as u32 * 2;
+
+            for _ in num_args..num_locals as usize {
+                let default_value = machine.get_frame_view().read_u16_at_head(0)?;
+                machine.call_stack.stack.push(default_value);
+                // advance the pointer
+                machine.ip += 2;
             }
-            &Operand::LargeConstant { value } |
-            // note here that we are not evaluating the variable, so we push 
-            // the value
-            &Operand::Variable { value, .. } => {
-                num_args += 1;
-                machine.call_stack.stack.push(value);
+        }
+        // from version 5, there are no default values in the routine
+        // header - the locals all start at 0, and the code starts right away
+        _ => {
+            for _ in num_args..num_locals as usize {
+                machine.call_stack.stack.push(0);
             }
-        };
-        num_locals -= 1
+        }
     }
 
-    // first advance the pointer by the header of the function
-    machine.ip += 1;
+    // done!
 
-    // advance the pointer. note that if there are
-    // any default values for operands, these
-    // will automatically be included in the locals
-    // below ( we only read when we know we have no arg )
-    //
-    // also note these are words, so we skip two bytes
-    // for each local
+    Ok(())
+}
 
-    if num_args > 0 {
-        machine.ip = machine.ip + num_args * 2;
-    }
+// catch hands the story something it can give to throw, to get
+// back to this routine from however deep it is
+pub fn catch<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+    code.result = machine.call_stack.frame_pointer();
+
+    Ok(())
+}
+
+// branches if the routine we are in was given at least this many arguments
+pub fn check_arg_count<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.branch = true;
+
+    let argument = code.operands[0].get_value()?;
+    let args_supplied = machine.call_stack.current_frame_info().args_supplied as u16;
+
+    code.result = match argument {
+        n @ 1..=7 => (args_supplied >> (n - 1)) & 1,
+        _ => 0,
+    };
+
+    Ok(())
+}
+
+// bit 0 of the answer is whether the character can be printed, and bit 1 is
+// whether it can be typed - which it can if zscii has a code for it
+pub fn check_unicode<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
 
-    if num_locals > 0 {
-        for _ in 0..num_locals {
-            let default_value = machine.get_frame_view().read_u16_at_head(0);
-            machine.call_stack.stack.push(default_value);
-            // advance the pointer
-            machine.ip += 2;
+    code.result = match std::char::from_u32(code.operands[0].get_value()? as u32) {
+        Some(ch) if !ch.is_control() => {
+            1 | (ZString::encode_zscii(ch, &machine.charset).is_some() as u16) << 1
         }
-    }
+        _ => 0,
+    };
 
-    // done!
+    Ok(())
 }
 
 // this clears a bit in the attributes table
-pub fn clear_attr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let (object, attr) = (code.operands[0].get_value(), code.operands[1].get_value());
+pub fn clear_attr<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (object, attr) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
-    machine.get_object_view(object).unset_attribute(attr);
+    machine.get_object_view(object)?.unset_attribute(attr)?;
     // done
+
+    Ok(())
+}
+
+// copies size bytes from first to second
+//
+// if second is 0, first is zeroed out instead. a positive size has to be copied
+// as if through a temporary buffer ( so overlapping tables don't corrupt ), but
+// a negative size means copy forwards no matter what, which stories use on
+// purpose to fill a table with its first byte
+pub fn copy_table<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (first, second, size) = (
+        code.operands[0].get_value()? as u32,
+        code.operands[1].get_value()? as u32,
+        code.operands[2].get_value()? as i16,
+    );
+
+    let length = size.unsigned_abs() as u32;
+
+    if length == 0 {
+        return Ok(());
+    }
+
+    machine.check_address(first + length - 1)?;
+
+    let view = machine.get_memory_view();
+
+    if second == 0 {
+        for i in 0..length {
+            view.write_at(first + i, 0)?;
+        }
+
+        return Ok(());
+    }
+
+    machine.check_address(second + length - 1)?;
+
+    if size < 0 {
+        for i in 0..length {
+            let byte = view.read_at(first + i)?;
+            view.write_at(second + i, byte)?;
+        }
+    } else {
+        let bytes = (0..length)
+            .map(|i| view.read_at(first + i))
+            .collect::<InstructionResult<Vec<u8>>>()?;
+
+        for (i, byte) in bytes.iter().enumerate() {
+            view.write_at(second + i as u32, *byte)?;
+        }
+    }
+
+    Ok(())
 }
 
-pub fn dec<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let variable = code.operands[0].get_value();
+pub fn dec<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let variable = code.operands[0].get_value()?;
+
+    // like the arithmetic opcodes, -32768 goes round to 32767
+    let current = (machine.read_variable(variable as u8)? as i16).wrapping_sub(1);
 
-    let mut current = machine.read_variable(variable as u8) as i16;
-    current -= 1;
+    machine.write_variable_in_place(variable as u8, current as u16)?;
 
-    machine.write_variable_in_place(variable as u8, current as u16);
+    Ok(())
 }
 
 // dec check decrements a variable and branches if the variable is now
@@ -192,18 +406,20 @@ pub fn dec<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
 // this is not a store function, it actually operates under different
 // rules in which the stack is not popped when read or written to
 
-pub fn dec_chk<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn dec_chk<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.branch = true;
 
     let (variable, value) = (
-        code.operands[0].get_value(),
-        code.operands[1].get_value() as i16,
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as i16,
     );
 
-    let mut current = machine.read_variable(variable as u8) as i16;
-    current -= 1;
+    let current = (machine.read_variable(variable as u8)? as i16).wrapping_sub(1);
 
-    machine.write_variable_in_place(variable as u8, current as u16);
+    machine.write_variable_in_place(variable as u8, current as u16)?;
 
     match current < value {
         false => code.result = 0,
@@ -211,6 +427,8 @@ pub fn dec_chk<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     }
 
     // done
+
+    Ok(())
 }
 
 // although not officially documented, this code exists, and was probably
@@ -219,89 +437,282 @@ pub fn dec_chk<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
 // 2) use it for debugging
 //
 // it's not a "no-op", strictly speaking, but for our purposes it is.
-pub fn debug<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {}
+pub fn debug<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    Ok(())
+}
 
 // signed division, should halt interpreter on divide by zero ( the inform
 // compiler should guarantee that never happens )
-pub fn div<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn div<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.store = true;
 
-    let (dividend, divisor) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (dividend, divisor) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     if divisor == 0 {
-        panic!("division by zero!");
+        return Err(ErrorKind::DivisionByZero);
+    }
+
+    // -32768 / -1 doesn't fit, and goes round like everything else
+    code.result = (dividend as i16).wrapping_div(divisor as i16) as u16;
+
+    Ok(())
+}
+
+// draws a picture in the current window, at y and x inside it ( or at
+// the cursor ) - pictures we don't have are skipped
+pub fn draw_picture<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let number = code.operands[0].get_value()?;
+    let (y, x) = (optional_value(code, 1)?, optional_value(code, 2)?);
+
+    if let Some(command) = machine.graphics.draw_picture(number, y, x) {
+        machine.zinterface.draw(&command);
+    }
+
+    Ok(())
+}
+
+// encodes length zscii characters from zscii_text + from into a dictionary
+// word at coded_text, the same way sread encodes what the player types
+pub fn encode_text<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (zscii_text, length, from, coded_text) = (
+        code.operands[0].get_value()? as u32,
+        code.operands[1].get_value()? as u32,
+        code.operands[2].get_value()? as u32,
+        code.operands[3].get_value()? as u32,
+    );
+
+    let view = machine.get_memory_view();
+
+    let text = (0..length)
+        .map(|i| view.read_at(zscii_text + from + i))
+        .collect::<InstructionResult<Vec<u8>>>()?;
+
+    let encoded =
+        ZString::encode_word(&text, machine.get_version(), &machine.charset).encoded_as_vec();
+
+    machine.check_address(coded_text + encoded.len() as u32 - 1)?;
+
+    for (i, byte) in encoded.iter().enumerate() {
+        view.write_at(coded_text + i as u32, *byte)?;
+    }
+
+    Ok(())
+}
+
+// erasing to the end of the line needs to know where the end of the line is,
+// and we don't know how wide the screen is - except in version 6, where
+// we know where everything is
+pub fn erase_line<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    if machine.get_version() == 6 {
+        let pixels = code.operands[0].get_value()?;
+        let command = machine.graphics.erase_line(pixels);
+
+        machine.zinterface.draw(&command);
+    }
+
+    Ok(())
+}
+
+// fills where the picture would be with the background colour
+pub fn erase_picture<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let number = code.operands[0].get_value()?;
+    let (y, x) = (optional_value(code, 1)?, optional_value(code, 2)?);
+
+    if let Some(command) = machine.graphics.erase_picture(number, y, x) {
+        machine.zinterface.draw(&command);
+    }
+
+    Ok(())
+}
+
+// -1 and -2 clear the whole screen ( -1 also unsplits it ),
+// 0 and 1 clear the lower and upper windows
+pub fn erase_window<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    if machine.get_version() == 6 {
+        let command = match code.operands[0].get_value()? as i16 {
+            -1 => machine.graphics.erase_screen(true),
+            -2 => machine.graphics.erase_screen(false),
+            _ => {
+                let window = graphics_window(code, 0, machine)?;
+                machine.graphics.erase_window(window)
+            }
+        };
+
+        machine.zinterface.draw(&command);
+        return Ok(());
+    }
+
+    match code.operands[0].get_value()? as i16 {
+        -1 => {
+            machine.screen.upper_height = 0;
+            machine.screen.current = Window::Lower;
+            machine.screen.cursor = (1, 1);
+            machine.zinterface.split_window(machine.screen.top, 0);
+            machine.zinterface.clear();
+        }
+        -2 => {
+            machine.screen.cursor = (1, 1);
+            machine.zinterface.clear();
+        }
+        0 => machine.zinterface.erase_window(Window::Lower),
+        1 => {
+            machine.screen.cursor = (1, 1);
+            machine.zinterface.erase_window(Window::Upper);
+        }
+        _ => (),
     }
 
-    code.result = (dividend as i16 / divisor as i16) as u16;
+    Ok(())
 }
 
 // gets the child id of the object
-pub fn get_child<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_child<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
     code.branch = true;
 
-    let object = code.operands[0].get_value();
-    code.result = machine.get_object_view(object).get_child();
+    let object = code.operands[0].get_value()?;
+    code.result = machine.get_object_view(object)?.get_child()?;
     // done
+
+    Ok(())
+}
+
+// writes the cursor's line and column into the array
+//
+// we only know where the cursor is in the upper window - the lower window's
+// cursor belongs to the interface, so as far as we can tell it's at the start
+// of the line under the upper window
+pub fn get_cursor<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let array = code.operands[0].get_value()? as u32;
+
+    machine.check_address(array + 3)?;
+
+    let (line, column) = match machine.screen.current {
+        _ if machine.get_version() == 6 => machine.graphics.current_window().cursor,
+        Window::Upper => machine.screen.cursor,
+        Window::Lower => (machine.screen.upper_height + 1, 1),
+    };
+
+    let view = machine.get_memory_view();
+    view.write_u16_at(array, line)?;
+    view.write_u16_at(array + 2, column)?;
+
+    Ok(())
+}
+
+// reads a property of a version 6 window ( see GraphicsWindow )
+pub fn get_wind_prop<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+
+    let window = graphics_window(code, 0, machine)?;
+    let property = code.operands[1].get_value()?;
+
+    code.result = machine.graphics.windows[window]
+        .get_property(property)
+        .ok_or(ErrorKind::InvalidWindowProperty { property })?;
+
+    Ok(())
 }
 
 // gets the parent id of the object
-pub fn get_parent<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_parent<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    let object = code.operands[0].get_value();
-    code.result = machine.get_object_view(object).get_parent();
+    let object = code.operands[0].get_value()?;
+    code.result = machine.get_object_view(object)?.get_parent()?;
     // done
+
+    Ok(())
 }
 
-pub fn get_prop<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_prop<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    let (object, property) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (object, property) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     let value = machine
-        .get_object_view(object)
-        .get_properties_table_view()
-        .get_property(property as u8)
+        .get_object_view(object)?
+        .get_properties_table_view()?
+        .get_property(property as u8)?
         .value;
 
     // println!("object:{}\nproperty:{}\nvalue:{}", object, property, value);
     // println!("****");
 
     code.result = value;
+
+    Ok(())
 }
 
-pub fn get_prop_len<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_prop_len<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    // we subtract one because the size byte itself is actually 1 below
-    // the address given.
-    //
-    // this is kind of confusing until you realize that the value coming
-    // into this operand is coming out of get_prop_addr
-    //
-    // in order to get the size byte, we have to reduce by one
-
-    let property_address = code.operands[0].get_value() - 1;
-    let size_byte = machine.get_memory_view().read_at(property_address as u32);
-
-    let ObjectPropertyInfo { size, .. } =
-        ObjectPropertiesView::get_object_property_from_size_byte(size_byte);
+    // the value coming into this operand is coming out of get_prop_addr,
+    // so the size byte is just below it - and 0 means there's no property
+    // at all, which has no length
+    let property_address = code.operands[0].get_value()? as u32;
+
+    code.result = match property_address {
+        0 => 0,
+        x => ObjectPropertiesView::get_property_length(
+            &machine.get_memory_view(),
+            x,
+            machine.get_version(),
+        )? as u16,
+    };
 
-    code.result = size as u16;
+    Ok(())
 }
 
-pub fn get_prop_addr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_prop_addr<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    let (object, property) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (object, property) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     //we add one, because we are actually returning the "property address"
     //not the size byte address
     code.result = machine
-        .get_object_view(object)
-        .get_properties_table_view()
-        .get_property_addr(property as u8) as u16;
+        .get_object_view(object)?
+        .get_properties_table_view()?
+        .get_property_addr(property as u8)? as u16;
+
+    Ok(())
 }
 
 // this gets the next property of the property listed,
@@ -311,76 +722,132 @@ pub fn get_prop_addr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine
 // and in either case, returns the id of the next property
 //
 // if the property specified is non-existant, the interpreter should halt.
-pub fn get_next_prop<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_next_prop<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
     let (object, property) = (
-        code.operands[0].get_value(),
-        code.operands[1].get_value() as u8,
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as u8,
     );
 
-    let property_view = machine.get_object_view(object).get_properties_table_view();
-
-    // this was not appropriately being done before
-    let size_byte = {
-        let info = property_view.get_property_info(property);
+    let property_view = machine
+        .get_object_view(object)?
+        .get_properties_table_view()?;
 
-        let addr = match info.addr {
-            Some(x) => x,
-            None => panic!("attempted to find the next property of a non existant property!"),
-        };
+    // 0 asks for the first property, otherwise we find the one after property
+    let next_position = match property {
+        0 => property_view.first_property_offset(),
+        _ => {
+            let info = property_view.get_property_info(property)?;
 
-        let next_addr = addr + info.size as u32;
-        property_view.view.read_at_head(next_addr)
+            match info.addr {
+                Some(x) => x + info.size as u32,
+                // attempted to find the next property of a non existant property!
+                None => return Err(ErrorKind::UnknownProperty { object, property }),
+            }
+        }
     };
 
-    let ObjectPropertyInfo { id, .. } =
-        ObjectPropertiesView::get_object_property_from_size_byte(size_byte);
+    // the end of the list has a size byte of 0, which reads as property 0
+    let (ObjectPropertyInfo { id, .. }, _) = ObjectPropertiesView::read_size_bytes(
+        &property_view.view,
+        property_view.view.pointer + next_position,
+        property_view.version,
+    )?;
 
     code.result = id as u16;
+
+    Ok(())
 }
 
-pub fn get_sibling<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn get_sibling<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
     code.branch = true;
 
-    let object = code.operands[0].get_value();
-    code.result = machine.get_object_view(object).get_sibling();
+    let object = code.operands[0].get_value()?;
+    code.result = machine.get_object_view(object)?.get_sibling()?;
 
     // println!("slibling is {}", code.result);
     // done
+
+    Ok(())
+}
+
+// the version 6 window an operand names, where -3 ( or leaving the
+// operand off ) is the current window
+fn graphics_window<T: ZInterface>(
+    code: &OpCode<T>,
+    index: usize,
+    machine: &ZMachine<T>,
+) -> InstructionResult<usize> {
+    let window = match code.operands[index] {
+        Operand::Omitted => return Ok(machine.graphics.current),
+        ref op => op.get_value()?,
+    };
+
+    machine
+        .graphics
+        .window_index(window)
+        .ok_or(ErrorKind::InvalidWindow { window })
 }
 
-pub fn inc<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let variable = code.operands[0].get_value();
+pub fn inc<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let variable = code.operands[0].get_value()?;
 
-    let mut current = machine.read_variable(variable as u8) as i16;
-    current += 1;
+    // like the arithmetic opcodes, 32767 goes round to -32768
+    let current = (machine.read_variable(variable as u8)? as i16).wrapping_add(1);
 
-    machine.write_variable_in_place(variable as u8, current as u16);
+    machine.write_variable_in_place(variable as u8, current as u16)?;
+
+    Ok(())
 }
 
-pub fn inc_chk<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn inc_chk<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.branch = true;
 
     let (variable, value) = (
-        code.operands[0].get_value(),
-        code.operands[1].get_value() as i16,
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as i16,
     );
 
-    let mut current = machine.read_variable(variable as u8) as i16;
-
-    current += 1;
-    machine.write_variable_in_place(variable as u8, current as u16);
+    let current = (machine.read_variable(variable as u8)? as i16).wrapping_add(1);
+    machine.write_variable_in_place(variable as u8, current as u16)?;
 
     match current > value {
         false => code.result = 0,
         true => code.result = 1,
     }
+
+    Ok(())
 }
 
-pub fn input_stream<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    unimplemented!();
+// 0 is the keyboard, and 1 is a file of commands ( see output_stream 4 ) -
+// the interface does the reading, we just tell it which
+pub fn input_stream<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    match code.operands[0].get_value()? {
+        0 => machine.zinterface.stop_command_playback(),
+        1 => {
+            machine.zinterface.start_command_playback();
+        }
+        _ => (),
+    }
+
+    Ok(())
 }
 
 // this code moves object to the first child of destination -
@@ -395,40 +862,45 @@ pub fn input_stream<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
 // you can do insert_obj 0, 1 to basically remove everything from a
 // bag, and insert_obj 1, 0 to basically remove an object from a bag
 // its more or less up to the author to decide what they want to use
-pub fn insert_obj<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let (child, parent) = (code.operands[0].get_value(), code.operands[1].get_value());
+pub fn insert_obj<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (child, parent) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     // println!( "inserting:{} into:{}", child, parent );
     // child will never be 0, parent might be though
-    let mut child_view = machine.get_object_view(child);
-    let current_parent = child_view.get_parent();
+    let mut child_view = machine.get_object_view(child)?;
+    let current_parent = child_view.get_parent()?;
 
     // we are done if they are the same
-    if child_view.get_parent() == parent {
-        return;
+    if child_view.get_parent()? == parent {
+        return Ok(());
     }
 
     // if the current parent is not 0, we have to deparent
     if current_parent != 0 {
-        unparent_object(&mut child_view, machine);
+        unparent_object(&mut child_view, machine)?;
     }
 
     // in the case of 0, this deparents, but would have been deparented
     // because of above anyway
-    child_view.set_parent(parent);
+    child_view.set_parent(parent)?;
 
     if parent != 0 {
-        let parent_view = machine.get_object_view(parent);
-        let old_child = parent_view.get_child();
+        let parent_view = machine.get_object_view(parent)?;
+        let old_child = parent_view.get_child()?;
 
-        parent_view.set_child(child);
+        parent_view.set_child(child)?;
 
         if child != 0 {
             // we could make this more efficient, but it would get kind of ugly
-            let child_view = machine.get_object_view(child);
-            child_view.set_sibling(old_child);
+            let child_view = machine.get_object_view(child)?;
+            child_view.set_sibling(old_child)?;
         }
     }
+
+    Ok(())
 }
 
 // je is the only jump expression which can take 4 arguments
@@ -436,19 +908,19 @@ pub fn insert_obj<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>
 //
 // some aspects of the design are kind of confusing, im wondering
 // if this was more for the lexer
-pub fn je<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn je<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.branch = true;
 
     // god i love rust, watch this
 
-    let candidate = code.operands[0].get_value();
+    let candidate = code.operands[0].get_value()?;
     let mut condition = 0;
 
     for operand in code.operands[1..].iter() {
         match operand {
             &Operand::Omitted => break,
             _ => {
-                if candidate == operand.get_value() {
+                if candidate == operand.get_value()? {
                     condition = 1;
                     break;
                 }
@@ -460,14 +932,18 @@ pub fn je<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
     // println!( "{}",code );
 
     code.result = condition;
+
+    Ok(())
 }
 
 // this function jumps if greater than
-pub fn jg<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn jg<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     // casting between signed and unsigned values should be OK
     code.branch = true;
     code.result =
-        ((code.operands[0].get_value() as i16) > (code.operands[1].get_value() as i16)) as u16;
+        ((code.operands[0].get_value()? as i16) > (code.operands[1].get_value()? as i16)) as u16;
+
+    Ok(())
 }
 
 // this function jumps if the object is a child of the other object,
@@ -479,32 +955,42 @@ pub fn jg<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
 // since that question would make no sense, unless the answer was also
 // "nothing", but that would just be like a jump( since its an unconditional
 // branch
-pub fn jin<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn jin<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.branch = true;
 
-    let (child, parent) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (child, parent) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     //println!( "checking for:{} in:{}", child, parent );
 
-    let child = machine.get_object_view(child);
-    code.result = (child.get_parent() == parent) as u16;
+    let child = machine.get_object_view(child)?;
+    code.result = (child.get_parent()? == parent) as u16;
     // println!("result is:{}", code.result );
 
     // done!
+
+    Ok(())
 }
 
 // this function jumps if less than
-pub fn jl<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn jl<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.branch = true;
     code.result =
-        ((code.operands[0].get_value() as i16) < (code.operands[1].get_value() as i16)) as u16;
+        ((code.operands[0].get_value()? as i16) < (code.operands[1].get_value()? as i16)) as u16;
+
+    Ok(())
 }
 
 // another one of the few instructions that just modifies ip,
 // this time it does absolutely nothing to the call stack
-pub fn jump<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn jump<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     // we have to be careful about this cast or we will lose the negative value
-    let offset = code.operands[0].get_value() as i16;
+    let offset = code.operands[0].get_value()? as i16;
     let offset = offset as i32;
 
     // so because we know that machine.ip will always be lower than
@@ -523,12 +1009,16 @@ pub fn jump<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     // reset read bytes so machine does not advance the code
     code.read_bytes = 0;
     // done
+
+    Ok(())
 }
 
-pub fn jz<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn jz<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.branch = true;
-    code.result = (code.operands[0].get_value() == 0) as u16;
+    code.result = (code.operands[0].get_value()? == 0) as u16;
     // println!( "result of jz:{}", code.result );
+
+    Ok(())
 }
 
 // this was not the easiest one
@@ -543,174 +1033,569 @@ pub fn jz<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
 // so instead of just returning the value, we always read the
 // variable and return it
 
-pub fn load<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn load<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
-    let variable = code.operands[0].get_value();
-    code.result = machine.read_variable(variable as u8);
+    let variable = code.operands[0].get_value()?;
+    code.result = machine.read_variable(variable as u8)?;
+
+    Ok(())
 }
 
 // this actually operates on the entirety of static + dynamic memory, and
 // can be used to load things outside the global variables table or stack
 // so again, we actually use the entire memory view here (just like storew)
 
-pub fn loadw<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn loadw<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    let (start, index) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (start, index) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     //this address has to by in dynamic memory,
     //which means it has to be in the lower 64k
-    let address = (start) + (index * 2);
+    let address = start.wrapping_add(index.wrapping_mul(2)) as u32;
+
+    code.result = machine.get_memory_view().read_u16_at(address)?;
 
-    code.result = machine.get_memory_view().read_u16_at(address as u32);
+    Ok(())
 }
 
 // this operates on the entirety of static + dynamic memory
 
-pub fn loadb<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn loadb<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    let (array, byte_index) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (array, byte_index) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
-    let address = (array) + (byte_index);
+    let address = array.wrapping_add(byte_index) as u32;
 
-    code.result = machine.get_memory_view().read_at(address as u32) as u16;
+    code.result = machine.get_memory_view().read_at(address)? as u16;
     // done
-}
 
-// signed multiplication
-pub fn mul<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    code.store = true;
-    code.result =
-        ((code.operands[0].get_value() as i16) * (code.operands[1].get_value() as i16)) as u16;
-    // done
+    Ok(())
 }
 
-// signed modulo
-pub fn mod_fn<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+// log_shift is an unsigned shift - negative places shift right, filling with 0s
+pub fn log_shift<T: ZInterface>(
+    code: &mut OpCode<T>,
+    _: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
-    code.result =
-        ((code.operands[0].get_value() as i16) % (code.operands[1].get_value() as i16)) as u16;
-    // done
-}
 
-pub fn new_line<T: ZInterface>(_: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    machine.zinterface.as_ref().print_to_main("\n");
-}
+    let (number, places) = (
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as i16,
+    );
 
-//uh... do nothing!
-pub fn nop<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {}
+    code.result = match places {
+        p if p >= 16 || p <= -16 => 0,
+        p if p >= 0 => number << p,
+        p => number >> -p,
+    };
 
-pub fn or<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    code.store = true;
-    code.result = code.operands[0].get_value() | code.operands[1].get_value();
-    // done
+    Ok(())
 }
 
-pub fn output_stream<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    unimplemented!();
-}
+// version 6 menus are only for the macintosh's menu bar, which we don't
+// have - the story is told the menu couldn't be made
+pub fn make_menu<T: ZInterface>(
+    code: &mut OpCode<T>,
+    _: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.branch = true;
+    code.result = 0;
 
-pub fn quit<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    machine.zinterface.print_to_main("Quitting.");
-    machine.state = MachineState::Stopped;
-    code.read_bytes = 0;
+    Ok(())
 }
 
-pub fn pop<T: ZInterface>(_: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    machine.call_stack.stack.pop();
-    //thats it
-}
+// version 6 mice only count clicks in one window ( -1 is anywhere )
+pub fn mouse_window<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    machine.graphics.mouse_window = code.operands[0].get_value()?;
 
-pub fn print<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let view = machine.get_frame_view();
-    let abbreviations_view = machine.get_abbreviations_view();
+    Ok(())
+}
 
-    let string = ZString::create(code.read_bytes, &view, &abbreviations_view);
+// moves a version 6 window to y, x on the screen
+pub fn move_window<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let window = graphics_window(code, 0, machine)?;
+    let (y, x) = (code.operands[1].get_value()?, code.operands[2].get_value()?);
 
-    code.read_bytes += string.encoded_length;
+    machine.graphics.move_window(window, y, x);
 
-    // all print functions use print, instead of println
-    machine.zinterface.print_to_main(&format!("{}", string));
+    Ok(())
 }
 
-pub fn print_addr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let addr = code.operands[0].get_value() as u32;
-    // let packed_addr = (addr as u32) * 2;
+// signed multiplication
+pub fn mul<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    code.store = true;
+    code.result = (code.operands[0].get_value()? as i16)
+        .wrapping_mul(code.operands[1].get_value()? as i16) as u16;
+    // done
 
-    let view = machine.get_memory_view();
-    let abbreviations_view = machine.get_abbreviations_view();
+    Ok(())
+}
 
-    let string = ZString::create(addr, &view, &abbreviations_view);
+// signed modulo
+pub fn mod_fn<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    code.store = true;
 
-    machine.zinterface.print_to_main(&format!("{}", string));
-}
+    let (dividend, divisor) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
-pub fn print_char<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    // let ch = (code.operands[0].get_value());
-    // let mut ch_str = String::with_capacity(1);
-    //
-    // this is similar to print_obj in that we do not println!
-    match ZString::decode_zscii(code.operands[0].get_value()) {
-        Some(x) => machine.zinterface.print_to_main(&char::to_string(&x)),
-        None => {}
+    if divisor == 0 {
+        return Err(ErrorKind::DivisionByZero);
     }
-}
 
-pub fn print_obj<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let object = code.operands[0].get_value();
+    code.result = (dividend as i16).wrapping_rem(divisor as i16) as u16;
 
-    let view = machine.get_object_view(object).get_properties_table_view();
+    Ok(())
+}
 
-    // the string is offset by one because properties starts with the size byte,
-    // then is followed by the short name of the object
-    let string = ZString::create(1, &view.view, &machine.get_abbreviations_view());
+pub fn new_line<T: ZInterface>(
+    _: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    machine.print("\n");
 
-    // we actually print instead of println here because
-    // objects can handle carriage returns themselves ( a ZString has a newline
-    // character )
+    Ok(())
+}
 
-    machine.zinterface.print_to_main(&format!("{}", string));
+//uh... do nothing!
+pub fn nop<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    Ok(())
 }
 
-pub fn print_paddr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let packed_addr = code.operands[0].get_value();
+// bitwise not - 1OP in versions 1-4, VAR from 5 on
+pub fn not<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    code.store = true;
+    code.result = !code.operands[0].get_value()?;
+
+    Ok(())
+}
+
+// an operand the instruction can go without, which is 0 if it does
+fn optional_value<T: ZInterface>(code: &OpCode<T>, index: usize) -> InstructionResult<u16> {
+    match code.operands[index] {
+        Operand::Omitted => Ok(0),
+        ref op => op.get_value(),
+    }
+}
+
+pub fn or<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    code.store = true;
+    code.result = code.operands[0].get_value()? | code.operands[1].get_value()?;
+    // done
+
+    Ok(())
+}
+
+// turns an output stream on ( a positive number ) or off ( a negative one )
+//
+// 1 is the screen and 2 is the transcript - the transcript is really the
+// header bit, so we set that and let the machine catch up with it
+//
+// 3 redirects into the table given, and closing it writes down how
+// much went in
+//
+// 4 is a record of the player's commands, which the interface keeps
+pub fn output_stream<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    match code.operands[0].get_value()? as i16 {
+        0 => (),
+        1 => machine.streams.screen = true,
+        -1 => machine.streams.screen = false,
+        2 => {
+            machine.header.set_transcripting(true);
+            machine.update_transcript();
+        }
+        -2 => {
+            machine.header.set_transcripting(false);
+            machine.update_transcript();
+        }
+        3 => {
+            if machine.streams.memory.len() == MAX_MEMORY_STREAMS {
+                return Err(ErrorKind::StreamOverflow);
+            }
+
+            let table = code.operands[1].get_value()? as u32;
+            machine.check_address(table + 1)?;
+
+            machine
+                .streams
+                .memory
+                .push(MemoryStream { table, length: 0 });
+        }
+        -3 => {
+            if let Some(stream) = machine.streams.memory.pop() {
+                machine
+                    .get_memory_view()
+                    .write_u16_at(stream.table, stream.length)?;
+            }
+        }
+        4 => {
+            machine.zinterface.start_command_record();
+        }
+        -4 => machine.zinterface.stop_command_record(),
+        _ => (),
+    }
+
+    Ok(())
+}
+
+pub fn quit<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    machine.zinterface.print_to_main("Quitting.");
+    machine.state = MachineState::Stopped;
+    code.read_bytes = 0;
+
+    Ok(())
+}
+
+// writes the height and width of a picture into the array, branching if we
+// have it - picture 0 asks how many pictures there are, and their release
+pub fn picture_data<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.branch = true;
+
+    let (number, array) = (
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as u32,
+    );
+
+    machine.check_address(array + 3)?;
+
+    let view = machine.get_memory_view();
+
+    let found = match number {
+        0 => {
+            view.write_u16_at(array, machine.graphics.pictures.len() as u16)?;
+            view.write_u16_at(array + 2, machine.graphics.pictures_release)?;
+            !machine.graphics.pictures.is_empty()
+        }
+        x => match machine.graphics.pictures.get(&x) {
+            Some(&(width, height)) => {
+                view.write_u16_at(array, height)?;
+                view.write_u16_at(array + 2, width)?;
+                true
+            }
+            None => false,
+        },
+    };
+
+    code.result = found as u16;
+
+    Ok(())
+}
+
+// tells the interface which pictures are about to be drawn, so it can load
+// them ahead of time - we have nothing to load
+pub fn picture_table<T: ZInterface>(
+    _: &mut OpCode<T>,
+    _: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    Ok(())
+}
+
+// we are always a genuine copy
+pub fn piracy<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+    code.branch = true;
+    code.result = 1;
+
+    Ok(())
+}
+
+pub fn pop<T: ZInterface>(_: &mut OpCode<T>, machine: &mut ZMachine<T>) -> InstructionResult<()> {
+    machine.call_stack.pop()?;
+    //thats it
+
+    Ok(())
+}
+
+// takes items off the top of the stack, or off a user stack ( see push_stack )
+pub fn pop_stack<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let items = code.operands[0].get_value()?;
+
+    match code.operands[1] {
+        Operand::Omitted => {
+            for _ in 0..items {
+                machine.call_stack.pop()?;
+            }
+        }
+        ref op => {
+            let stack = op.get_value()? as u32;
+            machine.check_address(stack + 1)?;
+
+            let view = machine.get_memory_view();
+            let free = view.read_u16_at(stack)?;
+            view.write_u16_at(stack, free.wrapping_add(items))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn print<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let view = machine.get_frame_view();
+    let abbreviations_view = machine.get_abbreviations_view();
+
+    let string = ZString::create(
+        code.read_bytes,
+        &view,
+        &abbreviations_view,
+        &machine.charset,
+    )?;
+
+    code.read_bytes += string.encoded_length;
+
+    // all print functions use print, instead of println
+    machine.print(&format!("{}", string));
+
+    Ok(())
+}
+
+pub fn print_addr<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let addr = code.operands[0].get_value()? as u32;
+    // let packed_addr = (addr as u32) * 2;
+    machine.check_address(addr)?;
+
+    let view = machine.get_memory_view();
+    let abbreviations_view = machine.get_abbreviations_view();
+
+    let string = ZString::create(addr, &view, &abbreviations_view, &machine.charset)?;
+
+    machine.print(&format!("{}", string));
 
-    // another thing that we will have to change in the future,
-    // packed addresses vary based on version..
+    Ok(())
+}
+
+pub fn print_char<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    // let ch = (code.operands[0].get_value()?);
+    // let mut ch_str = String::with_capacity(1);
     //
-    // also gotta be careful with these casts - they are packed for
-    // a reason ( they are 16bit representations of 32 bit word locations )
+    // this is similar to print_obj in that we do not println!
+    match ZString::decode_zscii(code.operands[0].get_value()?, &machine.charset) {
+        Some(x) => machine.print(&char::to_string(&x)),
+        None => {}
+    }
+
+    Ok(())
+}
+
+pub fn print_obj<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let object = code.operands[0].get_value()?;
+
+    let view = machine
+        .get_object_view(object)?
+        .get_properties_table_view()?;
+
+    // the string is offset by one because properties starts with the size byte,
+    // then is followed by the short name of the object
+    let string = ZString::create(
+        1,
+        &view.view,
+        &machine.get_abbreviations_view(),
+        &machine.charset,
+    )?;
 
-    let full_addr = (packed_addr as u32) * 2;
+    // we actually print instead of println here because
+    // objects can handle carriage returns themselves ( a ZString has a newline
+    // character )
+
+    machine.print(&format!("{}", string));
+
+    Ok(())
+}
+
+pub fn print_paddr<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let packed_addr = code.operands[0].get_value()?;
+
+    // gotta be careful with these casts - they are packed for
+    // a reason ( they are 16bit representations of 32 bit word locations )
+    let full_addr = machine.unpack_string_address(packed_addr);
+    machine.check_address(full_addr)?;
 
     let view = machine.get_memory_view();
     let abbreviations_view = machine.get_abbreviations_view();
-    let string = ZString::create(full_addr, &view, &abbreviations_view);
+    let string = ZString::create(full_addr, &view, &abbreviations_view, &machine.charset)?;
+
+    machine.print(&format!("{}", string));
+
+    Ok(())
+}
+
+// prints a formatted table - a run of lines, each a word with how long it
+// is followed by that many characters, ending with a line of length 0
+pub fn print_form<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let mut address = code.operands[0].get_value()? as u32;
+    let view = machine.get_memory_view();
+    let mut out = String::new();
 
-    machine.zinterface.print_to_main(&format!("{}", string));
+    loop {
+        machine.check_address(address + 1)?;
+
+        let length = view.read_u16_at(address)? as u32;
+
+        if length == 0 {
+            break;
+        }
+
+        machine.check_address(address + 1 + length)?;
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        for i in 0..length {
+            let ch = view.read_at(address + 2 + i)? as u16;
+
+            if let Some(x) = ZString::decode_zscii(ch, &machine.charset) {
+                out.push(x);
+            }
+        }
+
+        address += 2 + length;
+    }
+
+    machine.print(&out);
+
+    Ok(())
 }
 
-pub fn print_num<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let num = code.operands[0].get_value();
-    machine.zinterface.print_to_main(&format!("{}", num as i16));
+pub fn print_num<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let num = code.operands[0].get_value()?;
+    machine.print(&format!("{}", num as i16));
+
+    Ok(())
 }
 
 // there are a lot of "macro commands" in the z-instruction set,
 // probably to save on common tasks, such as this one,
 // frequently used when you succeed in doing something
 // print success message, new line, and return true)
-pub fn print_ret<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    print(code, machine);
-    new_line(code, machine);
-    rtrue(code, machine);
+pub fn print_ret<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    print(code, machine)?;
+    new_line(code, machine)?;
+    rtrue(code, machine)
 }
 
-pub fn put_prop<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+// prints a rectangle of zscii text, width characters wide and height rows high,
+// skipping skip characters at the end of each row
+//
+// without a cursor to move around, each row just goes on its own line
+pub fn print_table<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (text, width) = (
+        code.operands[0].get_value()? as u32,
+        code.operands[1].get_value()? as u32,
+    );
+
+    let height = match code.operands[2] {
+        Operand::Omitted => 1,
+        ref op => op.get_value()? as u32,
+    };
+
+    let skip = match code.operands[3] {
+        Operand::Omitted => 0,
+        ref op => op.get_value()? as u32,
+    };
+
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
+    machine.check_address(text + (height - 1) * (width + skip) + width - 1)?;
+
+    let view = machine.get_memory_view();
+    let mut out = String::new();
+
+    for row in 0..height {
+        if row > 0 {
+            out.push('\n');
+        }
+
+        for column in 0..width {
+            let ch = view.read_at(text + row * (width + skip) + column)? as u16;
+
+            if let Some(x) = ZString::decode_zscii(ch, &machine.charset) {
+                out.push(x);
+            }
+        }
+    }
+
+    machine.print(&out);
+
+    Ok(())
+}
+
+// prints a unicode character directly, anything that isn't one
+// comes out as a question mark
+pub fn print_unicode<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let ch = std::char::from_u32(code.operands[0].get_value()? as u32).unwrap_or('?');
+
+    machine.print(&ch.to_string());
+
+    Ok(())
+}
+
+pub fn put_prop<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     let (object, property, value) = (
-        code.operands[0].get_value(),
-        code.operands[1].get_value(),
-        code.operands[2].get_value(),
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()?,
+        code.operands[2].get_value()?,
     );
 
     // println!("************************************************");
@@ -719,38 +1604,122 @@ pub fn put_prop<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>)
     // println!("value: {}", value);
     // println!("****");
     //
-    machine.get_object_view(object).
-        get_properties_table_view().
+    machine.get_object_view(object)?.
+        get_properties_table_view()?.
         //its virtually assured property is always a byte value
         //otherwise, its an inform compiler bug
-        write_property(property as u8, value);
+        write_property(property as u8, value)?;
+
+    Ok(())
 }
 
 // weirdly enough, this is not a store call
 // i have no idea if its legal to pull and push
 // back onto the stack, but i don't see why not
-pub fn pull<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let destination = code.operands[0].get_value();
-    let value = machine.call_stack.stack.pop();
+//
+// ( in version 6 it is a store call, and can pull from a user stack -
+// see push_stack )
+pub fn pull<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    if machine.get_version() == 6 {
+        code.store = true;
 
-    let value = match value {
-        Some(x) => x,
-        None => panic!("stack underflow!"),
-    };
+        code.result = match code.operands[0] {
+            Operand::Omitted => machine.call_stack.pop()?,
+            ref op => {
+                let stack = op.get_value()? as u32;
+                machine.check_address(stack + 1)?;
+
+                let view = machine.get_memory_view();
+                let free = view.read_u16_at(stack)?.wrapping_add(1);
+                let slot = stack + free as u32 * 2;
+
+                machine.check_address(slot + 1)?;
+                view.write_u16_at(stack, free)?;
+                view.read_u16_at(slot)?
+            }
+        };
+
+        return Ok(());
+    }
+
+    let destination = code.operands[0].get_value()?;
+    let value = machine.call_stack.pop()?;
 
-    machine.store_variable(destination as u8, value);
+    machine.store_variable(destination as u8, value)?;
     // done
+
+    Ok(())
 }
 
-pub fn push<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let value = code.operands[0].get_value();
+pub fn push<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let value = code.operands[0].get_value()?;
     machine.call_stack.stack.push(value);
+
+    Ok(())
+}
+
+// version 6 stories can keep stacks of their own in memory - the first word
+// is how many slots are free, and the stack grows down from the end of the
+// table towards it. branches if there was room
+pub fn push_stack<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.branch = true;
+
+    let (value, stack) = (
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as u32,
+    );
+
+    machine.check_address(stack + 1)?;
+
+    let view = machine.get_memory_view();
+    let free = view.read_u16_at(stack)?;
+
+    code.result = match free {
+        0 => 0,
+        _ => {
+            let slot = stack + free as u32 * 2;
+            machine.check_address(slot + 1)?;
+
+            view.write_u16_at(slot, value)?;
+            view.write_u16_at(stack, free - 1)?;
+            1
+        }
+    };
+
+    Ok(())
 }
 
-pub fn random<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+// sets a property of a version 6 window ( see GraphicsWindow )
+pub fn put_wind_prop<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let window = graphics_window(code, 0, machine)?;
+    let (property, value) = (code.operands[1].get_value()?, code.operands[2].get_value()?);
+
+    if !machine.graphics.windows[window].set_property(property, value) {
+        return Err(ErrorKind::InvalidWindowProperty { property });
+    }
+
+    Ok(())
+}
+
+pub fn random<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.store = true;
 
-    let (range, seed) = match code.operands[0].get_value() as i16 {
+    let (range, seed) = match code.operands[0].get_value()? as i16 {
         x if x <= 0 => (None, Some(-1 * x)),
         x @ _ => (Some(x), None),
     };
@@ -766,53 +1735,188 @@ pub fn random<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
         //generator will handle it
         machine.random_generator.seed(seed_value as u16);
         code.result = 0;
-        return;
+        return Ok(());
     };
 
     if let Some(range_value) = range {
         //If range is positive, returns a uniformly random number between 1 and range.
         let random = machine.random_generator.next(range_value as u16);
         code.result = random;
-        return;
+        return Ok(());
     };
+
+    Ok(())
 }
 
-pub fn remove_obj<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let obj = code.operands[0].get_value();
-    let mut view = machine.get_object_view(obj);
-    unparent_object(&mut view, machine);
+// reads a single character
+//
+// our interfaces only give us whole lines, so this waits for a line like sread,
+// and takes the first character of it ( an empty line is just enter )
+//
+// the time and routine operands ask for a timed interrupt, which we don't do
+pub fn read_char<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    // see sread
+    let _ = io::stdout().flush();
+
+    // like aread, we store a placeholder until we have the character
+    code.store = true;
+    code.result = 0;
+
+    let store = Some(machine.get_frame_view().read_at_head(code.read_bytes)?);
+
+    let charset = machine.charset.clone();
+
+    let process_input = Rc::new(move |input: String| match input.chars().next() {
+        None => 13,
+        Some(ch) if ch.is_control() => '?' as u16,
+        // anything zscii doesn't have comes through as a question mark
+        Some(ch) => ZString::encode_zscii(ch, &charset).unwrap_or('?' as u16),
+    });
+
+    machine.state = MachineState::TakingInput {
+        callback: process_input,
+        store,
+        line: false,
+        max_length: 1,
+    };
+
+    Ok(())
 }
 
-pub fn restore<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    //. we are pretending it failed.
-    code.branch = true;
+// writes where the mouse was last clicked into the array, as y, x, the
+// buttons that were down and the menu picked ( we have no menus )
+pub fn read_mouse<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let array = code.operands[0].get_value()? as u32;
+
+    machine.check_address(array + 7)?;
+
+    let (y, x, buttons) = machine.zinterface.read_mouse();
+    let view = machine.get_memory_view();
+
+    view.write_u16_at(array, y)?;
+    view.write_u16_at(array + 2, x)?;
+    view.write_u16_at(array + 4, buttons)?;
+    view.write_u16_at(array + 6, 0)?;
+
+    Ok(())
+}
+
+pub fn remove_obj<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let obj = code.operands[0].get_value()?;
+    let mut view = machine.get_object_view(obj)?;
+    unparent_object(&mut view, machine)?;
+
+    Ok(())
+}
+
+// restoring puts the machine back at the save instruction that made the file,
+// and the game carries on from the save's branch as if it had just succeeded
+// like save, restore branches in versions 1-3 and stores from 4 on
+pub fn restore<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    match machine.get_version() {
+        1..=3 => code.branch = true,
+        _ => code.store = true,
+    }
+
     code.result = 0;
 
-    let string = "Restore not supported. Nice try. ";
+    // the extended form can be given a table to restore a part of memory
+    // from its own file - we only do whole games
+    if let OpForm::Extended = code.form {
+        if code.operand_count > 0 {
+            return Ok(());
+        }
+    }
+
+    let data = match machine.zinterface.restore() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    // the saved pc points at the branch data ( or store byte ) of save,
+    // and both are read from ip + read_bytes, so we back up by our own
+    // instruction length
+    match machine.restore_save(&data, code.read_bytes) {
+        Ok(ip) => {
+            machine.ip = ip;
+
+            // the save itself "returns" 2 in versions that store
+            code.result = match machine.get_version() {
+                1..=3 => 1,
+                _ => 2,
+            };
+        }
+        Err(e) => {
+            machine
+                .zinterface
+                .print_to_main(&format!("Restore failed: {}.\n", e));
+        }
+    }
 
-    machine.zinterface.print_to_main(&format!("{}", string));
+    Ok(())
 }
 
-pub fn restart<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    machine.state = MachineState::Restarting;
+// like restore, a successful restore_undo ends up "returning" 2 from save_undo
+pub fn restore_undo<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+
+    // see restore
+    code.result = match machine.restore_undo(code.read_bytes) {
+        Some(ip) => {
+            machine.ip = ip;
+            2
+        }
+        None => 0,
+    };
+
+    Ok(())
+}
+
+// the machine moves the program counter back to the start
+// itself, so there's nothing to read past
+pub fn restart<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    machine.restart();
     code.read_bytes = 0;
+
+    Ok(())
 }
 
 // ret
-pub fn ret<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn ret<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     // return takes one operand, which is the address to return
-    let value = code.operands[0].get_value();
+    let value = code.operands[0].get_value()?;
     code.result = value;
 
     // drain the stack and restore the last call frame
     // this will leave our information at the top of the stack
-    machine.call_stack.restore_last_frame();
+    machine.call_stack.restore_last_frame()?;
+
+    // the frame info tells us if anyone wants the value we return
+    let info = FrameInfo::from_word(machine.call_stack.pop()?);
 
     // retrieve the offset
-    let offset = match machine.call_stack.stack.pop() {
-        Some(x) => x as u32,
-        None => panic!("stack underflow when restoring stack offset!"),
-    };
+    let offset = machine.call_stack.pop()? as u32;
 
     // so... return is not technically a store, but we are faking the end
     // of the "call" here
@@ -826,16 +1930,13 @@ pub fn ret<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     // we add the offset to complete the "fake"
 
     code.read_bytes = offset;
-    code.store = true;
+    code.store = !info.discard_result;
 
     // retrieve the lower and top parts of the address
-    let address_uhalf = machine.call_stack.stack.pop();
-    let address_lhalf = machine.call_stack.stack.pop();
+    let address_uhalf = machine.call_stack.pop()?;
+    let address_lhalf = machine.call_stack.pop()?;
 
-    let address = match (address_uhalf, address_lhalf) {
-        (Some(uhalf), Some(lhalf)) => ((((uhalf as u32) << 16) & 0xFFFF0000) | (lhalf as u32)),
-        _ => panic!("return call resulted in stack underflow!"),
-    };
+    let address = (((address_uhalf as u32) << 16) & 0xFFFF0000) | (address_lhalf as u32);
 
     // we don't do *2 on this version since we
     // stored the address in-system ( not as part of asm )
@@ -844,6 +1945,8 @@ pub fn ret<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     // println!("returning to:{:x} in ret ********", address + offset);
 
     // we are done, machine handles store calls
+
+    Ok(())
 }
 
 // pop the stack and return that value,
@@ -851,54 +1954,389 @@ pub fn ret<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
 // the next three fucntions are all 0-op,
 // so we don't have to worry too much about modifying operands
 // to use ret ( 1-OP )
-pub fn ret_popped<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let value = machine.read_variable(0);
+pub fn ret_popped<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let value = machine.read_variable(0)?;
     code.operands[0] = Operand::LargeConstant { value: value };
-    ret(code, machine);
+    ret(code, machine)
 }
 
 // return the value false
-pub fn rfalse<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn rfalse<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     // similar to rtrue
     code.operands[0] = Operand::SmallConstant { value: 0 };
-    ret(code, machine);
+    ret(code, machine)
 }
 
 // return the value true
-pub fn rtrue<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn rtrue<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     // so here we do some fudging, because we want to re-use ret
     // the alternative is to create an abstraction that "handled" returning, but
     // that seems like almost everything that ret does
     code.operands[0] = Operand::SmallConstant { value: 1 };
-    ret(code, machine);
+    ret(code, machine)
 }
 
-pub fn save<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    //. we are pretending it succeeded.
+// save branches on success in versions 1-3, and stores 0/1 from 4 on
+pub fn save<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    match machine.get_version() {
+        1..=3 => code.branch = true,
+        _ => code.store = true,
+    }
+
+    code.result = 0;
+
+    // the extended form can be given a table to save a part of memory
+    // to its own file - we only do whole games
+    if let OpForm::Extended = code.form {
+        if code.operand_count > 0 {
+            return Ok(());
+        }
+    }
+
+    // quetzal wants the address of the branch data ( or the store byte ),
+    // which is right after the operands
+    let data = machine.create_save(machine.ip + code.read_bytes);
+
+    code.result = machine.zinterface.save(&data) as u16;
+
+    Ok(())
+}
+
+// keeps an undo state in memory - -1 tells the story undo is turned off
+pub fn save_undo<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+
+    // like save, we come back to the store byte
+    let pc = machine.ip + code.read_bytes;
+
+    code.result = match machine.save_undo(pc) {
+        true => 1,
+        false => 0xFFFF,
+    };
+
+    Ok(())
+}
+
+// scrolls a version 6 window up by pixels ( or down, if it's negative )
+// without moving the cursor
+pub fn scroll_window<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let window = graphics_window(code, 0, machine)?;
+    let pixels = code.operands[1].get_value()? as i16;
+
+    let command = machine.graphics.scroll(window, pixels);
+    machine.zinterface.draw(&command);
+
+    Ok(())
+}
+
+// finds x in a table of len fields, storing the address of the field
+// ( and branching ) if it's there
+//
+// form tells us how big each field is ( bottom 7 bits ), and if we are
+// comparing words ( top bit ) or bytes - it defaults to a table of words
+pub fn scan_table<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
     code.branch = true;
-    code.result = 1;
+    code.result = 0;
+
+    let (x, table, len) = (
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()? as u32,
+        code.operands[2].get_value()? as u32,
+    );
+
+    let form = match code.operands[3] {
+        Operand::Omitted => 0x82,
+        ref op => op.get_value()?,
+    };
+
+    let field_length = (form & 0x7f) as u32;
+    let words = form & 0x80 != 0;
+
+    let view = machine.get_memory_view();
+
+    for i in 0..len {
+        let address = table + i * field_length;
+        machine.check_address(address + words as u32)?;
+
+        let value = match words {
+            true => view.read_u16_at(address)?,
+            false => view.read_at(address)? as u16,
+        };
+
+        if value == x {
+            code.result = address as u16;
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// colours need the window model to mean anything, so they are ignored
+// for now - except in version 6, where each window has its own
+pub fn set_colour<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    if machine.get_version() == 6 {
+        let (foreground, background) =
+            (code.operands[0].get_value()?, code.operands[1].get_value()?);
+        let window = graphics_window(code, 2, machine)?;
+
+        machine.graphics.set_colours(window, foreground, background);
+    }
+
+    Ok(())
+}
+
+// moves the cursor in the upper window - the lower window's cursor can't be moved
+//
+// in version 6 any window's cursor can be moved ( a line of -1 hides the
+// cursor and -2 shows it again, which doesn't matter to us )
+pub fn set_cursor<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (line, column) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
+
+    if machine.get_version() == 6 {
+        let window = graphics_window(code, 2, machine)?;
+
+        if (line as i16) > 0 {
+            machine.graphics.windows[window].cursor = (line, cmp::max(column, 1));
+        }
+
+        return Ok(());
+    }
 
-    let string = "Save not supported. Maybe next time. ";
+    if machine.screen.current == Window::Upper && line > 0 && column > 0 {
+        machine.screen.cursor = (line, column);
+    }
 
-    machine.zinterface.print_to_main(&format!("{}", string));
+    Ok(())
+}
+
+// font 1 is the normal font, and 4 is fixed pitch - in a terminal they are
+// the same thing, so we can give either. 0 just asks what the current one is
+//
+// the result is the previous font, or 0 if we can't do the one asked for
+pub fn set_font<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.store = true;
+
+    // version 6 windows each have their own font
+    if machine.get_version() == 6 {
+        let window = graphics_window(code, 1, machine)?;
+        let current = &mut machine.graphics.windows[window].font;
+
+        code.result = match code.operands[0].get_value()? {
+            0 => *current,
+            font @ 1 | font @ 4 => {
+                let previous = *current;
+                *current = font;
+                previous
+            }
+            _ => 0,
+        };
+
+        return Ok(());
+    }
+
+    code.result = match code.operands[0].get_value()? {
+        0 => machine.font,
+        font @ 1 | font @ 4 => {
+            let previous = machine.font;
+            machine.font = font;
+            previous
+        }
+        _ => 0,
+    };
+
+    Ok(())
+}
+
+// text in a version 6 window stays out of its margins ( in pixels )
+pub fn set_margins<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (left, right) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
+    let window = graphics_window(code, 2, machine)?;
+
+    machine.graphics.set_margins(window, left, right);
+
+    Ok(())
+}
+
+// see set_colour - styles add up, until style 0 turns them all off
+pub fn set_text_style<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    if machine.get_version() == 6 {
+        let style = code.operands[0].get_value()?;
+        let window = machine.graphics.current_window();
+
+        window.style = match style {
+            0 => 0,
+            x => window.style | x,
+        };
+    }
+
+    Ok(())
+}
+
+// see set_colour
+pub fn set_true_colour<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    if machine.get_version() == 6 {
+        let (foreground, background) =
+            (code.operands[0].get_value()?, code.operands[1].get_value()?);
+        let window = graphics_window(code, 2, machine)?;
+
+        machine
+            .graphics
+            .set_true_colours(window, foreground, background);
+    }
+
+    Ok(())
 }
 
 // this sets a bit in the attributes table
-pub fn set_attr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn set_attr<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     // println!("{}",code);
-    let (object, attr) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (object, attr) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
-    machine.get_object_view(object).set_attribute(attr);
+    machine.get_object_view(object)?.set_attribute(attr)?;
 
     // done
+
+    Ok(())
 }
 
-pub fn set_window<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    unimplemented!();
+// selects the window everything is printed to - selecting the upper window
+// puts its cursor back at the top left
+pub fn set_window<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    // version 6 windows keep their cursors
+    if machine.get_version() == 6 {
+        machine.graphics.current = graphics_window(code, 0, machine)?;
+        return Ok(());
+    }
+
+    match code.operands[0].get_value()? {
+        0 => machine.screen.current = Window::Lower,
+        1 => {
+            machine.screen.current = Window::Upper;
+            machine.screen.cursor = (1, 1);
+        }
+        // only version 6 has any more windows than that
+        _ => (),
+    }
+
+    Ok(())
 }
 
-pub fn sound_effect<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    unimplemented!();
+// plays ( or prepares, stops, or unloads ) a sound - see sound.rs
+//
+// sounds are optional - a game has to carry on without them, so the
+// interface is free to not play anything at all
+pub fn sound_effect<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    // with nothing given at all, it's a high bleep
+    let number = match code.operands[0] {
+        Operand::Omitted => 1,
+        ref op => op.get_value()?,
+    };
+
+    // starting is what a sound does if it isn't told otherwise
+    let effect = match code.operands[1] {
+        Operand::Omitted => 2,
+        ref op => op.get_value()?,
+    };
+
+    // the volume is the low byte ( -1 is as loud as possible ), and
+    // the number of repeats is the high one
+    let (volume, repeats) = match code.operands[2] {
+        Operand::Omitted => (8, 0),
+        ref op => {
+            let value = op.get_value()?;
+            (cmp::min(value & 0xFF, 8) as u8, (value >> 8) as u8)
+        }
+    };
+
+    let sound = match (number, effect) {
+        (1, _) | (2, _) => SoundEffect::Bleep { high: number == 1 },
+        (_, 1) => SoundEffect::Prepare { number },
+        (_, 2) => {
+            // starting a sound means the last one won't finish by itself
+            machine.sound_interrupt = match code.operands[3] {
+                Operand::Omitted => None,
+                ref op => Some(SoundInterrupt {
+                    number,
+                    routine: op.get_value()?,
+                }),
+            };
+
+            SoundEffect::Start {
+                number,
+                volume: cmp::max(volume, 1),
+                // version 3 stories leave it to the sound
+                repeats: match machine.get_version() {
+                    1..=4 => 0,
+                    _ => repeats,
+                },
+            }
+        }
+        (_, 3) => {
+            if let Some(interrupt) = machine.sound_interrupt {
+                if number == 0 || interrupt.number == number {
+                    machine.sound_interrupt = None;
+                }
+            }
+
+            SoundEffect::Stop { number }
+        }
+        (_, 4) => SoundEffect::Unload { number },
+        _ => return Ok(()),
+    };
+
+    machine.zinterface.sound_effect(&sound);
+
+    Ok(())
 }
 
 // this is only for version 1-3
@@ -917,7 +2355,10 @@ pub fn sound_effect<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
 // In Versions 1 to 3, a status line should be printed by the interpreter, as follows. In Version
 // 3, it must set bit 4 of 'Flags 1' in the header if it is unable to produce a status line.
 //
-pub fn show_status<T: ZInterface>(_: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn show_status<T: ZInterface>(
+    _: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     // The short name of the object whose number is in the first global variable should be printed
     // on the left hand side of the line.
 
@@ -932,16 +2373,21 @@ pub fn show_status<T: ZInterface>(_: &mut OpCode<T>, machine: &mut ZMachine<T>)
     {
         let globals = machine.get_global_variables_view();
 
-        let score_object = globals.read_global(0);
-        let first_param = globals.read_global(1);
-        let second_param = globals.read_global(2);
+        let score_object = globals.read_global(0)?;
+        let first_param = globals.read_global(1)?;
+        let second_param = globals.read_global(2)?;
 
         let view = machine
-            .get_object_view(score_object)
-            .get_properties_table_view();
+            .get_object_view(score_object)?
+            .get_properties_table_view()?;
         // the string is offset by one because properties starts with the size byte,
         // then is followed by the short name of the object
-        let score_name = ZString::create(1, &view.view, &machine.get_abbreviations_view());
+        let score_name = ZString::create(
+            1,
+            &view.view,
+            &machine.get_abbreviations_view(),
+            &machine.charset,
+        )?;
 
         let out = match (status_line, first_param > 12) {
             (&StatusLineType::Hours, false) => format!("Time: {}:{} AM", first_param, second_param),
@@ -960,14 +2406,57 @@ pub fn show_status<T: ZInterface>(_: &mut OpCode<T>, machine: &mut ZMachine<T>)
         (Some(l), Some(r)) => machine.zinterface.print_to_header(&l, &r),
         _ => (),
     }
+
+    Ok(())
 }
 
-pub fn split_window<T: ZInterface>(_: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    unimplemented!();
+// gives lines lines at the top of the screen to the upper window
+// ( or takes them back, with 0 )
+pub fn split_window<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let lines = code.operands[0].get_value()?;
+
+    // version 6 splits in pixels, and only ever moves windows 0 and 1
+    if machine.get_version() == 6 {
+        machine.graphics.split(lines);
+        return Ok(());
+    }
+
+    machine.screen.upper_height = lines;
+
+    // the cursor can't be left outside of the window
+    if machine.screen.cursor.0 > lines {
+        machine.screen.cursor = (1, 1);
+    }
+
+    if lines == 0 {
+        machine.screen.current = Window::Lower;
+    }
+
+    machine.zinterface.split_window(machine.screen.top, lines);
+
+    // in version 3 the upper window is cleared every time it's split
+    if machine.get_version() == 3 && lines > 0 {
+        machine.screen.cursor = (1, 1);
+        machine.zinterface.erase_window(Window::Upper);
+    }
+
+    Ok(())
 }
 
-pub fn sread<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    show_status(code, machine);
+pub fn sread<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    show_status(code, machine)?;
+
+    // running again after an undo, so the prompt the game printed last
+    // time has to come back too
+    if let Some(prompt) = machine.undone_prompt.take() {
+        machine.print(&prompt);
+    }
 
     // we might want to change where this is in the future, it seems like
     // we might not want to rely on an opcode to flush the output -
@@ -980,12 +2469,16 @@ pub fn sread<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     // the CLI version, io::stdout has an implementation in WASM (it's the console),
     // but since we don't use it in the WASM version, nothing is flushed
 
-    match io::stdout().flush() {
-        Err(_) => panic!("could not flush the output!"),
-        Ok(_) => (),
-    };
+    // if this fails there isn't much we can do about it, and the
+    // output will come out eventually anyway
+    let _ = io::stdout().flush();
 
-    let (text_buffer, parse_buffer) = (code.operands[0].get_value(), code.operands[1].get_value());
+    // from version 5 the parse buffer is optional
+    let text_buffer = code.operands[0].get_value()?;
+    let parse_buffer = match code.operands[1] {
+        Operand::Omitted => 0,
+        ref op => op.get_value()?,
+    };
 
     // this a little cheat;
     //
@@ -1004,6 +2497,40 @@ pub fn sread<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
     let view = machine.get_memory_view();
     let dictionary_view = machine.get_dictionary_view();
     let version = machine.header.version;
+    let charset = machine.charset.clone();
+
+    // the callback writes straight into these, so if they aren't all in
+    // memory we have to find out now
+    tokenizer::check_text_buffer(&view, text_buffer, version, false)?;
+
+    if parse_buffer != 0 {
+        tokenizer::check_parse_buffer(&view, parse_buffer)?;
+        tokenizer::check_dictionary(&dictionary_view, version)?;
+    }
+
+    // the interface gets told how much fits, so it can stop the player
+    // typing any more - the text is cut off here anyway, in case it doesn't
+    let max_length = tokenizer::max_length(&view, text_buffer, version)?;
+
+    // before version 5, games have no undo of their own, so we keep a state
+    // from right before each command for the machine's undo ( the ip is still
+    // on this instruction, so undoing comes back here to ask again )
+    if version <= 4 {
+        let ip = machine.ip;
+        machine.save_undo(ip);
+    }
+
+    // from version 5 this is aread, which stores the character that ended
+    // the input - we don't know it until the input is in, so we store a
+    // placeholder now and the machine writes over it when the input comes
+    let store = match version {
+        1..=4 => None,
+        _ => {
+            code.store = true;
+            code.result = 0;
+            Some(machine.get_frame_view().read_at_head(code.read_bytes)?)
+        }
+    };
 
     let process_input = Rc::new(move |input: String| {
         // text and parse are addresses that indicate where
@@ -1012,344 +2539,218 @@ pub fn sread<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
         // the parse-buffer, if non-zero, should be filled
         // with tokenized words from the text buffer that
         // match the dictionary
-        //
-        // you'll notice a slight difference between this
-        // and the parse_buffer call - we want to avoid
-        // splitting the string if the input is too large
 
-        let mut cursor = text_buffer as u32;
-        let max_length = view.read_at(cursor);
-        // we also don't write over the max length,
-        // i believe that stays the same all game
-
-        cursor += 1;
-
-        if input.len() as u8 > max_length {
-            //println!("response too large! try again");
-            return;
-        }
-
-        // we have to double-let because it actually
-        // goes from String to &str and back again
+        // the text goes in as it was typed ( less the newline, and any
+        // spaces around it ), so the positions in the parse buffer line up -
+        // anything past what the buffer holds is cut off
         //
-        // im not 100% sure whats the best way to handle
-        // that uniformly at this point
-
-        let cleaned_input = input.trim();
-        let cleaned_input = cleaned_input.to_lowercase();
-        let split = cleaned_input.split_whitespace();
-
-        // we use cursor because we had to increment the text buffer pos by 1
-        // split is no longer ours
-        let words = sread_write_to_text_buffer(&view, split, cursor);
+        // the buffers and dictionary were checked before we asked for
+        // input, so these can't fail here
+        let _ = tokenizer::write_text_buffer(&view, text_buffer, input.trim(), version, &charset);
 
         // if the parse buffer is 0, that means we don't parse at all
-        if parse_buffer == 0 {
-            return;
+        if parse_buffer != 0 {
+            let _ = tokenizer::write_parse_buffer(
+                &view,
+                text_buffer,
+                parse_buffer,
+                &dictionary_view,
+                version,
+                &charset,
+                false,
+            );
         }
 
-        // we pass version so the word can be correctly encoded
-        sread_write_to_parse_buffer(&view, &words, &dictionary_view, parse_buffer, version);
+        13
     });
 
     machine.state = MachineState::TakingInput {
         callback: process_input,
-    }
-}
-
-// private helper function for sread, takes a cleaned, split input
-// and writes it to the text buffer at the position indicated,
-// and returns a Vec of words in a list ( for use in the next step )
-//
-// this function takes ownership of split ( and technically text buffer, but thats Copy )
-fn sread_write_to_text_buffer(
-    view: &MemoryView,
-    mut split: SplitWhitespace,
-    mut text_buffer: u32,
-) -> Vec<String> {
-    // 8 words is probably a reasonable upper bound
-    let mut words = Vec::with_capacity(8);
-
-    while let Some(word) = split.next() {
-        for ch in word.chars() {
-            view.write_at(text_buffer, ch as u8);
-            text_buffer += 1;
-        }
-
-        view.write_at(text_buffer, ' ' as u8);
-        text_buffer += 1;
-
-        words.push(String::from(word));
-    }
-
-    words
-}
-
-// private helper function for sread, takes a vec of words, looks them up in the dictionary,
-// then writes
-//
-// 1) address in dictionary in first two bytes( 0 if nothing ),
-// 2) # of characters in second byte
-// 3) position in string of first letter of the word
-fn sread_write_to_parse_buffer(
-    view: &MemoryView,
-    words: &Vec<String>,
-    dictionary_view: &MemoryView,
-    parse_buffer: u16,
-    version: u8,
-) {
-    let mut cursor = parse_buffer as u32;
-
-    // whats the max # of parsed tokens?
-    let max_tokens = view.read_at(cursor);
-
-    // determine the count from the lower of the two - the # of words,
-    // and the max count
-    let token_count = cmp::min(max_tokens, words.len() as u8) as usize;
-
-    // we don't write to this
-    cursor += 1;
-
-    // write the word(token) count in the first byte of the parse buffer
-    view.write_at(cursor, token_count as u8);
-
-    // we write to this
-    cursor += 1;
-
-    // we need a mutable letter cursor for this double loop,
-    // which indicates the start of the word in the sentence
-    // rather than the place in memory
-    let mut letter_cursor = 1;
-
-    for word in words[0..token_count].iter() {
-        let encoded_word = ZString::encode_word(word, version);
-        let address: Option<u32> = sread_find_word_in_dictionary(&encoded_word, &dictionary_view);
-
-        // write the address of the abbreviations table from the dictionary
-
-        // println!( "writing to: {:x}", cursor );
-
-        match address {
-            // this might look dangerous, but abbreviation strings
-            // actually are in the lower 128k of memory ( after the header )
-            // i think the maximum size allowed is 64k - 64 bytes ( the size
-            // of the header )
-            Some(x) => view.write_u16_at(cursor, x as u16),
-            None => view.write_u16_at(cursor, 0),
-        }
-
-        view.write_at(cursor + 2, word.len() as u8);
-        view.write_at(cursor + 3, letter_cursor);
-
-        // increment the letter cursor, include a space
-        letter_cursor += word.len() as u8 + 1;
-
-        // increment the parse cursor by 4 bytes
-        cursor += 4;
-    }
-}
-
-// private helper function for sread, finds words in the dictionary
-// i believe this is the only op - or part of the code - that uses it
-fn sread_find_word_in_dictionary(string: &ZWord, dictionary: &MemoryView) -> Option<u32> {
-    // so interestingly enough, the dictionary starts with a # and a list of
-    // codes which correspond to keyboard input.
-    let num_input_codes = dictionary.read_at_head(0) as u32;
-
-    // after all the input codes, which are a byyte each, we have the entry length
-    let entry_length = dictionary.read_at_head(num_input_codes + 1) as u32;
-
-    // and one after that we have the # of entries in a word
-    let dictionary_entries = dictionary.read_u16_at_head(num_input_codes + 2) as u32;
-
-    // so the total offset is four bytes + num_input_codes
-    let dictionary_header_offset = num_input_codes + 4;
-
-    // and we more or less have to compute this each time, since its actually legal
-    // to alter the dictionary
-
-    // binary search
-    // it ends up working because all characters are padded with the same
-    // end character (5), and the numerical order of z-characters turns
-    // out to also be the alphabetic one
-    //
-    // we basically take the first 4 or 6 bytes of the word and turn
-    // it into a number, and that's the index of the abbreviations table
-
-    let mut address = None;
-    let mut lower = 0;
-    let mut upper = dictionary_entries - 1;
-    let mut pointer;
-
-    while lower <= upper {
-        pointer = lower + (upper - lower) / 2;
-        let offset = dictionary_header_offset + pointer * entry_length;
-        let found: bool;
-
-        // we need to both pull the encoded value out of the enum,
-        // and the entry from the table itself, because if there
-        // is no match, we have to change the upper/lower bounds
-        // based on comparing the two values
-        //
-        // note that in the future these will probably need to be vecs,
-        // because the match arms wont match types when v4 is implemented
-
-        let (encoded_string, dictionary_entry) = match string {
-            // we can "move" encoded out of zword here, because it has the copy trait
-            // as an array of u8s
-            &ZWord::V3 { encoded, .. } => {
-                let encoded_entry = [
-                    dictionary.read_at_head(offset),
-                    dictionary.read_at_head(offset + 1),
-                    dictionary.read_at_head(offset + 2),
-                    dictionary.read_at_head(offset + 3),
-                ];
-
-                found = encoded_entry == encoded;
-
-                (encoded, encoded_entry)
-            }
-            // v4 not implemented yet
-            _ => unimplemented!(),
-        };
-
-        if found {
-            // println!("found the lookup!");
-            address = Some(dictionary.pointer + offset);
-            break;
-        }
-
-        // note this will not work if the entries are larger than
-        // four words ( 64 bits ), but realistically we only deal
-        // with 32 and 48 bit situations
-        //
-        // we take all the the bytes, and combine them into one entry,
-        // and then compare the 32 or "48" bit number ( both housed
-        // in a 64 bit number since modern cpus woo! )
-
-        let encode_map = [encoded_string, dictionary_entry]
-            .iter()
-            .map(|container| {
-                let mut total: u64 = 0;
-
-                for (i, value) in container.iter().enumerate() {
-                    let val = *value;
-                    let shift = ((container.len() - i - 1) * 8) as u8;
-                    total = total | ((val as u64) << shift);
-                }
-
-                total
-
-                // turbofish!
-            })
-            .collect::<Vec<u64>>();
-
-        let (encoded, encoded_entry) = (encode_map[0], encode_map[1]);
-
-        // the ordering of the table corresponds to dictionary ordering,
-        // and is sorted
-
-        if encoded < encoded_entry {
-            upper = pointer - 1;
-        } else {
-            lower = pointer + 1;
-        }
-    }
+        store,
+        line: true,
+        max_length,
+    };
 
-    address
+    Ok(())
 }
 
 // stores that aren't stores trip me up, honestly
-pub fn store<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
-    let (variable, value) = (code.operands[0].get_value(), code.operands[1].get_value());
-    machine.store_variable(variable as u8, value);
+pub fn store<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (variable, value) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
+    machine.store_variable(variable as u8, value)?;
     // done
+
+    Ok(())
 }
 
 // this only operates on dynamic and static memory
-pub fn storeb<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn storeb<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     let (start, index, value) = (
-        code.operands[0].get_value(),
-        code.operands[1].get_value(),
-        code.operands[2].get_value(),
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()?,
+        code.operands[2].get_value()?,
     );
 
-    let address = start + index;
+    let address = start.wrapping_add(index) as u32;
 
-    machine
-        .get_memory_view()
-        .write_at(address as u32, value as u8);
+    machine.get_memory_view().write_at(address, value as u8)?;
+
+    Ok(())
 }
 
 // this only operates on dynamic and static memory
-pub fn storew<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn storew<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     let (start, index, value) = (
-        code.operands[0].get_value(),
-        code.operands[1].get_value(),
-        code.operands[2].get_value(),
+        code.operands[0].get_value()?,
+        code.operands[1].get_value()?,
+        code.operands[2].get_value()?,
     );
 
-    let address = start + (index * 2);
-    machine
-        .get_memory_view()
-        .write_u16_at(address as u32, value);
+    let address = start.wrapping_add(index.wrapping_mul(2)) as u32;
+
+    machine.get_memory_view().write_u16_at(address, value)?;
 
     // thats all we wrote, its strange store calls don't
     // actually follow the "store" mechanism, but there
     // must have been an design reason
+
+    Ok(())
 }
 
-pub fn sub<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn sub<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.store = true;
-    code.result =
-        ((code.operands[0].get_value() as i16) - (code.operands[1].get_value() as i16)) as u16;
+    code.result = (code.operands[0].get_value()? as i16)
+        .wrapping_sub(code.operands[1].get_value()? as i16) as u16;
+
+    Ok(())
 }
 
-pub fn test<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
+pub fn test<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
     code.branch = true;
 
-    let (mask, flags) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (mask, flags) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     code.result = (mask & flags == flags) as u16;
+
+    Ok(())
 }
 
-pub fn test_attr<T: ZInterface>(code: &mut OpCode<T>, machine: &mut ZMachine<T>) {
+pub fn test_attr<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
     code.branch = true;
 
-    let (object, attribute) = (code.operands[0].get_value(), code.operands[1].get_value());
+    let (object, attribute) = (code.operands[0].get_value()?, code.operands[1].get_value()?);
 
     // println!( "object:{}", object);
     // println!( "attribute:{}", attribute);
 
-    code.result = machine.get_object_view(object).has_attribute(attribute) as u16;
+    code.result = machine.get_object_view(object)?.has_attribute(attribute)? as u16;
 
     // println!( "result:{}", code.result);
+
+    Ok(())
+}
+
+// returns value from the routine that frame ( the result of catch ) belongs to,
+// throwing away every routine called since
+pub fn throw<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let frame = code.operands[1].get_value()?;
+
+    machine.call_stack.unwind_to(frame)?;
+
+    // the value is already the first operand, so ret can take it from here
+    ret(code, machine)
+}
+
+// tokenise does the second half of sread on a text buffer that's already filled,
+// optionally using a different dictionary
+//
+// if flag is set, words that aren't in the dictionary are left alone in the
+// parse buffer
+pub fn tokenise<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let (text_buffer, parse_buffer) =
+        (code.operands[0].get_value()?, code.operands[1].get_value()?);
+
+    let dictionary = match code.operands[2] {
+        Operand::Omitted => 0,
+        ref op => op.get_value()?,
+    };
+
+    let dictionary_view = match dictionary {
+        0 => machine.get_dictionary_view(),
+        address => MemoryView {
+            memory: machine.memory.clone(),
+            pointer: address as u32,
+        },
+    };
+
+    let skip_unknown = match code.operands[3] {
+        Operand::Omitted => false,
+        ref op => op.get_value()? != 0,
+    };
+
+    let view = machine.get_memory_view();
+    let version = machine.get_version();
+
+    tokenizer::check_text_buffer(&view, text_buffer, version, true)?;
+    tokenizer::check_parse_buffer(&view, parse_buffer)?;
+    tokenizer::check_dictionary(&dictionary_view, version)?;
+
+    tokenizer::write_parse_buffer(
+        &view,
+        text_buffer,
+        parse_buffer,
+        &dictionary_view,
+        version,
+        &machine.charset,
+        skip_unknown,
+    )
 }
 
-fn unparent_object<T: ZInterface>(obj_view: &mut ObjectView, machine: &mut ZMachine<T>) {
-    let current_parent = obj_view.get_parent();
+fn unparent_object<T: ZInterface>(
+    obj_view: &mut ObjectView,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let current_parent = obj_view.get_parent()?;
 
     if current_parent == 0 {
-        return;
+        return Ok(());
     }
 
-    obj_view.set_parent(0);
+    obj_view.set_parent(0)?;
 
-    let parent_view = machine.get_object_view(current_parent);
-    let mut current_child = parent_view.get_child();
+    let parent_view = machine.get_object_view(current_parent)?;
+    let mut current_child = parent_view.get_child()?;
 
+    // object marked as having a parent that does not have any children!
     if current_child == 0 {
-        panic!(
-            "object tree badly formed - object marked as having a parent that \
-             does not have any children!"
-        );
+        return Err(ErrorKind::MalformedObjectTree {
+            object: obj_view.object_id,
+        });
     }
 
     // i would try to generalize the logic, but as it turns out, you do completely
     // different things
     if current_child == obj_view.object_id {
         // 1) if first child, set parent's new first child to child's sibling
-        parent_view.set_child(obj_view.get_sibling());
+        parent_view.set_child(obj_view.get_sibling()?)?;
     } else {
         // 2), if not first childk, progress through children until child is found, then
         // set previous child to child's sibling
@@ -1357,40 +2758,69 @@ fn unparent_object<T: ZInterface>(obj_view: &mut ObjectView, machine: &mut ZMach
 
         loop {
             last_child = current_child;
-            current_child = machine.get_object_view(current_child).get_sibling();
+            current_child = machine.get_object_view(current_child)?.get_sibling()?;
 
             if current_child == obj_view.object_id {
                 break;
             }
 
+            // object marked as having a parent it does not
             if current_child == 0 {
-                panic!(
-                    "object tree badly formed - object marked as having a parent it \
-                     does not"
-                );
+                return Err(ErrorKind::MalformedObjectTree {
+                    object: obj_view.object_id,
+                });
             }
         }
 
-        let new_sibling = obj_view.get_sibling();
-        machine.get_object_view(last_child).set_sibling(new_sibling);
+        let new_sibling = obj_view.get_sibling()?;
+        machine
+            .get_object_view(last_child)?
+            .set_sibling(new_sibling)?;
     }
+
+    Ok(())
 }
 
-// Checksum always passes, for now
+// checks the story adds up to the checksum in its header
 //
-// This was used for piracy and fidelity reasons;
-// but these days? I'm not sure what the benefit of implementing it would be.
+// this was used for piracy and fidelity reasons originally, but it's also handy
+// for telling which build of a story someone has. patched story files usually
+// don't fix up their checksum, so the machine can be told to always pass
+// ( see force_verify )
+pub fn verify<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    code.branch = true;
+    code.result = machine.verify() as u16;
 
-// I don't know of any games that actually use it for a game purpose,
-// and modified story files are so common these days - with different checksums -
-// that there's no real "verifying" an original - whats the point, if a user wants to play
-// it, let them.
-//
-// So, we aren't going to bother here. if the story file is tampered with
-// whatever, if it is tampered to the point of breaking, its going to crash
-// anyway.
+    Ok(())
+}
 
-pub fn verify<T: ZInterface>(code: &mut OpCode<T>, _: &mut ZMachine<T>) {
-    code.branch = true;
-    code.result = 1;
+// resizes a version 6 window to height by width pixels
+pub fn window_size<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let window = graphics_window(code, 0, machine)?;
+    let (height, width) = (code.operands[1].get_value()?, code.operands[2].get_value()?);
+
+    machine.graphics.resize_window(window, height, width);
+
+    Ok(())
+}
+
+// changes a version 6 window's attributes ( wrapping, scrolling, whether
+// it goes to the transcript and buffering )
+pub fn window_style<T: ZInterface>(
+    code: &mut OpCode<T>,
+    machine: &mut ZMachine<T>,
+) -> InstructionResult<()> {
+    let window = graphics_window(code, 0, machine)?;
+    let flags = code.operands[1].get_value()?;
+    let operation = optional_value(code, 2)?;
+
+    machine.graphics.set_attributes(window, flags, operation);
+
+    Ok(())
 }
@@ -1,3 +1,5 @@
+use super::error::*;
+
 use std::cell::RefCell;
 use std::rc::*;
 
@@ -52,66 +54,65 @@ pub struct MemoryView {
 }
 
 impl MemoryView {
-    // only takes a Vec<u8> now... no real need to make memory view a generic
-    // also it would break all the read etc functions , or at least make them
-    // really complex
-
-    pub fn from_vec(vec: Vec<u8>) -> MemoryView {
-        let rc = Rc::new(RefCell::new(vec));
-        MemoryView {
-            memory: rc,
-            pointer: 0,
-        }
-    }
-
     // i could return a usize here, but that means more
     // unnecessary casting
     fn program_offset(&self, offset: u32) -> u32 {
-        (self.pointer + (offset))
+        (self.pointer.wrapping_add(offset))
     }
 
-    // this peeks at the top of the stack and copies the first two bytes
+    // this peeks at the top of the stack and copies the first three bytes
     // into an array, and returns
-    pub fn peek_at_instruction(&self) -> [u8; 2] {
-        let x = [self.read_at_head(0), self.read_at_head(1)];
-        x
+    //
+    // most instructions only need the first two, so an instruction right at
+    // the end of memory just gets a 0 for the third
+    pub fn peek_at_instruction(&self) -> InstructionResult<[u8; 3]> {
+        let third = match self.memory.borrow().get(self.program_offset(2) as usize) {
+            Some(x) => *x,
+            None => 0,
+        };
+
+        let x = [self.read_at_head(0)?, self.read_at_head(1)?, third];
+        Ok(x)
     }
 
-    pub fn read_at(&self, address: u32) -> u8 {
+    // for when a whole run of memory is about to be read ( or written ) and
+    // it's easier to check the far end once up front
+    pub fn check_address(&self, address: u32) -> InstructionResult<()> {
+        match (address as usize) < self.memory.borrow().len() {
+            true => Ok(()),
+            false => Err(ErrorKind::MemoryOutOfRange { address }),
+        }
+    }
+
+    // every read and write below checks the address, since nearly all of
+    // them come from the story one way or another ( and a broken story
+    // shouldn't take us down with it )
+    pub fn read_at(&self, address: u32) -> InstructionResult<u8> {
         // println!("address: {}", address);
-        self.memory.borrow()[address as usize]
+        match self.memory.borrow().get(address as usize) {
+            Some(x) => Ok(*x),
+            None => Err(ErrorKind::MemoryOutOfRange { address }),
+        }
     }
 
-    pub fn read_at_head(&self, offset: u32) -> u8 {
+    pub fn read_at_head(&self, offset: u32) -> InstructionResult<u8> {
         self.read_at(self.program_offset(offset))
     }
 
-    pub fn read_u16_at(&self, address: u32) -> u16 {
-        let upper_half = (self.read_at(address) as u16) << 8;
-        let lower_half = self.read_at(address + 1) as u16;
+    pub fn read_u16_at(&self, address: u32) -> InstructionResult<u16> {
+        let upper_half = (self.read_at(address)? as u16) << 8;
+        let lower_half = self.read_at(address.wrapping_add(1))? as u16;
         let result = upper_half | lower_half;
 
         // println!( "upper_half: {}", upper_half );
         // println!( "lower_half: {}", lower_half );
         // println!( "result: {}", result );
 
-        result
-    }
-
-    pub fn read_u16_at_head(&self, offset: u32) -> u16 {
-        self.read_u16_at(self.pointer + offset)
+        Ok(result)
     }
 
-    pub fn read_u32_at(&self, address: u32) -> u32 {
-        let upper_half = (self.read_u16_at(address) as u32) << 16;
-        let lower_half = self.read_u16_at(address + 2) as u32;
-        let result = upper_half | lower_half;
-
-        result
-    }
-
-    pub fn read_u32_at_head(&self, offset: u32) -> u32 {
-        self.read_u32_at(self.pointer + offset)
+    pub fn read_u16_at_head(&self, offset: u32) -> InstructionResult<u16> {
+        self.read_u16_at(self.program_offset(offset))
     }
 
     // in the future, i would actually like to make this "mut self"
@@ -122,16 +123,22 @@ impl MemoryView {
     // level through the view anyway, even if no property of the view is property changed,
     // forcing mut to access mut might be a good pattern
 
-    pub fn write_at(&self, address: u32, value: u8) {
+    pub fn write_at(&self, address: u32, value: u8) -> InstructionResult<()> {
         let mut memory = self.memory.borrow_mut();
-        memory[address as usize] = value;
+
+        match memory.get_mut(address as usize) {
+            Some(x) => *x = value,
+            None => return Err(ErrorKind::MemoryOutOfRange { address }),
+        }
+
+        Ok(())
     }
 
-    pub fn write_at_head(&self, offset: u32, value: u8) {
-        self.write_at(self.pointer + offset, value);
+    pub fn write_at_head(&self, offset: u32, value: u8) -> InstructionResult<()> {
+        self.write_at(self.program_offset(offset), value)
     }
 
-    pub fn write_u16_at(&self, address: u32, value: u16) {
+    pub fn write_u16_at(&self, address: u32, value: u16) -> InstructionResult<()> {
         let upper_half = (value >> 8 & 0xFF) as u8;
         let lower_half = (value & 0xFF) as u8;
 
@@ -139,23 +146,15 @@ impl MemoryView {
         // println!( "upper:{}" , upper_half );
         // println!( "lower:{}" , lower_half );
 
-        self.write_at(address, upper_half);
-        self.write_at(address + 1, lower_half);
-    }
-
-    pub fn write_u16_at_head(&self, offset: u32, value: u16) {
-        self.write_u16_at(self.pointer + offset, value);
-    }
-
-    pub fn write_u32_at(&self, address: u32, value: u32) {
-        let upper_half = (value >> 16 & 0xFFFF) as u16;
-        let lower_half = (value & 0xFFFF) as u16;
+        // the far end first, so a word half off the end of memory
+        // doesn't get half written
+        self.check_address(address.wrapping_add(1))?;
 
-        self.write_u16_at(address, upper_half);
-        self.write_u16_at(address + 2, lower_half);
+        self.write_at(address, upper_half)?;
+        self.write_at(address.wrapping_add(1), lower_half)
     }
 
-    pub fn write_u32_at_head(&self, offset: u32, value: u32) {
-        self.write_u32_at(self.pointer + offset, value);
+    pub fn write_u16_at_head(&self, offset: u32, value: u16) -> InstructionResult<()> {
+        self.write_u16_at(self.program_offset(offset), value)
     }
 }
@@ -1,10 +1,19 @@
+pub mod blorb;
+pub mod error;
 mod global_variables_view;
-mod header;
+pub mod graphics;
+pub mod header;
+mod iff;
 pub mod input_handler;
 mod instruction_set;
-mod memory_view;
+pub mod memory_view;
 mod object_properties_view;
 mod object_view;
 mod opcode;
+mod quetzal;
+pub mod screen;
+pub mod sound;
+pub mod streams;
+mod tokenizer;
 pub mod zmachine;
 mod zstring;
@@ -1,8 +1,8 @@
+use super::error::*;
 use super::memory_view::*;
 
 pub struct ObjectProperty {
     pub value: u16,
-    pub info: ObjectPropertyInfo,
 }
 
 #[derive(Clone, Copy)]
@@ -23,6 +23,8 @@ pub struct ObjectPropertiesView {
     // to easily read the defaults table from any one object property view
     pub defaults_view: MemoryView,
     pub view: MemoryView,
+    // the size bytes changed in version 4, see read_size_bytes
+    pub version: u8,
 }
 
 impl ObjectPropertiesView {
@@ -31,44 +33,105 @@ impl ObjectPropertiesView {
         pointer_position: u32,
         defaults_view: &MemoryView,
         memory: &MemoryView,
-    ) -> ObjectPropertiesView {
+        version: u8,
+    ) -> InstructionResult<ObjectPropertiesView> {
         let mut view = memory.clone();
         view.pointer = pointer_position;
 
-        let text_size = view.read_at(pointer_position);
+        // the address comes out of the object, which the story can write
+        // anything to - and the short name after it is text_size words
+        let text_size = view.read_at(pointer_position)?;
+        view.check_address(pointer_position + 2 * text_size as u32)?;
 
-        ObjectPropertiesView {
-            object_id: object_id,
+        Ok(ObjectPropertiesView {
+            object_id,
             defaults_view: defaults_view.clone(),
-            text_size: text_size,
-            view: view,
-        }
+            text_size,
+            view,
+            version,
+        })
     }
 
-    pub fn get_object_property_from_size_byte(size_byte: u8) -> ObjectPropertyInfo {
-        // the size byte packs the size by encoding it as
-        // byte = 32(l-1) + id
-        ObjectPropertyInfo {
-            // careful now - don't try to feed this to the wrong place
-            addr: None,
-            size: (size_byte / 32) + 1,
-            id: (size_byte) % 32,
-        }
+    // reads the size byte(s) of the property at address, returning the
+    // property and how many bytes the size took up
+    //
+    // in versions 1-3 the size byte packs the size by encoding it as
+    // byte = 32(l-1) + id. from version 4 there can be 63 properties, so the
+    // id takes the bottom 6 bits - if the top bit is set, the size is in the
+    // bottom 6 bits of a second byte ( 0 means 64 ), otherwise the bit under
+    // it says whether the property is 2 bytes or 1
+    pub fn read_size_bytes(
+        view: &MemoryView,
+        address: u32,
+        version: u8,
+    ) -> InstructionResult<(ObjectPropertyInfo, u32)> {
+        let size_byte = view.read_at(address)?;
+
+        // careful now - don't try to feed this to the wrong place
+        let (size, id, length) = match (version, size_byte & 0x80, size_byte & 0x40) {
+            (1..=3, _, _) => ((size_byte / 32) + 1, size_byte % 32, 1),
+            (_, 0, 0) => (1, size_byte & 0x3F, 1),
+            (_, 0, _) => (2, size_byte & 0x3F, 1),
+            (_, _, _) => match view.read_at(address + 1)? & 0x3F {
+                0 => (64, size_byte & 0x3F, 2),
+                x => (x, size_byte & 0x3F, 2),
+            },
+        };
+
+        Ok((
+            ObjectPropertyInfo {
+                addr: None,
+                size,
+                id,
+            },
+            length,
+        ))
+    }
+
+    // the size of the property whose data starts at address ( which is what
+    // get_prop_addr hands out ), worked out from the byte just before it -
+    // from version 4, that's either the only size byte or the second one, and
+    // the second always has its top bit set
+    pub fn get_property_length(
+        view: &MemoryView,
+        address: u32,
+        version: u8,
+    ) -> InstructionResult<u8> {
+        let size_byte = view.read_at(address.wrapping_sub(1))?;
+
+        Ok(match (version, size_byte & 0x80, size_byte & 0x40) {
+            (1..=3, _, _) => (size_byte / 32) + 1,
+            (_, 0, 0) => 1,
+            (_, 0, _) => 2,
+            (_, _, _) => match size_byte & 0x3F {
+                0 => 64,
+                x => x,
+            },
+        })
+    }
+
+    // where the first property's size byte is - after the text and
+    // the text size byte
+    pub fn first_property_offset(&self) -> u32 {
+        2 * (self.text_size as u32) + 1
     }
 
     // gets the property address ( full address )
-    pub fn get_property_addr(&self, property_index: u8) -> u32 {
-        let info = self.get_property_info(property_index);
+    pub fn get_property_addr(&self, property_index: u8) -> InstructionResult<u32> {
+        let info = self.get_property_info(property_index)?;
         match info.addr {
-            None => 0,
-            Some(x) => x + self.view.pointer,
+            None => Ok(0),
+            Some(x) => Ok(x + self.view.pointer),
         }
     }
 
     // gets the property info, which includes address, size, and id
-    pub fn get_property_info(&self, property_index: u8) -> ObjectPropertyInfo {
+    //
+    // a table that runs off the end of memory ( no 0 to end it, or a
+    // property that's longer than what's left ) is an error, not a panic
+    pub fn get_property_info(&self, property_index: u8) -> InstructionResult<ObjectPropertyInfo> {
         // we skip the text and the text size byte
-        let mut pointer_cursor = 2 * (self.text_size as u32) + 1;
+        let mut pointer_cursor = self.first_property_offset();
 
         let mut info = ObjectPropertyInfo {
             addr: None,
@@ -79,70 +142,100 @@ impl ObjectPropertiesView {
         let find_first = property_index == 0;
 
         loop {
-            let size_byte = self.view.read_at_head(pointer_cursor);
-
             // terminate on size byte of 0
-            if size_byte == 0 {
+            if self.view.read_at_head(pointer_cursor)? == 0 {
                 break;
             }
 
-            let found_info = ObjectPropertiesView::get_object_property_from_size_byte(size_byte);
+            let (found_info, size_length) = ObjectPropertiesView::read_size_bytes(
+                &self.view,
+                self.view.pointer + pointer_cursor,
+                self.version,
+            )?;
+
+            let data = pointer_cursor + size_length;
+            self.view
+                .check_address(self.view.pointer + data + found_info.size as u32 - 1)?;
 
             if find_first || found_info.id == info.id {
                 info.size = found_info.size;
-                info.addr = Some(pointer_cursor + 1);
+                info.addr = Some(data);
                 break;
             }
 
-            pointer_cursor += (found_info.size as u32) + 1;
+            pointer_cursor = data + found_info.size as u32;
         }
 
-        info
+        Ok(info)
     }
 
     // gets the property default for this property
-    pub fn get_property_default(&self, property_index: u8) -> u16 {
-        self.defaults_view
-            .read_u16_at_head((property_index as u32 - 1) * 2)
+    pub fn get_property_default(&self, property_index: u8) -> InstructionResult<u16> {
+        // there are only 31 defaults in version 1-3, numbered from 1,
+        // and 63 from version 4
+        let defaults = match self.version {
+            1..=3 => 31,
+            _ => 63,
+        };
+
+        match property_index {
+            i if i >= 1 && i <= defaults => self
+                .defaults_view
+                .read_u16_at_head((property_index as u32 - 1) * 2),
+            _ => Err(ErrorKind::UnknownProperty {
+                object: self.object_id,
+                property: property_index,
+            }),
+        }
     }
 
     // note that this is a little inefficient. but whatever
     // if this really ends up being a performance problem, we can come back to it
     // this will return the default value if the property is not found
-    pub fn get_property(&self, property_index: u8) -> ObjectProperty {
-        let info = self.get_property_info(property_index);
+    pub fn get_property(&self, property_index: u8) -> InstructionResult<ObjectProperty> {
+        let info = self.get_property_info(property_index)?;
 
         let value = match info.addr {
             None => {
                 // println!( "reading default for:{}", property_index );
-                self.get_property_default(property_index)
+                self.get_property_default(property_index)?
             }
             Some(addr) => match info.size {
-                1 => self.view.read_at_head(addr) as u16,
-                2 => self.view.read_u16_at_head(addr),
-                _ => panic!(
-                    "you have an address but no size, or are trying to read a property \
-                     of length > 2"
-                ),
+                1 => self.view.read_at_head(addr)? as u16,
+                2 => self.view.read_u16_at_head(addr)?,
+                // you have an address but no size, or are trying to read
+                // a property of length > 2
+                size => return Err(self.invalid_length(property_index, size)),
             },
         };
 
-        ObjectProperty {
-            info: info,
-            value: value,
-        }
+        Ok(ObjectProperty { value })
     }
 
-    pub fn write_property(&self, property_index: u8, value: u16) {
-        let info = self.get_property_info(property_index);
+    pub fn write_property(&self, property_index: u8, value: u16) -> InstructionResult<()> {
+        let info = self.get_property_info(property_index)?;
 
         match (info.size, info.addr) {
-            (1, Some(addr)) => self.view.write_at_head(addr, value as u8),
-            (2, Some(addr)) => self.view.write_u16_at_head(addr, value),
-            _ => panic!(
-                "you are attempting to write a property to memory that is greater than 2 \
-                 bytes, or doesnt exist"
-            ),
+            (1, Some(addr)) => self.view.write_at_head(addr, value as u8)?,
+            (2, Some(addr)) => self.view.write_u16_at_head(addr, value)?,
+            // unlike get_prop, there's no default to fall back on
+            (_, None) => {
+                return Err(ErrorKind::UnknownProperty {
+                    object: self.object_id,
+                    property: property_index,
+                })
+            }
+            (size, _) => return Err(self.invalid_length(property_index, size)),
+        }
+
+        Ok(())
+    }
+
+    fn invalid_length(&self, property_index: u8, size: u8) -> ErrorKind {
+        ErrorKind::InvalidPropertyLength {
+            object: self.object_id,
+            property: property_index,
+            length: size,
         }
     }
 }
@@ -1,3 +1,4 @@
+use super::error::*;
 use super::memory_view::*;
 use super::object_properties_view::*;
 
@@ -11,6 +12,9 @@ use super::object_properties_view::*;
 
 pub struct ObjectView {
     pub object_id: u16,
+    // objects grew in version 4 - 48 attributes instead of 32, room for 65535
+    // objects instead of 255, and more properties
+    pub version: u8,
     // how long are the attributes in bytes?
     pub attributes_length: u32,
     // this is the property defaults view - mostly
@@ -28,38 +32,49 @@ pub struct ObjectView {
 impl ObjectView {
     // an object can have only one child - everything else in the "bag" is a sibling
     // of the child
-    pub fn get_child(&self) -> u16 {
+    pub fn get_child(&self) -> InstructionResult<u16> {
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
         let pointer_position = self.attributes_length +
                                //the order is parent, sibling, child
                                self.related_obj_length * 2;
 
-        let child_id = self.view.read_at_head(pointer_position);
-        child_id as u16
+        self.read_relative(pointer_position)
     }
 
-    pub fn get_parent(&self) -> u16 {
+    pub fn get_parent(&self) -> InstructionResult<u16> {
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
         let pointer_position = self.attributes_length;
-        let parent_id = self.view.read_at_head(pointer_position);
-
-        parent_id as u16
+        self.read_relative(pointer_position)
     }
 
-    pub fn get_sibling(&self) -> u16 {
+    pub fn get_sibling(&self) -> InstructionResult<u16> {
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
         let pointer_position = self.attributes_length +
                                //the order is parent, sibling, child
                                self.related_obj_length;
 
-        let sibling_id = self.view.read_at_head(pointer_position);
-        sibling_id as u16
+        self.read_relative(pointer_position)
+    }
+
+    // relatives are a byte each in versions 1-3, and a word from 4
+    fn read_relative(&self, pointer_position: u32) -> InstructionResult<u16> {
+        match self.related_obj_length {
+            1 => Ok(self.view.read_at_head(pointer_position)? as u16),
+            _ => self.view.read_u16_at_head(pointer_position),
+        }
+    }
+
+    fn write_relative(&self, pointer_position: u32, object_id: u16) -> InstructionResult<()> {
+        match self.related_obj_length {
+            1 => self.view.write_at_head(pointer_position, object_id as u8),
+            _ => self.view.write_u16_at_head(pointer_position, object_id),
+        }
     }
 
-    pub fn get_properties_table_view(&self) -> ObjectPropertiesView {
+    pub fn get_properties_table_view(&self) -> InstructionResult<ObjectPropertiesView> {
         // println!("starting: {}", self.view.pointer);
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
@@ -67,55 +82,57 @@ impl ObjectView {
 
         // we should now be at the properties table address
         // object addresses are not packed and are in dynamic mem
-        let pointer = self.view.read_u16_at_head(pointer_position) as u32;
+        let pointer = self.view.read_u16_at_head(pointer_position)? as u32;
 
-        ObjectPropertiesView::create(self.object_id, pointer, &self.defaults_view, &self.view)
+        ObjectPropertiesView::create(
+            self.object_id,
+            pointer,
+            &self.defaults_view,
+            &self.view,
+            self.version,
+        )
     }
 
-    pub fn has_attribute(&self, attribute: u16) -> bool {
-        // this will also have to change with the new version
-        // v4 may have up to 48
-        match attribute {
-            i @ 0..=31 => {
-                ObjectView::is_bit_set_in_u32((31 - i) as u8, self.view.read_u32_at_head(0))
-            }
-            _ => panic!("attempt to read an invalid attribute"),
-        }
-    }
+    pub fn has_attribute(&self, attribute: u16) -> InstructionResult<bool> {
+        let (byte, bit) = self.attribute_position(attribute)?;
 
-    pub fn is_bit_set(num: u8, byte: u8) -> bool {
-        (1 << num) & byte != 0
+        Ok(ObjectView::is_bit_set(bit, self.view.read_at_head(byte)?))
     }
 
-    pub fn is_bit_set_in_u16(num: u8, word: u16) -> bool {
-        (1 << (num as u16)) & word != 0
+    // attributes are numbered from the top bit of the first byte down - there
+    // are 32 of them in versions 1-3, and 48 from 4
+    fn attribute_position(&self, attribute: u16) -> InstructionResult<(u32, u8)> {
+        match attribute as u32 {
+            i if i < self.attributes_length * 8 => Ok((i / 8, 7 - (i % 8) as u8)),
+            _ => Err(ErrorKind::InvalidAttribute { attribute }),
+        }
     }
 
-    pub fn is_bit_set_in_u32(num: u8, dword: u32) -> bool {
-        (1 << (num as u32)) & dword != 0
+    pub fn is_bit_set(num: u8, byte: u8) -> bool {
+        (1 << num) & byte != 0
     }
 
-    pub fn set_child(&self, child_id: u16) {
+    pub fn set_child(&self, child_id: u16) -> InstructionResult<()> {
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
         let pointer_position = self.attributes_length +
                                //the order is parent, sibling, child
                                self.related_obj_length * 2;
 
-        self.view.write_at_head(pointer_position, child_id as u8);
+        self.write_relative(pointer_position, child_id)
     }
 
-    pub fn set_parent(&self, parent_id: u16) {
+    pub fn set_parent(&self, parent_id: u16) -> InstructionResult<()> {
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
         let pointer_position = self.attributes_length;
         // the order is parent, sibling, child
         // so parent has no relative offset after attributes
 
-        self.view.write_at_head(pointer_position, parent_id as u8);
+        self.write_relative(pointer_position, parent_id)
     }
 
-    pub fn set_sibling(&self, sibling_id: u16) {
+    pub fn set_sibling(&self, sibling_id: u16) -> InstructionResult<()> {
         // first we start from the beginning of the object table
         // then offset by attribute length + all relatives length
         let pointer_position = self.attributes_length +
@@ -123,30 +140,142 @@ impl ObjectView {
                                self.related_obj_length;
         // so parent has no relative offset after attributes
 
-        self.view.write_at_head(pointer_position, sibling_id as u8);
+        self.write_relative(pointer_position, sibling_id)
+    }
+
+    pub fn set_attribute(&self, attribute: u16) -> InstructionResult<()> {
+        let (byte, bit) = self.attribute_position(attribute)?;
+        let new_attr_mask = self.view.read_at_head(byte)? | (1 << bit);
+        self.view.write_at_head(byte, new_attr_mask)?;
+
+        Ok(())
+    }
+
+    pub fn unset_attribute(&self, attribute: u16) -> InstructionResult<()> {
+        let (byte, bit) = self.attribute_position(attribute)?;
+        let new_attr_mask = self.view.read_at_head(byte)? & !(1 << bit);
+        self.view.write_at_head(byte, new_attr_mask)?;
+
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::interfaces::zinterface::ZInterface;
+    use super::super::error::ErrorKind;
+    use super::super::zmachine::ZMachine;
+
+    struct Silent;
+
+    impl ZInterface for Silent {
+        fn quit(&self) {}
 
-    pub fn set_attribute(&self, attribute: u16) {
-        // this will also have to change with the new version
-        // v4 may have up to 48
-        match attribute {
-            i @ 0..=31 => {
-                let new_attr_mask = self.view.read_u32_at_head(0) | (1 << (31 - i as u32));
-                self.view.write_u32_at_head(0, new_attr_mask);
-            }
-            _ => panic!("attempt to read an invalid attribute"),
+        fn read_next_line(&self, _: &mut String, _: usize) -> Option<usize> {
+            None
         }
+
+        fn print_to_main(&self, _: &str) {}
+    }
+
+    fn write_u16(data: &mut [u8], at: usize, value: u16) {
+        data[at] = (value >> 8) as u8;
+        data[at + 1] = value as u8;
+    }
+
+    // a story with an object table and nothing else - object 1 holds object 2
+    // and has attribute 40, and its properties are 20 ( 3 bytes, so it needs
+    // two size bytes ) and 5 ( a word )
+    fn machine(version: u8) -> ZMachine<Silent> {
+        let mut data = vec![0; 0x800];
+        data[0] = version;
+        write_u16(&mut data, 0x06, 0x400);
+        write_u16(&mut data, 0x0A, 0x100);
+        write_u16(&mut data, 0x0E, 0x300);
+
+        // the defaults come first, 63 of them - the last one is 0xBEEF
+        write_u16(&mut data, 0x100 + 62 * 2, 0xBEEF);
+
+        let first = 0x100 + 63 * 2;
+        let second = first + 14;
+
+        data[first + 5] = 0x80;
+        write_u16(&mut data, first + 6 + 4, 2);
+        write_u16(&mut data, first + 12, 0x200);
+
+        write_u16(&mut data, second + 6, 1);
+        write_u16(&mut data, second + 12, 0x220);
+
+        data[0x200..0x209].copy_from_slice(&[
+            0,
+            0x80 | 20,
+            0x80 | 3,
+            1,
+            2,
+            3,
+            0x40 | 5,
+            0x12,
+            0x34,
+        ]);
+
+        ZMachine::new(data, Silent).unwrap()
     }
 
-    pub fn unset_attribute(&self, attribute: u16) {
-        // this will also have to change with the new version
-        // v4 may have up to 48
-        match attribute {
-            i @ 0..=31 => {
-                let new_attr_mask = self.view.read_u32_at_head(0) & !(1 << (31 - i as u32));
-                self.view.write_u32_at_head(0, new_attr_mask);
-            }
-            _ => panic!("attempt to read an invalid attribute"),
+    #[test]
+    fn version_4_objects_are_bigger() {
+        let machine = machine(5);
+
+        let first = machine.get_object_view(1).unwrap();
+        let second = machine.get_object_view(2).unwrap();
+
+        assert_eq!(first.get_child(), Ok(2));
+        assert_eq!(first.get_parent(), Ok(0));
+        assert_eq!(second.get_parent(), Ok(1));
+        assert_eq!(second.get_sibling(), Ok(0));
+
+        assert!(first.has_attribute(40).unwrap());
+        assert!(!first.has_attribute(39).unwrap());
+        assert!(first.has_attribute(48).is_err());
+
+        // objects past 255 only exist from version 4 ( this one just
+        // doesn't fit in memory )
+        match machine.get_object_view(300) {
+            Err(ErrorKind::MemoryOutOfRange { .. }) => (),
+            _ => panic!("object 300 should be past the end of memory"),
         }
     }
+
+    #[test]
+    fn version_4_properties_have_bigger_sizes() {
+        let machine = machine(5);
+        let properties = machine
+            .get_object_view(1)
+            .unwrap()
+            .get_properties_table_view()
+            .unwrap();
+
+        let long = properties.get_property_info(20).unwrap();
+        assert_eq!(long.size, 3);
+        assert_eq!(properties.get_property_addr(20).unwrap(), 0x203);
+
+        assert_eq!(properties.get_property(5).unwrap().value, 0x1234);
+        assert_eq!(properties.get_property(63).unwrap().value, 0xBEEF);
+        assert!(properties.get_property(64).is_err());
+    }
+
+    #[test]
+    fn property_tables_past_the_end_of_memory_are_errors() {
+        let machine = machine(5);
+        let object = machine.get_object_view(1).unwrap();
+
+        object.view.write_u16_at_head(12, 0x900).unwrap();
+        assert!(object.get_properties_table_view().is_err());
+
+        // no short name, and then a word property with only a byte left
+        object.view.write_u16_at_head(12, 0x7FE).unwrap();
+        object.view.write_at(0x7FF, 0x40 | 5).unwrap();
+
+        let properties = object.get_properties_table_view().unwrap();
+        assert!(properties.get_property_info(5).is_err());
+    }
 }
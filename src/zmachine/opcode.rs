@@ -1,6 +1,7 @@
 // opcode struct
 
 use super::super::interfaces::zinterface::ZInterface;
+use super::error::*;
 use super::global_variables_view::*;
 use super::instruction_set;
 use super::memory_view::*;
@@ -10,10 +11,10 @@ use std::fmt;
 
 // the "form" of the opcode, which dictates how the first byte(s) are read,
 //
-// if short or long, there is one byte, if variable, two ( in later versions
-// of zmachine, this becomes 2-4, hence the name variable, and a new form
-// extended is added, which we do not deal with here yet ( but is somewhat similar to
-// variable )
+// if short or long, there is one byte, if variable, two ( or three, for
+// the two calls that take up to 8 operands ), and from version 5 on there is
+// the extended form, which is 0xbe, followed by the opcode, and then the
+// operand types, just like variable
 
 pub enum OpForm {
     Short,
@@ -27,6 +28,7 @@ pub enum OpForm {
     // lead to collisions, such as between je and storew.
     LongAsVariable,
     Variable,
+    Extended,
 }
 
 impl fmt::Display for OpForm {
@@ -39,6 +41,7 @@ impl fmt::Display for OpForm {
                 &OpForm::Long => "Long",
                 &OpForm::LongAsVariable => "Long as Variable",
                 &OpForm::Variable => "Variable",
+                &OpForm::Extended => "Extended",
             }
         )
     }
@@ -89,16 +92,21 @@ impl Operand {
     // actually bothers to write small constants to the local call frame (instead of on the stack),
     // or bothers implementing a stack in bytes just to accomodate one data type
 
-    pub fn get_value(&self) -> u16 {
+    pub fn get_value(&self) -> InstructionResult<u16> {
         match self {
-            // not 100% if this is a good idea at this point
-            &Operand::Omitted => panic!("tried to get the value of an omitted operand!"),
-            &Operand::SmallConstant { value } => value as u16,
-            &Operand::LargeConstant { value } | &Operand::Variable { value, .. } => value,
+            // this is always a malformed instruction - the opcode said
+            // it had fewer operands than the instruction needs
+            &Operand::Omitted => Err(ErrorKind::OmittedOperand),
+            &Operand::SmallConstant { value } => Ok(value as u16),
+            &Operand::LargeConstant { value } | &Operand::Variable { value, .. } => Ok(value),
         }
     }
 }
 
+// every instruction gets the decoded opcode and the machine, and
+// reports back if anything went wrong
+pub type Instruction<T> = fn(&mut OpCode<T>, &mut ZMachine<T>) -> InstructionResult<()>;
+
 pub struct OpCode<T: ZInterface> {
     // the pointer to the code, not used directly but
     // very helpful for debugging
@@ -109,17 +117,23 @@ pub struct OpCode<T: ZInterface> {
     // the "form" of this opcode, or how it encodes the first byte(s)
     pub form: OpForm,
 
+    // the version of the story - a handful of opcodes mean different
+    // things depending on it ( pop becomes catch, not becomes call_1n )
+    pub version: u8,
+
     // a list of the operands. when initialized, it will
     // be an array of Omitted{}, then the opcode will figure out
     // what it is, change the structs to what is necessary,
     // and then when asked, pull the right information from the buffer
-    pub operands: [Operand; 4],
+    pub operands: [Operand; 8],
 
-    // the # of operands present, 0..3
-    // the operands field is always a 4 byte
+    // the # of operands present, 0..8
+    // the operands field is always an 8 operand
     // array to make things simple ( we don't need
     // dynamic sizing ), this lets us know how many
     // to pull before realizing the rest will be Omitted
+    //
+    // only call_vs2 and call_vn2 can actually use all 8
     pub operand_count: u8, /* the instruction itself
                             * pub instruction: OpType, */
 
@@ -133,14 +147,8 @@ pub struct OpCode<T: ZInterface> {
     // does this code store a value?
     pub store: bool,
 
-    // does this code print something?
-    pub print: bool,
-
-    // does this code pause for input?
-    pub input: bool,
-
     // this is the actual opcode instruction, its hidden behind "execute"
-    instruction: fn(&mut OpCode<T>, &mut ZMachine<T>),
+    instruction: Instruction<T>,
 
     // how many bytes have we read until the instruction is executed
     // ( and the stack pointer potentially changes )?
@@ -170,7 +178,7 @@ pub struct OpCode<T: ZInterface> {
     pub result: u16,
 }
 
-fn return_name<T: ZInterface>(code: &OpCode<T>) -> &str {
+pub fn return_name<T: ZInterface>(code: &OpCode<T>) -> &'static str {
     let name = match (&code.form, code.operand_count, code.code) {
         (&OpForm::Long, _, 0x0) | (&OpForm::LongAsVariable, _, 0x0) => "debug",
         (&OpForm::Long, _, 0x1) | (&OpForm::LongAsVariable, _, 0x1) => "je",
@@ -197,6 +205,10 @@ fn return_name<T: ZInterface>(code: &OpCode<T>) -> &str {
         (&OpForm::Long, _, 0x16) | (&OpForm::LongAsVariable, _, 0x16) => "mul",
         (&OpForm::Long, _, 0x17) | (&OpForm::LongAsVariable, _, 0x17) => "div",
         (&OpForm::Long, _, 0x18) | (&OpForm::LongAsVariable, _, 0x18) => "mod_fn",
+        (&OpForm::Long, _, 0x19) | (&OpForm::LongAsVariable, _, 0x19) => "call_2s",
+        (&OpForm::Long, _, 0x1A) | (&OpForm::LongAsVariable, _, 0x1A) => "call_2n",
+        (&OpForm::Long, _, 0x1B) | (&OpForm::LongAsVariable, _, 0x1B) => "set_colour",
+        (&OpForm::Long, _, 0x1C) | (&OpForm::LongAsVariable, _, 0x1C) => "throw",
         // 1 op
         (&OpForm::Short, 1, 0x0) => "jz",
         (&OpForm::Short, 1, 0x1) => "get_sibling",
@@ -206,34 +218,40 @@ fn return_name<T: ZInterface>(code: &OpCode<T>) -> &str {
         (&OpForm::Short, 1, 0x5) => "inc",
         (&OpForm::Short, 1, 0x6) => "dec",
         (&OpForm::Short, 1, 0x7) => "print_addr",
+        (&OpForm::Short, 1, 0x8) => "call_1s",
         (&OpForm::Short, 1, 0x9) => "remove_obj",
         (&OpForm::Short, 1, 0xA) => "print_obj",
         (&OpForm::Short, 1, 0xB) => "ret",
         (&OpForm::Short, 1, 0xC) => "jump",
         (&OpForm::Short, 1, 0xD) => "print_paddr",
         (&OpForm::Short, 1, 0xE) => "load",
+        (&OpForm::Short, 1, 0xF) if code.version < 5 => "not",
+        (&OpForm::Short, 1, 0xF) => "call_1n",
         // 0 op
         (&OpForm::Short, 0, 0x0) => "rtrue",
         (&OpForm::Short, 0, 0x1) => "rfalse",
         (&OpForm::Short, 0, 0x2) => "print",
         (&OpForm::Short, 0, 0x3) => "print_ret",
         (&OpForm::Short, 0, 0x4) => "nop",
-        // these next two calls are illegal after version 5
-        (&OpForm::Short, 0, 0x5) => "save",
-        (&OpForm::Short, 0, 0x6) => "restore",
+        // these next two calls are illegal from version 5 ( they move to extended )
+        (&OpForm::Short, 0, 0x5) if code.version < 5 => "save",
+        (&OpForm::Short, 0, 0x6) if code.version < 5 => "restore",
         // still legal
         (&OpForm::Short, 0, 0x7) => "restart",
         (&OpForm::Short, 0, 0x8) => "ret_popped",
-        (&OpForm::Short, 0, 0x9) => "pop",
+        (&OpForm::Short, 0, 0x9) if code.version < 5 => "pop",
+        (&OpForm::Short, 0, 0x9) => "catch",
         (&OpForm::Short, 0, 0xA) => "quit",
         (&OpForm::Short, 0, 0xB) => "new_line",
         (&OpForm::Short, 0, 0xC) => "show_status",
         (&OpForm::Short, 0, 0xD) => "verify",
+        (&OpForm::Short, 0, 0xF) => "piracy",
         // variable op codes
         (&OpForm::Variable, _, 0x0) => "call",
         (&OpForm::Variable, _, 0x1) => "storew",
         (&OpForm::Variable, _, 0x2) => "storeb",
         (&OpForm::Variable, _, 0x3) => "put_prop",
+        (&OpForm::Variable, _, 0x4) if code.version >= 5 => "aread",
         (&OpForm::Variable, _, 0x4) => "sread",
         (&OpForm::Variable, _, 0x5) => "print_char",
         (&OpForm::Variable, _, 0x6) => "print_num",
@@ -242,9 +260,55 @@ fn return_name<T: ZInterface>(code: &OpCode<T>) -> &str {
         (&OpForm::Variable, _, 0x9) => "pull",
         (&OpForm::Variable, _, 0xA) => "split_window",
         (&OpForm::Variable, _, 0xB) => "set_window",
+        (&OpForm::Variable, _, 0xC) => "call_vs2",
+        (&OpForm::Variable, _, 0xD) => "erase_window",
+        (&OpForm::Variable, _, 0xE) => "erase_line",
+        (&OpForm::Variable, _, 0xF) => "set_cursor",
+        (&OpForm::Variable, _, 0x10) => "get_cursor",
+        (&OpForm::Variable, _, 0x11) => "set_text_style",
+        (&OpForm::Variable, _, 0x12) => "buffer_mode",
         (&OpForm::Variable, _, 0x13) => "output_stream",
         (&OpForm::Variable, _, 0x14) => "input_stream",
         (&OpForm::Variable, _, 0x15) => "sound_effect",
+        (&OpForm::Variable, _, 0x16) => "read_char",
+        (&OpForm::Variable, _, 0x17) => "scan_table",
+        (&OpForm::Variable, _, 0x18) => "not",
+        (&OpForm::Variable, _, 0x19) => "call_vn",
+        (&OpForm::Variable, _, 0x1A) => "call_vn2",
+        (&OpForm::Variable, _, 0x1B) => "tokenise",
+        (&OpForm::Variable, _, 0x1C) => "encode_text",
+        (&OpForm::Variable, _, 0x1D) => "copy_table",
+        (&OpForm::Variable, _, 0x1E) => "print_table",
+        (&OpForm::Variable, _, 0x1F) => "check_arg_count",
+        // extended op codes
+        (&OpForm::Extended, _, 0x0) => "save",
+        (&OpForm::Extended, _, 0x1) => "restore",
+        (&OpForm::Extended, _, 0x2) => "log_shift",
+        (&OpForm::Extended, _, 0x3) => "art_shift",
+        (&OpForm::Extended, _, 0x4) => "set_font",
+        (&OpForm::Extended, _, 0x5) => "draw_picture",
+        (&OpForm::Extended, _, 0x6) => "picture_data",
+        (&OpForm::Extended, _, 0x7) => "erase_picture",
+        (&OpForm::Extended, _, 0x8) => "set_margins",
+        (&OpForm::Extended, _, 0x9) => "save_undo",
+        (&OpForm::Extended, _, 0xA) => "restore_undo",
+        (&OpForm::Extended, _, 0xB) => "print_unicode",
+        (&OpForm::Extended, _, 0xC) => "check_unicode",
+        (&OpForm::Extended, _, 0xD) => "set_true_colour",
+        (&OpForm::Extended, _, 0x10) => "move_window",
+        (&OpForm::Extended, _, 0x11) => "window_size",
+        (&OpForm::Extended, _, 0x12) => "window_style",
+        (&OpForm::Extended, _, 0x13) => "get_wind_prop",
+        (&OpForm::Extended, _, 0x14) => "scroll_window",
+        (&OpForm::Extended, _, 0x15) => "pop_stack",
+        (&OpForm::Extended, _, 0x16) => "read_mouse",
+        (&OpForm::Extended, _, 0x17) => "mouse_window",
+        (&OpForm::Extended, _, 0x18) => "push_stack",
+        (&OpForm::Extended, _, 0x19) => "put_wind_prop",
+        (&OpForm::Extended, _, 0x1A) => "print_form",
+        (&OpForm::Extended, _, 0x1B) => "make_menu",
+        (&OpForm::Extended, _, 0x1C) => "picture_table",
+        (&OpForm::Extended, _, 0x1D) => "buffer_screen",
         _ => "illegal_operation",
     };
 
@@ -252,14 +316,14 @@ fn return_name<T: ZInterface>(code: &OpCode<T>) -> &str {
 }
 
 impl<T: ZInterface> OpCode<T> {
-    pub fn assign_instruction(code: &mut OpCode<T>) {
+    pub fn assign_instruction(code: &mut OpCode<T>) -> InstructionResult<()> {
         // the form is an object that does not copy, so we need a reference
         // to it
         // behold, the match to rule them all
         //
         // not sure if this is better nested, or split apart
         // at all
-        let instruction = match (&code.form, code.operand_count, code.code) {
+        let instruction: Instruction<T> = match (&code.form, code.operand_count, code.code) {
             // 2 op - long
             (&OpForm::Long, _, 0x0) | (&OpForm::LongAsVariable, _, 0x0) => instruction_set::debug,
             (&OpForm::Long, _, 0x1) | (&OpForm::LongAsVariable, _, 0x1) => instruction_set::je,
@@ -302,6 +366,14 @@ impl<T: ZInterface> OpCode<T> {
             (&OpForm::Long, _, 0x18) | (&OpForm::LongAsVariable, _, 0x18) => {
                 instruction_set::mod_fn
             }
+            (&OpForm::Long, _, 0x19) | (&OpForm::LongAsVariable, _, 0x19) => instruction_set::call,
+            (&OpForm::Long, _, 0x1A) | (&OpForm::LongAsVariable, _, 0x1A) => {
+                instruction_set::call_vn
+            }
+            (&OpForm::Long, _, 0x1B) | (&OpForm::LongAsVariable, _, 0x1B) => {
+                instruction_set::set_colour
+            }
+            (&OpForm::Long, _, 0x1C) | (&OpForm::LongAsVariable, _, 0x1C) => instruction_set::throw,
             // 1 op
             (&OpForm::Short, 1, 0x0) => instruction_set::jz,
             (&OpForm::Short, 1, 0x1) => instruction_set::get_sibling,
@@ -311,29 +383,34 @@ impl<T: ZInterface> OpCode<T> {
             (&OpForm::Short, 1, 0x5) => instruction_set::inc,
             (&OpForm::Short, 1, 0x6) => instruction_set::dec,
             (&OpForm::Short, 1, 0x7) => instruction_set::print_addr,
+            (&OpForm::Short, 1, 0x8) => instruction_set::call,
             (&OpForm::Short, 1, 0x9) => instruction_set::remove_obj,
             (&OpForm::Short, 1, 0xA) => instruction_set::print_obj,
             (&OpForm::Short, 1, 0xB) => instruction_set::ret,
             (&OpForm::Short, 1, 0xC) => instruction_set::jump,
             (&OpForm::Short, 1, 0xD) => instruction_set::print_paddr,
             (&OpForm::Short, 1, 0xE) => instruction_set::load,
+            (&OpForm::Short, 1, 0xF) if code.version < 5 => instruction_set::not,
+            (&OpForm::Short, 1, 0xF) => instruction_set::call_vn,
             // 0 op
             (&OpForm::Short, 0, 0x0) => instruction_set::rtrue,
             (&OpForm::Short, 0, 0x1) => instruction_set::rfalse,
             (&OpForm::Short, 0, 0x2) => instruction_set::print,
             (&OpForm::Short, 0, 0x3) => instruction_set::print_ret,
             (&OpForm::Short, 0, 0x4) => instruction_set::nop,
-            // these next two calls are illegal after version 5
-            (&OpForm::Short, 0, 0x5) => instruction_set::save,
-            (&OpForm::Short, 0, 0x6) => instruction_set::restore,
+            // these next two calls are illegal from version 5 ( they move to extended )
+            (&OpForm::Short, 0, 0x5) if code.version < 5 => instruction_set::save,
+            (&OpForm::Short, 0, 0x6) if code.version < 5 => instruction_set::restore,
             // still legal
             (&OpForm::Short, 0, 0x7) => instruction_set::restart,
             (&OpForm::Short, 0, 0x8) => instruction_set::ret_popped,
-            (&OpForm::Short, 0, 0x9) => instruction_set::pop,
+            (&OpForm::Short, 0, 0x9) if code.version < 5 => instruction_set::pop,
+            (&OpForm::Short, 0, 0x9) => instruction_set::catch,
             (&OpForm::Short, 0, 0xA) => instruction_set::quit,
             (&OpForm::Short, 0, 0xB) => instruction_set::new_line,
             (&OpForm::Short, 0, 0xC) => instruction_set::show_status,
             (&OpForm::Short, 0, 0xD) => instruction_set::verify,
+            (&OpForm::Short, 0, 0xF) => instruction_set::piracy,
             // variable op codes
             (&OpForm::Variable, _, 0x0) => instruction_set::call,
             (&OpForm::Variable, _, 0x1) => instruction_set::storew,
@@ -347,38 +424,92 @@ impl<T: ZInterface> OpCode<T> {
             (&OpForm::Variable, _, 0x9) => instruction_set::pull,
             (&OpForm::Variable, _, 0xA) => instruction_set::split_window,
             (&OpForm::Variable, _, 0xB) => instruction_set::set_window,
+            (&OpForm::Variable, _, 0xC) => instruction_set::call,
+            (&OpForm::Variable, _, 0xD) => instruction_set::erase_window,
+            (&OpForm::Variable, _, 0xE) => instruction_set::erase_line,
+            (&OpForm::Variable, _, 0xF) => instruction_set::set_cursor,
+            (&OpForm::Variable, _, 0x10) => instruction_set::get_cursor,
+            (&OpForm::Variable, _, 0x11) => instruction_set::set_text_style,
+            (&OpForm::Variable, _, 0x12) => instruction_set::buffer_mode,
             (&OpForm::Variable, _, 0x13) => instruction_set::output_stream,
             (&OpForm::Variable, _, 0x14) => instruction_set::input_stream,
             (&OpForm::Variable, _, 0x15) => instruction_set::sound_effect,
+            (&OpForm::Variable, _, 0x16) => instruction_set::read_char,
+            (&OpForm::Variable, _, 0x17) => instruction_set::scan_table,
+            (&OpForm::Variable, _, 0x18) => instruction_set::not,
+            (&OpForm::Variable, _, 0x19) => instruction_set::call_vn,
+            (&OpForm::Variable, _, 0x1A) => instruction_set::call_vn,
+            (&OpForm::Variable, _, 0x1B) => instruction_set::tokenise,
+            (&OpForm::Variable, _, 0x1C) => instruction_set::encode_text,
+            (&OpForm::Variable, _, 0x1D) => instruction_set::copy_table,
+            (&OpForm::Variable, _, 0x1E) => instruction_set::print_table,
+            (&OpForm::Variable, _, 0x1F) => instruction_set::check_arg_count,
+            // extended op codes
+            //
+            // 0x5 - 0x8 and 0x10 - 0x1d are all version 6 ( pictures, windows,
+            // menus ), see graphics.rs
+            (&OpForm::Extended, _, 0x0) => instruction_set::save,
+            (&OpForm::Extended, _, 0x1) => instruction_set::restore,
+            (&OpForm::Extended, _, 0x2) => instruction_set::log_shift,
+            (&OpForm::Extended, _, 0x3) => instruction_set::art_shift,
+            (&OpForm::Extended, _, 0x4) => instruction_set::set_font,
+            (&OpForm::Extended, _, 0x5) => instruction_set::draw_picture,
+            (&OpForm::Extended, _, 0x6) => instruction_set::picture_data,
+            (&OpForm::Extended, _, 0x7) => instruction_set::erase_picture,
+            (&OpForm::Extended, _, 0x8) => instruction_set::set_margins,
+            (&OpForm::Extended, _, 0x9) => instruction_set::save_undo,
+            (&OpForm::Extended, _, 0xA) => instruction_set::restore_undo,
+            (&OpForm::Extended, _, 0xB) => instruction_set::print_unicode,
+            (&OpForm::Extended, _, 0xC) => instruction_set::check_unicode,
+            (&OpForm::Extended, _, 0xD) => instruction_set::set_true_colour,
+            (&OpForm::Extended, _, 0x10) => instruction_set::move_window,
+            (&OpForm::Extended, _, 0x11) => instruction_set::window_size,
+            (&OpForm::Extended, _, 0x12) => instruction_set::window_style,
+            (&OpForm::Extended, _, 0x13) => instruction_set::get_wind_prop,
+            (&OpForm::Extended, _, 0x14) => instruction_set::scroll_window,
+            (&OpForm::Extended, _, 0x15) => instruction_set::pop_stack,
+            (&OpForm::Extended, _, 0x16) => instruction_set::read_mouse,
+            (&OpForm::Extended, _, 0x17) => instruction_set::mouse_window,
+            (&OpForm::Extended, _, 0x18) => instruction_set::push_stack,
+            (&OpForm::Extended, _, 0x19) => instruction_set::put_wind_prop,
+            (&OpForm::Extended, _, 0x1A) => instruction_set::print_form,
+            (&OpForm::Extended, _, 0x1B) => instruction_set::make_menu,
+            (&OpForm::Extended, _, 0x1C) => instruction_set::picture_table,
+            (&OpForm::Extended, _, 0x1D) => instruction_set::buffer_screen,
             // end
-            err @ _ => panic!(
-                "Instruction not found!: form: {}, num_ops: {}, op_code: {}\n IP: {:x}",
-                err.0, err.1, err.2, code.ip
-            ),
+            (form, _, op_code) => {
+                return Err(ErrorKind::IllegalInstruction {
+                    form: format!("{}", form),
+                    code: op_code,
+                })
+            }
         };
 
         //warn now, since this is a valid instruction
         //warn!( "IP: {:x}", code.ip );
         code.instruction = instruction;
+        Ok(())
     }
 
-    pub fn execute(&mut self, env: &mut ZMachine<T>) {
-        (self.instruction)(self, env);
+    pub fn execute(&mut self, env: &mut ZMachine<T>) -> InstructionResult<()> {
+        (self.instruction)(self, env)
     }
 
     // opcode can be several bytes long, but in the
     // form section we always allow the function to peek
-    // at the top two bytes of the program stack
+    // at the top three bytes of the program stack
     //
-    // sometimes variable will need the second one,
+    // sometimes variable will need the second one ( and the double
+    // variable calls and extended codes need the third ),
     // and we trust the opcode itself, since the length is variable,
     // to move the pc
 
-    pub fn form_opcode(word: [u8; 2]) -> OpCode<T> {
+    pub fn form_opcode(word: [u8; 3], version: u8) -> OpCode<T> {
         // set some defaults and do stuff we will have to do anyway,
         // like filling out the operands table
 
         let mut op_code: OpCode<T> = OpCode::form_base_opcode();
+        op_code.version = version;
 
         // make a closure here to let rust know when we want to drop
         // the mutable reference
@@ -396,10 +527,11 @@ impl<T: ZInterface> OpCode<T> {
             match word[0] {
                 // here, id is matched as the first byte, so we can access the opcode
                 0x00..=0x7f => OpCode::form_long_opcode(code_ref, word[0]),
-                // the fallthrough for be , the code for extended opcodes,
-                // falls through in form_short_opcode
+                // be is the code for extended opcodes, it would otherwise
+                // be a 0 op short code ( which never existed )
+                0xbe => OpCode::form_extended_opcode(code_ref, word[1], word[2]),
                 0x80..=0xbf => OpCode::form_short_opcode(code_ref, word[0]),
-                0xc0..=0xff => OpCode::form_variable_opcode(code_ref, word[0], word[1]),
+                0xc0..=0xff => OpCode::form_variable_opcode(code_ref, word),
             }
         }
 
@@ -409,8 +541,8 @@ impl<T: ZInterface> OpCode<T> {
     fn form_base_opcode() -> OpCode<T> {
         // almost all of these values will be set/determined
         // by the instruction, either in "form_opcode", "assign_instruction",
-        // or by the actual instruction code itself ( in the case of branch,
-        // store, input and print )
+        // or by the actual instruction code itself ( in the case of branch
+        // and store )
         //
         // it might be worth it to make one or two more structs here
         // to show that responsiblity, but right now this is fairly simple
@@ -418,10 +550,9 @@ impl<T: ZInterface> OpCode<T> {
         OpCode {
             ip: 0,
             code: 0,
+            version: 0,
             branch: false,
             store: false,
-            print: false,
-            input: false,
             instruction: OpCode::null_instruction,
             form: OpForm::Short,
             operands: [
@@ -429,6 +560,10 @@ impl<T: ZInterface> OpCode<T> {
                 Operand::Omitted {},
                 Operand::Omitted {},
                 Operand::Omitted {},
+                Operand::Omitted {},
+                Operand::Omitted {},
+                Operand::Omitted {},
+                Operand::Omitted {},
             ],
             operand_count: 0,
             read_bytes: 0,
@@ -437,7 +572,9 @@ impl<T: ZInterface> OpCode<T> {
     }
 
     //placeholder, does nothing
-    pub fn null_instruction(_: &mut OpCode<T>, _: &mut ZMachine<T>) {}
+    pub fn null_instruction(_: &mut OpCode<T>, _: &mut ZMachine<T>) -> InstructionResult<()> {
+        Ok(())
+    }
 
     // there are cases where we need to "return true" or "return false" after
     // branch operations - basically, we need to run two opcodes at a time
@@ -542,8 +679,8 @@ impl<T: ZInterface> OpCode<T> {
                     address: 0,
                 };
             }
-            0xbe => panic!("Extended opcodes not supported!"),
-            0xb0..=0xbd | 0xbf => {
+            // 0xbe never gets here, it's the extended form
+            0xb0..=0xbf => {
                 code.operand_count = 0;
             }
             // this should not be reachable
@@ -551,7 +688,9 @@ impl<T: ZInterface> OpCode<T> {
         }
     }
 
-    fn form_variable_opcode(code: &mut OpCode<T>, id: u8, second_byte: u8) {
+    fn form_variable_opcode(code: &mut OpCode<T>, word: [u8; 3]) {
+        let id = word[0];
+
         // we read the first 2 here, as indicated by second byte above
         code.read_bytes = 2;
 
@@ -576,8 +715,32 @@ impl<T: ZInterface> OpCode<T> {
             _ => unreachable!(),
         }
 
-        for i in 0..code.operands.len() {
-            let t = (second_byte >> (6 - (i * 2))) & 0b11;
+        match id {
+            // call_vs2 and call_vn2 can take up to 8 operands, so they
+            // have a second byte of operand types
+            0xec | 0xfa => {
+                code.read_bytes = 3;
+                OpCode::form_operand_types(code, &word[1..3]);
+            }
+            _ => OpCode::form_operand_types(code, &word[1..2]),
+        }
+    }
+
+    // extended codes are 0xbe, the actual opcode, and then a byte of operand
+    // types, exactly like variable codes
+    fn form_extended_opcode(code: &mut OpCode<T>, id: u8, types: u8) {
+        code.read_bytes = 3;
+        code.form = OpForm::Extended;
+        code.code = id;
+
+        OpCode::form_operand_types(code, &[types]);
+    }
+
+    // each type byte holds 4 operand types, 2 bits each, starting from
+    // the top - the first omitted type means the rest are omitted too
+    fn form_operand_types(code: &mut OpCode<T>, type_bytes: &[u8]) {
+        for i in 0..type_bytes.len() * 4 {
+            let t = (type_bytes[i / 4] >> (6 - ((i % 4) * 2))) & 0b11;
 
             if t == 0b11 {
                 break;
@@ -593,44 +756,39 @@ impl<T: ZInterface> OpCode<T> {
         frame_view: MemoryView,
         globals: GlobalVariablesView,
         call_stack: &mut Stack,
-    ) {
+    ) -> InstructionResult<()> {
         if self.operand_count == 0 {
-            return;
+            return Ok(());
         }
 
         for i in 0..self.operand_count {
             let op = &mut self.operands[i as usize];
             match op {
                 &mut Operand::LargeConstant { ref mut value } => {
-                    *value = frame_view.read_u16_at_head(self.read_bytes);
+                    *value = frame_view.read_u16_at_head(self.read_bytes)?;
                     self.read_bytes += 2;
                 }
                 &mut Operand::SmallConstant { ref mut value } => {
-                    *value = frame_view.read_at_head(self.read_bytes);
+                    *value = frame_view.read_at_head(self.read_bytes)?;
                     self.read_bytes += 1;
                 }
                 &mut Operand::Variable {
                     ref mut value,
                     ref mut address,
                 } => {
-                    let addr = frame_view.read_at_head(self.read_bytes);
+                    let addr = frame_view.read_at_head(self.read_bytes)?;
                     *address = addr;
 
                     match addr {
                         // 0, its the stack, pop it and return
-                        0 => {
-                            *value = match call_stack.stack.pop() {
-                                Some(x) => x,
-                                None => panic!("stack underflow!"),
-                            }
-                        }
+                        0 => *value = call_stack.pop()?,
                         // 1 to 15, its a local
-                        i @ 0x01..=0x0f => *value = call_stack.get_local_variable(i),
+                        i @ 0x01..=0x0f => *value = call_stack.get_local_variable(i)?,
                         // 16 to 255, it's a global variable.
                         global @ 0x10..=0xff => {
                             // offset by 16 to get the global "index"
                             let index = global - 0x10;
-                            *value = globals.read_global(index as u16)
+                            *value = globals.read_global(index as u16)?
                         }
                     }
 
@@ -639,6 +797,8 @@ impl<T: ZInterface> OpCode<T> {
                 &mut Operand::Omitted => break,
             };
         }
+
+        Ok(())
     }
 }
 
@@ -666,11 +826,11 @@ impl<T: ZInterface> fmt::Display for OpCode<T> {
                self.ip,
                self.form,
                return_name(&self),
-               format!("0: {},\n1: {},\n2: {},\n3: {}\n",
-                       self.operands[0],
-                       self.operands[1],
-                       self.operands[2],
-                       self.operands[3]),
+               self.operands
+                   .iter()
+                   .enumerate()
+                   .map(|(i, operand)| format!("{}: {}\n", i, operand))
+                   .collect::<String>(),
                self.operand_count,
                self.branch,
                self.result)
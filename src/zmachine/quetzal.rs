@@ -0,0 +1,431 @@
+use super::iff::*;
+use super::zmachine::{CallFrame, FrameInfo};
+
+use std::cmp;
+
+// quetzal is the "standard" save file format for the zmachine,
+// so saves made here can be picked up by frotz ( and everyone else ),
+// and vice versa
+//
+// http://inform-fiction.org/zmachine/standards/quetzal/index.html
+//
+// a save has three chunks we care about:
+//
+// IFhd: which story this save is for, and the pc to resume from
+// CMem: dynamic memory, xor'd against the original story and run length
+//       encoded ( or UMem, which is just the raw dynamic memory )
+// Stks: every call frame on the stack
+//
+// anything else ( annotations, authors, etc ) is skipped on restore
+
+// the bits of the header that identify a story file - a save
+// can only be restored to the story ( and release ) it was made from
+#[derive(PartialEq)]
+pub struct StoryId {
+    pub release: u16,
+    pub serial: [u8; 6],
+    pub checksum: u16,
+}
+
+impl StoryId {
+    pub fn from_memory(memory: &[u8]) -> StoryId {
+        let mut serial = [0; 6];
+        serial.copy_from_slice(&memory[0x12..0x18]);
+
+        StoryId {
+            release: read_u16(memory, 0x2),
+            serial,
+            checksum: read_u16(memory, 0x1C),
+        }
+    }
+}
+
+pub struct QuetzalState {
+    // for v1-3 this is the address of the branch data of the save
+    // instruction, for v4+ it is the address of its store byte
+    pub pc: u32,
+    pub dynamic_memory: Vec<u8>,
+    pub frames: Vec<CallFrame>,
+}
+
+// memory is the current state of memory, original is the story as it
+// was loaded - the first dynamic_length bytes of each are what get saved
+pub fn write_save(
+    memory: &[u8],
+    original: &[u8],
+    dynamic_length: usize,
+    frames: &[CallFrame],
+    pc: u32,
+) -> Vec<u8> {
+    let mut form = FormWriter::new(b"IFZS");
+
+    let id = StoryId::from_memory(original);
+    let mut header = Vec::with_capacity(13);
+    push_u16(&mut header, id.release);
+    header.extend_from_slice(&id.serial);
+    push_u16(&mut header, id.checksum);
+    push_u24(&mut header, pc);
+
+    form.write_chunk(b"IFhd", &header);
+    form.write_chunk(
+        b"CMem",
+        &compress_memory(&memory[..dynamic_length], &original[..dynamic_length]),
+    );
+    form.write_chunk(b"Stks", &write_frames(memory, frames));
+
+    form.finish()
+}
+
+pub fn read_save(
+    data: &[u8],
+    original: &[u8],
+    dynamic_length: usize,
+) -> Result<QuetzalState, String> {
+    let form = FormReader::read(data)?;
+
+    if &form.form_type != b"IFZS" {
+        return Err("not a quetzal save file".to_string());
+    }
+
+    let header = match form.find(b"IFhd") {
+        Some(chunk) if chunk.data.len() >= 13 => chunk.data,
+        _ => return Err("save file has no header".to_string()),
+    };
+
+    let mut serial = [0; 6];
+    serial.copy_from_slice(&header[2..8]);
+
+    let saved_id = StoryId {
+        release: read_u16(header, 0),
+        serial,
+        checksum: read_u16(header, 8),
+    };
+
+    if saved_id != StoryId::from_memory(original) {
+        return Err("save file is for a different story".to_string());
+    }
+
+    let dynamic_memory = match (form.find(b"CMem"), form.find(b"UMem")) {
+        (Some(chunk), _) => decompress_memory(chunk.data, &original[..dynamic_length])?,
+        (None, Some(chunk)) => {
+            if chunk.data.len() != dynamic_length {
+                return Err("save file memory is the wrong size".to_string());
+            }
+
+            chunk.data.to_vec()
+        }
+        _ => return Err("save file has no memory".to_string()),
+    };
+
+    let frames = match form.find(b"Stks") {
+        Some(chunk) => read_frames(chunk.data)?,
+        None => return Err("save file has no stack".to_string()),
+    };
+
+    Ok(QuetzalState {
+        pc: read_u24(header, 10),
+        dynamic_memory,
+        frames,
+    })
+}
+
+// xor the current memory against the original, then collapse runs
+// of zeroes ( unchanged bytes ) into a 0 followed by the run length - 1.
+// a trailing run of zeroes is just left off
+fn compress_memory(memory: &[u8], original: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut zeroes = 0;
+
+    for (current, start) in memory.iter().zip(original.iter()) {
+        let byte = current ^ start;
+
+        if byte == 0 {
+            zeroes += 1;
+            continue;
+        }
+
+        // a run can only count up to 256, so longer ones are split up - they
+        // are only written once we know they aren't the trailing run
+        while zeroes > 0 {
+            let run = cmp::min(zeroes, 256);
+            out.push(0);
+            out.push((run - 1) as u8);
+            zeroes -= run;
+        }
+
+        out.push(byte);
+    }
+
+    out
+}
+
+fn decompress_memory(data: &[u8], original: &[u8]) -> Result<Vec<u8>, String> {
+    let mut memory = original.to_vec();
+    let mut cursor = 0;
+    let mut i = 0;
+
+    while i < data.len() {
+        let byte = data[i];
+        i += 1;
+
+        if byte == 0 {
+            if i == data.len() {
+                return Err("save file memory ends in the middle of a run".to_string());
+            }
+
+            cursor += data[i] as usize + 1;
+            i += 1;
+            continue;
+        }
+
+        if cursor >= memory.len() {
+            return Err("save file memory is larger than dynamic memory".to_string());
+        }
+
+        memory[cursor] ^= byte;
+        cursor += 1;
+    }
+
+    if cursor > memory.len() {
+        return Err("save file memory is larger than dynamic memory".to_string());
+    }
+
+    Ok(memory)
+}
+
+// each frame is written as:
+//
+// 3 bytes  return pc
+// 1 byte   flags ( 0b000pvvvv, p is set if the result is discarded, v is the # of locals )
+// 1 byte   the variable the result is stored in
+// 1 byte   the arguments supplied
+// 2 bytes  the # of words on the evaluation stack
+// ...      the locals, then the evaluation stack, as words
+//
+// the first frame is a dummy frame for the main routine ( which has no
+// locals and never returns ), so it is all zeroes besides its stack
+fn write_frames(memory: &[u8], frames: &[CallFrame]) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for (i, frame) in frames.iter().enumerate() {
+        let (return_pc, variable) = match i {
+            0 => (0, 0),
+            _ => {
+                // we keep the address of the call instruction, and
+                // the offset to its store byte, but quetzal wants the
+                // address just after it ( and the store byte separately )
+                let store_address = frame.return_address + frame.return_offset as u32;
+
+                match frame.info.discard_result {
+                    true => (store_address, 0),
+                    false => (store_address + 1, memory[store_address as usize]),
+                }
+            }
+        };
+
+        let flags = frame.info.to_word() as u8;
+
+        push_u24(&mut out, return_pc);
+        out.push(flags);
+        out.push(variable);
+        out.push(frame.info.args_supplied);
+        push_u16(&mut out, frame.evaluation_stack.len() as u16);
+
+        for word in frame.locals.iter().chain(frame.evaluation_stack.iter()) {
+            push_u16(&mut out, *word);
+        }
+    }
+
+    out
+}
+
+fn read_frames(data: &[u8]) -> Result<Vec<CallFrame>, String> {
+    let mut frames = Vec::new();
+    let mut cursor = 0;
+
+    while cursor < data.len() {
+        if cursor + 8 > data.len() {
+            return Err("save file stack is truncated".to_string());
+        }
+
+        let return_pc = read_u24(data, cursor);
+        let flags = data[cursor + 3];
+        let args_supplied = data[cursor + 5];
+        let stack_length = read_u16(data, cursor + 6) as usize;
+
+        let info = FrameInfo {
+            locals: flags & 0x0f,
+            args_supplied,
+            discard_result: flags & 0x10 != 0,
+        };
+
+        cursor += 8;
+
+        let words = info.locals as usize + stack_length;
+
+        if cursor + words * 2 > data.len() {
+            return Err("save file stack is truncated".to_string());
+        }
+
+        let mut values: Vec<u16> = (0..words).map(|i| read_u16(data, cursor + i * 2)).collect();
+        let evaluation_stack = values.split_off(info.locals as usize);
+
+        cursor += words * 2;
+
+        // the inverse of write_frames - we point back at the store byte,
+        // and let ret read the variable out of the instruction itself
+        let return_address = match (frames.len(), info.discard_result) {
+            (0, _) => 0,
+            (_, true) => return_pc,
+            (_, false) if return_pc > 0 => return_pc - 1,
+            _ => return Err("save file has a frame with no return address".to_string()),
+        };
+
+        frames.push(CallFrame {
+            return_address,
+            return_offset: 0,
+            info,
+            locals: values,
+            evaluation_stack,
+        });
+    }
+
+    if frames.len() == 0 {
+        return Err("save file stack is empty".to_string());
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(return_address: u32, info: FrameInfo, locals: Vec<u16>, stack: Vec<u16>) -> CallFrame {
+        CallFrame {
+            return_address: return_address,
+            return_offset: 0,
+            info: info,
+            locals: locals,
+            evaluation_stack: stack,
+        }
+    }
+
+    #[test]
+    fn memory_round_trips() {
+        let original: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let mut memory = original.clone();
+        memory[3] ^= 0x40;
+        memory[4] ^= 0x01;
+        memory[900] = 0;
+
+        let compressed = compress_memory(&memory, &original);
+        assert_eq!(decompress_memory(&compressed, &original).unwrap(), memory);
+    }
+
+    #[test]
+    fn long_runs_are_split() {
+        let original = vec![7; 700];
+        let mut memory = original.clone();
+        memory[600] = 8;
+
+        // 600 unchanged bytes is runs of 256, 256 and 88, then the change -
+        // and the unchanged bytes after it are left off
+        let compressed = compress_memory(&memory, &original);
+        assert_eq!(compressed, vec![0, 255, 0, 255, 0, 87, 7 ^ 8]);
+        assert_eq!(decompress_memory(&compressed, &original).unwrap(), memory);
+    }
+
+    #[test]
+    fn unchanged_memory_is_empty() {
+        let original = vec![1; 300];
+
+        assert!(compress_memory(&original, &original).is_empty());
+        assert_eq!(decompress_memory(&[], &original).unwrap(), original);
+    }
+
+    #[test]
+    fn runs_past_the_end_are_errors() {
+        let original = vec![0; 10];
+
+        assert!(decompress_memory(&[0], &original).is_err());
+        assert!(decompress_memory(&[0, 20, 1], &original).is_err());
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        // the store byte of the call at 0x20 is at 0x23, and says variable 0x10
+        let mut memory = vec![0; 0x40];
+        memory[0x23] = 0x10;
+
+        let frames = vec![
+            frame(0, FrameInfo::from_word(0), vec![], vec![1, 2]),
+            CallFrame {
+                return_address: 0x20,
+                return_offset: 3,
+                info: FrameInfo {
+                    locals: 3,
+                    args_supplied: 0b011,
+                    discard_result: false,
+                },
+                locals: vec![0xAAAA, 0xBBBB, 0xCCCC],
+                evaluation_stack: vec![0x1234],
+            },
+            frame(
+                0x30,
+                FrameInfo {
+                    locals: 1,
+                    args_supplied: 0b1,
+                    discard_result: true,
+                },
+                vec![5],
+                vec![],
+            ),
+        ];
+
+        let data = write_frames(&memory, &frames);
+
+        // the second frame: return pc just after the store byte, flags with
+        // 3 locals, the store variable, the arguments, and one word of stack
+        assert_eq!(
+            &data[12..20],
+            &[0x00, 0x00, 0x24, 0x03, 0x10, 0b011, 0x00, 0x01]
+        );
+
+        // the third throws its result away, so its return pc is the next
+        // instruction and the flags have the discard bit
+        assert_eq!(
+            &data[28..36],
+            &[0x00, 0x00, 0x30, 0x11, 0x00, 0b1, 0x00, 0x00]
+        );
+
+        let read = read_frames(&data).unwrap();
+        assert_eq!(read.len(), 3);
+
+        assert_eq!(read[0].return_address, 0);
+        assert_eq!(read[0].evaluation_stack, vec![1, 2]);
+
+        // it comes back pointing at the store byte itself
+        assert_eq!(read[1].return_address + read[1].return_offset as u32, 0x23);
+        assert_eq!(read[1].info.locals, 3);
+        assert_eq!(read[1].info.args_supplied, 0b011);
+        assert!(!read[1].info.discard_result);
+        assert_eq!(read[1].locals, vec![0xAAAA, 0xBBBB, 0xCCCC]);
+        assert_eq!(read[1].evaluation_stack, vec![0x1234]);
+
+        assert_eq!(read[2].return_address, 0x30);
+        assert_eq!(read[2].info.args_supplied, 0b1);
+        assert!(read[2].info.discard_result);
+        assert_eq!(read[2].locals, vec![5]);
+        assert!(read[2].evaluation_stack.is_empty());
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        let frames = vec![frame(0, FrameInfo::from_word(0), vec![], vec![1, 2])];
+        let data = write_frames(&[], &frames);
+
+        assert!(read_frames(&data[..data.len() - 1]).is_err());
+        assert!(read_frames(&[]).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use super::super::interfaces::zinterface::Window;
+
+// the screen as far as the machine is concerned
+//
+// from version 3 on, the screen can be split into an upper window and a lower
+// window. the lower window scrolls, and always has its own cursor ( which the
+// interface looks after, since text just flows into it ), but the upper window
+// is written to like a grid, so we keep track of the cursor there ourselves
+pub struct Screen {
+    // the screen line the upper window starts on - in versions 1-3
+    // the status line is above it
+    pub top: u16,
+
+    // how many lines the upper window has, 0 if the screen isn't split
+    pub upper_height: u16,
+
+    pub current: Window,
+
+    // the cursor in the upper window, as ( line, column ) - both start at 1,
+    // and the line is inside the window, not the screen
+    pub cursor: (u16, u16),
+}
+
+impl Screen {
+    pub fn new(version: u8) -> Screen {
+        Screen {
+            top: match version {
+                1..=3 => 2,
+                _ => 1,
+            },
+            upper_height: 0,
+            current: Window::Lower,
+            cursor: (1, 1),
+        }
+    }
+
+    // the screen position of the upper window's cursor
+    pub fn cursor_on_screen(&self) -> (u16, u16) {
+        (self.top + self.cursor.0 - 1, self.cursor.1)
+    }
+
+    // moves the upper window cursor past text printed at it, returning the
+    // pieces of the text to draw ( and where ) - text that falls out of the
+    // bottom of the window is lost, like on a real screen
+    pub fn advance_cursor(&mut self, text: &str) -> Vec<((u16, u16), String)> {
+        let mut pieces = Vec::new();
+
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.cursor = (self.cursor.0 + 1, 1);
+            }
+
+            if line.is_empty() || self.cursor.0 > self.upper_height {
+                continue;
+            }
+
+            pieces.push((self.cursor_on_screen(), line.to_string()));
+            self.cursor.1 += line.chars().count() as u16;
+        }
+
+        pieces
+    }
+}
@@ -0,0 +1,42 @@
+// sound_effect, as far as the machine is concerned
+//
+// sounds 1 and 2 are a high and a low bleep, which any interface can manage
+// somehow. everything from 3 up is a sampled sound from the story's blorb
+// file, which the story can load ahead of time, start, stop and unload - the
+// interface does the actual playing ( see interfaces/audio.rs )
+//
+// from version 5, the story can also give a routine to call when a sound
+// finishes by itself, so the machine keeps an eye on the last one started
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SoundEffect {
+    Bleep {
+        high: bool,
+    },
+    // loads a sound, so starting it later doesn't have to wait
+    Prepare {
+        number: u16,
+    },
+    // volume is from 1 ( quietest ) to 8, and repeats is how many times to
+    // play it - 0 leaves it up to the sound itself, and 255 is forever
+    Start {
+        number: u16,
+        volume: u8,
+        repeats: u8,
+    },
+    // number 0 stops ( or unloads ) every sound
+    Stop {
+        number: u16,
+    },
+    Unload {
+        number: u16,
+    },
+}
+
+// the routine to call when a sound finishes by itself - stopping it, or
+// starting another sound, means it never gets called
+#[derive(Clone, Copy, Debug)]
+pub struct SoundInterrupt {
+    pub number: u16,
+    pub routine: u16,
+}
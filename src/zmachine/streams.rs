@@ -0,0 +1,44 @@
+// where the machine's output ends up, besides the windows themselves
+//
+// output stream 1 is the screen, and 2 is the transcript ( a copy of the game
+// kept by the player ). the game picks which are on with output_stream, but
+// the transcript can also be turned on by setting a bit in the header, which
+// the game is allowed to do directly
+//
+// stream 3 sends text into a table in memory instead ( games use it to do
+// things like capitalise names ) - while it's on, nothing else gets anything
+//
+// stream 4 ( and the input streams ) are all about reading lines, which is
+// the interface's job, so they live there
+
+// stream 3 can be opened again while it's open, up to this deep
+pub const MAX_MEMORY_STREAMS: usize = 16;
+
+// the table is a word with how many characters there are, followed by
+// the characters themselves
+pub struct MemoryStream {
+    pub table: u32,
+    pub length: u16,
+}
+
+pub struct Streams {
+    // stream 1
+    pub screen: bool,
+
+    // stream 2 - the header bit is what the game asked for, this is whether
+    // the interface actually gave it a transcript
+    pub transcript: bool,
+
+    // stream 3 - only the last one opened gets written to
+    pub memory: Vec<MemoryStream>,
+}
+
+impl Streams {
+    pub fn new() -> Streams {
+        Streams {
+            screen: true,
+            transcript: false,
+            memory: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use super::error::*;
+use super::memory_view::MemoryView;
+use super::zstring::*;
+
+use std::cmp;
+
+// turning a line of input into words, the way sread and tokenise need it
+//
+// there are two buffers, both given to us by the story:
+//
+// the text buffer: byte 0 is how many characters fit. in versions 1-4 the
+// text starts at byte 1 and ends with a 0, from version 5 byte 1 is how many
+// characters there are, the text starts at byte 2, and there's no end marker
+//
+// the parse buffer: byte 0 is how many words fit, byte 1 is how many there
+// are, then 4 bytes a word - its address in the dictionary ( or 0 ), how many
+// characters it is, and where it starts, counting from the start of the
+// text buffer ( not the text )
+//
+// words are split up by spaces, and by the "word separators" listed at the top
+// of the dictionary ( usually things like , . and " ) - unlike spaces, the
+// separators are words themselves, so "take lamp, sword" is four words
+
+// a word from the text buffer, and where it starts ( from the start of the
+// text buffer )
+pub struct Token {
+    pub text: Vec<u8>,
+    pub position: u8,
+}
+
+// the dictionary starts with how many separators there are, and then the
+// separators themselves ( as zscii, a byte each )
+pub fn separators(dictionary: &MemoryView) -> InstructionResult<Vec<u8>> {
+    let count = dictionary.read_at_head(0)? as u32;
+
+    (1..=count).map(|i| dictionary.read_at_head(i)).collect()
+}
+
+// where the text starts in the text buffer
+fn text_start(version: u8) -> u32 {
+    match version {
+        1..=4 => 1,
+        _ => 2,
+    }
+}
+
+// how many characters the text buffer can hold - in versions 1-4 the text is
+// followed by a 0, so we need to leave room for it
+pub fn max_length(view: &MemoryView, text_buffer: u16, version: u8) -> InstructionResult<u8> {
+    match version {
+        1..=4 => Ok(view.read_at(text_buffer as u32)?.saturating_sub(1)),
+        _ => view.read_at(text_buffer as u32),
+    }
+}
+
+// the buffers ( and dictionaries ) are wherever the story says they are, so
+// these check that all of one is in memory up front. sread needs to do this
+// before it waits for input, since the input callback has no way of handing
+// back an error
+
+// filled is whether the story has already put text in the buffer ( for
+// tokenise ) - from version 5 that says how long it is, which can be more
+// than the buffer has room for
+pub fn check_text_buffer(
+    view: &MemoryView,
+    text_buffer: u16,
+    version: u8,
+    filled: bool,
+) -> InstructionResult<()> {
+    let text_buffer = text_buffer as u32;
+
+    let mut length = max_length(view, text_buffer as u16, version)? as u32;
+
+    if filled && version >= 5 {
+        length = cmp::max(length, view.read_at(text_buffer + 1)? as u32);
+    }
+
+    // either the 0 after the text, or the last character after the length byte
+    view.check_address(text_buffer + 1 + length)
+}
+
+pub fn check_parse_buffer(view: &MemoryView, parse_buffer: u16) -> InstructionResult<()> {
+    let parse_buffer = parse_buffer as u32;
+
+    let words = view.read_at(parse_buffer)? as u32;
+
+    // the word count, then 4 bytes a word
+    view.check_address(parse_buffer + 1 + words * 4)
+}
+
+pub fn check_dictionary(dictionary: &MemoryView, version: u8) -> InstructionResult<()> {
+    let separators = dictionary.read_at_head(0)? as u32;
+
+    // the entry length, and how many entries there are
+    let entry_length = dictionary.read_at_head(separators + 1)? as u32;
+    let entries = (dictionary.read_u16_at_head(separators + 2)? as i16).unsigned_abs() as u32;
+
+    if entries == 0 {
+        return Ok(());
+    }
+
+    // find_word compares the whole encoded word, even if the story says
+    // the entries are shorter than that
+    let encoded_length = match version {
+        1..=3 => 4,
+        _ => 6,
+    };
+
+    let last_entry = dictionary.pointer + separators + 4 + (entries - 1) * entry_length;
+    dictionary.check_address(last_entry + cmp::max(entry_length, encoded_length) - 1)
+}
+
+// writes a line of input into the text buffer ( in lower case, as zscii ),
+// exactly as it was typed so the positions of the words line up with it, and
+// ends it the way the version wants. returns how many characters were written
+pub fn write_text_buffer(
+    view: &MemoryView,
+    text_buffer: u16,
+    input: &str,
+    version: u8,
+    charset: &Charset,
+) -> InstructionResult<u8> {
+    let text_buffer = text_buffer as u32;
+    let start = text_buffer + text_start(version);
+    let max_length = max_length(view, text_buffer as u16, version)?;
+
+    let mut length = 0;
+
+    for ch in input.to_lowercase().chars().take(max_length as usize) {
+        // anything zscii doesn't have becomes a question mark
+        let zscii = ZString::encode_zscii(ch, charset).unwrap_or('?' as u16);
+        view.write_at(start + length as u32, zscii as u8)?;
+        length += 1;
+    }
+
+    match version {
+        1..=4 => view.write_at(start + length as u32, 0)?,
+        _ => view.write_at(text_buffer + 1, length)?,
+    }
+
+    Ok(length)
+}
+
+// reads the text back out of the text buffer, as zscii
+pub fn read_text_buffer(
+    view: &MemoryView,
+    text_buffer: u16,
+    version: u8,
+) -> InstructionResult<Vec<u8>> {
+    let start = text_buffer as u32 + text_start(version);
+
+    match version {
+        // the text runs until a 0, but never past the end of the buffer
+        1..=4 => {
+            let max_length = max_length(view, text_buffer, version)? as u32;
+
+            (start..start + max_length)
+                .map(|i| view.read_at(i))
+                .take_while(|ch| *ch != Ok(0))
+                .collect()
+        }
+        _ => {
+            let length = view.read_at(text_buffer as u32 + 1)? as u32;
+            (start..start + length).map(|i| view.read_at(i)).collect()
+        }
+    }
+}
+
+// splits text from the text buffer into words
+pub fn tokenize(text: &[u8], version: u8, separators: &[u8]) -> Vec<Token> {
+    let start = text_start(version) as usize;
+    let mut tokens: Vec<Token> = Vec::with_capacity(8);
+
+    // whether the last token can be added to
+    let mut in_word = false;
+
+    for (i, &ch) in text.iter().enumerate() {
+        let starts_word = ch != b' ' && (!in_word || separators.contains(&ch));
+
+        // where a word starts only gets a byte in the parse buffer, and a
+        // full version 5 buffer ( 255 characters from byte 2 ) runs past that,
+        // so no words start after 255 ( one that's already going carries on )
+        if starts_word && start + i > u8::MAX as usize {
+            break;
+        }
+
+        let position = (start + i) as u8;
+
+        if ch == b' ' {
+            in_word = false;
+        } else if separators.contains(&ch) {
+            tokens.push(Token {
+                text: vec![ch],
+                position,
+            });
+            in_word = false;
+        } else if in_word {
+            // in_word is only ever set after a push, so there's a last token
+            if let Some(token) = tokens.last_mut() {
+                token.text.push(ch);
+            }
+        } else {
+            tokens.push(Token {
+                text: vec![ch],
+                position,
+            });
+            in_word = true;
+        }
+    }
+
+    tokens
+}
+
+// reads the words out of the text buffer, looks them up in the dictionary,
+// and fills in the parse buffer
+//
+// if skip_unknown is set, words that aren't in the dictionary are left alone
+// in the parse buffer ( tokenise uses this to fill in the gaps with a second
+// dictionary )
+pub fn write_parse_buffer(
+    view: &MemoryView,
+    text_buffer: u16,
+    parse_buffer: u16,
+    dictionary: &MemoryView,
+    version: u8,
+    charset: &Charset,
+    skip_unknown: bool,
+) -> InstructionResult<()> {
+    let text = read_text_buffer(view, text_buffer, version)?;
+    let tokens = tokenize(&text, version, &separators(dictionary)?);
+
+    let mut cursor = parse_buffer as u32;
+
+    // only as many words as the parse buffer has room for
+    let max_tokens = view.read_at(cursor)?;
+    let token_count = cmp::min(max_tokens as usize, tokens.len());
+
+    view.write_at(cursor + 1, token_count as u8)?;
+    cursor += 2;
+
+    for token in tokens[0..token_count].iter() {
+        let encoded_word = ZString::encode_word(&token.text, version, charset);
+
+        // dictionaries live in the lower 64k, so the address always fits
+        match (find_word(&encoded_word, dictionary)?, skip_unknown) {
+            (Some(x), _) => view.write_u16_at(cursor, x as u16)?,
+            (None, false) => view.write_u16_at(cursor, 0)?,
+            (None, true) => {
+                cursor += 4;
+                continue;
+            }
+        }
+
+        // the length is of the whole word, even if the dictionary only
+        // keeps the start of it
+        view.write_at(cursor + 2, token.text.len() as u8)?;
+        view.write_at(cursor + 3, token.position)?;
+
+        cursor += 4;
+    }
+
+    Ok(())
+}
+
+// finds an encoded word in the dictionary, and returns the address of its entry
+pub fn find_word(string: &ZWord, dictionary: &MemoryView) -> InstructionResult<Option<u32>> {
+    // after the separators, which are a byte each, we have the entry length
+    let num_input_codes = dictionary.read_at_head(0)? as u32;
+    let entry_length = dictionary.read_at_head(num_input_codes + 1)? as u32;
+
+    // and one after that we have the # of entries - a negative number means
+    // the entries aren't sorted ( only in dictionaries given to tokenise )
+    let dictionary_entries = dictionary.read_u16_at_head(num_input_codes + 2)? as i16;
+
+    // so the total offset is four bytes + num_input_codes
+    let dictionary_header_offset = num_input_codes + 4;
+
+    // we only compare the encoded part of the entry, the rest is
+    // up to the game
+    let encoded_string = string.encoded_as_vec();
+
+    let read_entry = |offset: u32| -> InstructionResult<Vec<u8>> {
+        (0..encoded_string.len() as u32)
+            .map(|i| dictionary.read_at_head(offset + i))
+            .collect()
+    };
+
+    // and we more or less have to compute this each time, since its actually legal
+    // to alter the dictionary
+
+    if dictionary_entries < 0 {
+        // nothing to do but look at every entry
+        for pointer in 0..(-(dictionary_entries as i32)) as u32 {
+            let offset = dictionary_header_offset + pointer * entry_length;
+
+            if read_entry(offset)? == encoded_string {
+                return Ok(Some(dictionary.pointer + offset));
+            }
+        }
+
+        return Ok(None);
+    }
+
+    // binary search
+    // it ends up working because all characters are padded with the same
+    // end character (5), and the numerical order of z-characters turns
+    // out to also be the alphabetic one
+    //
+    // comparing the encoded bytes one at a time is the same as taking the 4 or 6
+    // bytes of the word as one number, and that's the order of the table
+
+    let mut lower: i32 = 0;
+    let mut upper: i32 = dictionary_entries as i32 - 1;
+
+    while lower <= upper {
+        let pointer = lower + (upper - lower) / 2;
+        let offset = dictionary_header_offset + pointer as u32 * entry_length;
+
+        match encoded_string.cmp(&read_entry(offset)?) {
+            cmp::Ordering::Equal => return Ok(Some(dictionary.pointer + offset)),
+            cmp::Ordering::Less => upper = pointer - 1,
+            cmp::Ordering::Greater => lower = pointer + 1,
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn texts(tokens: &[Token]) -> Vec<&[u8]> {
+        tokens.iter().map(|token| &token.text[..]).collect()
+    }
+
+    fn positions(tokens: &[Token]) -> Vec<u8> {
+        tokens.iter().map(|token| token.position).collect()
+    }
+
+    // a dictionary at 0x100 with , as its only separator and the given words,
+    // in the order given - a negative count means they aren't sorted
+    fn dictionary(words: &[&[u8]], count: i16) -> MemoryView {
+        let charset = Charset::default_table(5);
+        let mut data = vec![0; 0x200];
+
+        data[0x100..0x105].copy_from_slice(&[1, b',', 7, (count >> 8) as u8, count as u8]);
+
+        for (i, word) in words.iter().enumerate() {
+            let entry = 0x105 + i * 7;
+            let encoded = ZString::encode_word(word, 5, &charset).encoded_as_vec();
+            data[entry..entry + 6].copy_from_slice(&encoded);
+        }
+
+        MemoryView {
+            memory: Rc::new(RefCell::new(data)),
+            pointer: 0x100,
+        }
+    }
+
+    fn find(word: &[u8], dictionary: &MemoryView) -> Option<u32> {
+        let charset = Charset::default_table(5);
+        find_word(&ZString::encode_word(word, 5, &charset), dictionary).unwrap()
+    }
+
+    #[test]
+    fn separators_are_words_but_spaces_arent() {
+        let tokens = tokenize(b"take lamp, sword", 5, b",.\"");
+
+        assert_eq!(texts(&tokens), vec![&b"take"[..], b"lamp", b",", b"sword"]);
+        assert_eq!(positions(&tokens), vec![2, 7, 11, 13]);
+    }
+
+    #[test]
+    fn quotes_split_words() {
+        let tokens = tokenize(b"say \"hello\"", 5, b",.\"");
+
+        assert_eq!(texts(&tokens), vec![&b"say"[..], b"\"", b"hello", b"\""]);
+        assert_eq!(positions(&tokens), vec![2, 6, 7, 12]);
+    }
+
+    #[test]
+    fn positions_count_from_the_start_of_the_text_buffer() {
+        // the text starts a byte later from version 5, after its length
+        assert_eq!(
+            positions(&tokenize(b"take lamp, sword", 3, b",")),
+            vec![1, 6, 10, 12]
+        );
+        assert_eq!(positions(&tokenize(b"  go", 4, b",")), vec![3]);
+        assert_eq!(positions(&tokenize(b"  go", 5, b",")), vec![4]);
+    }
+
+    #[test]
+    fn no_words_start_past_255() {
+        // "ab" starts at 255 and runs on past it, "cd" would start at 258
+        let mut text = vec![b' '; 253];
+        text.extend_from_slice(b"ab cd");
+
+        let tokens = tokenize(&text, 5, b",");
+
+        assert_eq!(texts(&tokens), vec![&b"ab"[..]]);
+        assert_eq!(positions(&tokens), vec![255]);
+    }
+
+    #[test]
+    fn sorted_dictionaries_are_searched() {
+        let dictionary = dictionary(&[b"lamp", b"sword", b"take"], 3);
+
+        assert_eq!(find(b"lamp", &dictionary), Some(0x105));
+        assert_eq!(find(b"sword", &dictionary), Some(0x10C));
+        assert_eq!(find(b"take", &dictionary), Some(0x113));
+        assert_eq!(find(b"xyzzy", &dictionary), None);
+    }
+
+    #[test]
+    fn unsorted_dictionaries_are_read_through() {
+        // a binary search wouldn't find "lamp" in here
+        let dictionary = dictionary(&[b"take", b"sword", b"lamp"], -3);
+
+        assert_eq!(find(b"take", &dictionary), Some(0x105));
+        assert_eq!(find(b"lamp", &dictionary), Some(0x113));
+        assert_eq!(find(b"xyzzy", &dictionary), None);
+    }
+
+    #[test]
+    fn parse_buffers_get_every_word() {
+        let charset = Charset::default_table(5);
+        let dictionary = dictionary(&[b"lamp", b"sword", b"take"], 3);
+        let view = MemoryView {
+            memory: dictionary.memory.clone(),
+            pointer: 0,
+        };
+
+        // room for 20 characters at 0x10, and 3 words at 0x40
+        view.write_at(0x10, 20).unwrap();
+        view.write_at(0x40, 3).unwrap();
+
+        let length = write_text_buffer(&view, 0x10, "Take lamp, sword", 5, &charset).unwrap();
+        assert_eq!(length, 16);
+
+        write_parse_buffer(&view, 0x10, 0x40, &dictionary, 5, &charset, false).unwrap();
+
+        let parse_buffer: Vec<u8> = (0x41..0x4E).map(|i| view.read_at(i).unwrap()).collect();
+
+        // the fourth word ( "sword" ) doesn't fit
+        assert_eq!(
+            parse_buffer,
+            vec![3, 0x01, 0x13, 4, 2, 0x01, 0x05, 4, 7, 0, 0, 1, 11]
+        );
+    }
+}
@@ -2,15 +2,24 @@ extern crate rand;
 use self::rand::*;
 
 use super::super::interfaces::zinterface::*;
+use super::error::*;
 use super::opcode::*;
 
 // represents the current zmachine
+use super::blorb::Blorb;
 use super::global_variables_view::*;
+use super::graphics::{Graphics, TRANSCRIPT};
 use super::header::*;
 use super::memory_view::*;
 use super::object_view::*;
+use super::quetzal;
+use super::screen::*;
+use super::sound::SoundInterrupt;
+use super::streams::*;
+use super::zstring::{Charset, ZString};
 
 use std::cell::*;
+use std::collections::VecDeque;
 use std::rc::*;
 
 // once this is a FnMut or FnOnce, I don't think we
@@ -18,10 +27,21 @@ use std::rc::*;
 #[derive(Clone)]
 pub enum MachineState {
     Stopped,
-    Restarting,
     Running,
     // input finished takes ownership of the string
-    TakingInput { callback: Rc<dyn Fn(String)> },
+    //
+    // the callback hands back a value for the instruction to store ( the
+    // key pressed, or what ended the line ), if it has somewhere to store it
+    //
+    // line is true for a whole command ( sread ), and false for a single
+    // key ( read_char ) - max_length is how many characters the story has
+    // room for
+    TakingInput {
+        callback: Rc<dyn Fn(String) -> u16>,
+        store: Option<u8>,
+        line: bool,
+        max_length: u8,
+    },
 }
 
 pub struct RandomGen<T> {
@@ -83,7 +103,64 @@ impl<T: rand::SeedableRng<[u32; 4]>> RandomGen<T> {
     }
 }
 
+// this is tucked under every call frame ( just below the saved top of frame ),
+// because the routine header that told us how many locals there are is long
+// gone by the time we need to write the frame out to a save file
+//
+// it's packed into a single word so it lives on the stack like everything else;
+// the low byte is laid out exactly like the quetzal "flags" byte ( 0b000pvvvv ),
+// and the high byte is the "arguments supplied" mask
+#[derive(Clone, Copy, Default)]
+pub struct FrameInfo {
+    // how many locals does the routine have ( 0..15 )
+    pub locals: u8,
+    // bit n is set if argument n + 1 was given to the call
+    pub args_supplied: u8,
+    // true if the result of the routine is thrown away ( call_vn and friends )
+    pub discard_result: bool,
+}
+
+impl FrameInfo {
+    pub fn from_word(word: u16) -> FrameInfo {
+        FrameInfo {
+            locals: (word & 0x0f) as u8,
+            args_supplied: (word >> 8) as u8,
+            discard_result: word & 0x10 != 0,
+        }
+    }
+
+    pub fn to_word(&self) -> u16 {
+        let flags = (self.locals & 0x0f) | ((self.discard_result as u8) << 4);
+        ((self.args_supplied as u16) << 8) | flags as u16
+    }
+}
+
+// undo keeps this many turns by default
+pub const DEFAULT_UNDO_DEPTH: usize = 10;
+
+// a copy of everything that changes as a story runs - like a save file,
+// but kept in memory ( and without any of the file format )
+pub struct UndoState {
+    pub dynamic_memory: Vec<u8>,
+    pub call_stack: Stack,
+    pub pc: u32,
+}
+
+// an "unrolled" view of one frame on the stack - we don't execute from these,
+// but save files (and anything else that wants to look at the whole call chain)
+// need the stack split up by routine
+pub struct CallFrame {
+    // the address of the call instruction, and how far into it
+    // the store byte is - exactly what call pushes and ret pops
+    pub return_address: u32,
+    pub return_offset: u16,
+    pub info: FrameInfo,
+    pub locals: Vec<u16>,
+    pub evaluation_stack: Vec<u16>,
+}
+
 // wraps a Vec with some other information
+#[derive(Clone)]
 pub struct Stack {
     // this holds the top of the last frame,
     // this is important because we tuck addresses under here
@@ -100,22 +177,45 @@ impl Stack {
     // gotta be careful here because you would normally think you would have
     // to offset by index because of where top_of_frame is, but as it turns
     // out, starting out with "1" prevents that
-    pub fn get_local_variable(&self, num: u8) -> u16 {
+    pub fn get_local_variable(&self, num: u8) -> InstructionResult<u16> {
         // here we will cast because i do want some restrictions
         // around get local variable
-        let offset = num as usize;
-        let index = self.top_of_frame + offset;
+        let index = self.local_variable_index(num)?;
 
         // println!("num is: {}", num);
         // println!("getting index: {}", index);
 
-        self.stack[index as usize]
+        match self.stack.get(index) {
+            Some(value) => Ok(*value),
+            None => Err(ErrorKind::InvalidLocal { index: num }),
+        }
+    }
+
+    pub fn store_local_variable(&mut self, num: u8, value: u16) -> InstructionResult<()> {
+        let index = self.local_variable_index(num)?;
+
+        match self.stack.get_mut(index) {
+            Some(local) => *local = value,
+            None => return Err(ErrorKind::InvalidLocal { index: num }),
+        }
+
+        Ok(())
     }
 
-    pub fn store_local_variable(&mut self, num: u8, value: u16) {
-        let offset = num as usize;
-        let index = self.top_of_frame + offset;
-        self.stack[index as usize] = value;
+    // the routine told us how many locals it has when it was called,
+    // so anything past that is a broken story file
+    fn local_variable_index(&self, num: u8) -> InstructionResult<usize> {
+        let locals = match self.top_of_frame {
+            // the main routine has no locals
+            0 => 0,
+            top => FrameInfo::from_word(self.stack[top - 1]).locals,
+        };
+
+        if num == 0 || num > locals {
+            return Err(ErrorKind::InvalidLocal { index: num });
+        }
+
+        Ok(self.top_of_frame + num as usize)
     }
 
     pub fn switch_to_new_frame(&mut self) {
@@ -128,22 +228,171 @@ impl Stack {
         self.top_of_frame = self.top_of_stack();
     }
 
-    pub fn restore_last_frame(&mut self) {
+    pub fn restore_last_frame(&mut self) -> InstructionResult<()> {
+        // the main routine has nowhere to return to
+        if self.top_of_frame == 0 {
+            return Err(ErrorKind::StackUnderflow);
+        }
+
         // dump everything after the top of the frame
         self.stack.truncate(self.top_of_frame + 1);
 
         // restore the top of the frame ( hidden )
         self.top_of_frame = match self.stack.pop() {
             Some(frame) => frame as usize,
-            _ => panic!("restoring last frame resulted in stack underflow!"),
+            _ => return Err(ErrorKind::StackUnderflow),
         };
 
         // println!("top of frame:{}", self.top_of_frame);
+        Ok(())
+    }
+
+    // what we know about the routine we are in right now
+    pub fn current_frame_info(&self) -> FrameInfo {
+        match self.top_of_frame {
+            0 => FrameInfo::default(),
+            top => FrameInfo::from_word(self.stack[top - 1]),
+        }
+    }
+
+    // catch hands this out, so that throw can find its way back to
+    // the routine that called catch
+    pub fn frame_pointer(&self) -> u16 {
+        self.top_of_frame as u16
+    }
+
+    // throws away every frame above the one at frame_pointer, without
+    // returning from any of them
+    pub fn unwind_to(&mut self, frame_pointer: u16) -> InstructionResult<()> {
+        while self.top_of_frame != frame_pointer as usize {
+            // we went past it ( or it was never a frame ), so there's
+            // nothing sensible to return to
+            if self.top_of_frame < frame_pointer as usize {
+                return Err(ErrorKind::StackUnderflow);
+            }
+
+            self.restore_last_frame()?;
+
+            // the frame info, offset and return address
+            for _ in 0..4 {
+                self.pop()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // where the routine's own stack starts - anything under this is its
+    // locals ( and the words call tucked under them ), which a pop mustn't eat
+    fn bottom_of_frame(&self) -> usize {
+        match self.top_of_frame {
+            0 => 0,
+            top => top + 1 + FrameInfo::from_word(self.stack[top - 1]).locals as usize,
+        }
+    }
+
+    pub fn pop(&mut self) -> InstructionResult<u16> {
+        if self.stack.len() <= self.bottom_of_frame() {
+            return Err(ErrorKind::StackUnderflow);
+        }
+
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(ErrorKind::StackUnderflow),
+        }
+    }
+
+    // the top of the routine's stack, for the instructions that change
+    // it in place instead of popping it
+    pub fn top_mut(&mut self) -> InstructionResult<&mut u16> {
+        if self.stack.len() <= self.bottom_of_frame() {
+            return Err(ErrorKind::StackUnderflow);
+        }
+
+        match self.stack.last_mut() {
+            Some(value) => Ok(value),
+            None => Err(ErrorKind::StackUnderflow),
+        }
     }
 
     pub fn top_of_stack(&self) -> usize {
         self.stack.len() - 1
     }
+
+    // splits the stack up into its call frames, oldest first
+    //
+    // the first frame is always the "main" routine, which has no locals
+    // and no return address, just whatever it pushed onto the stack
+    pub fn get_frames(&self) -> Vec<CallFrame> {
+        let mut frames = Vec::new();
+        let mut top = self.top_of_frame;
+        let mut end = self.stack.len();
+
+        // every called frame has four words tucked under it, so only
+        // the main routine can have a top of frame of 0
+        while top != 0 {
+            let info = FrameInfo::from_word(self.stack[top - 1]);
+            let locals_end = top + 1 + info.locals as usize;
+
+            frames.push(CallFrame {
+                return_address: (self.stack[top - 4] as u32) | ((self.stack[top - 3] as u32) << 16),
+                return_offset: self.stack[top - 2],
+                info,
+                locals: self.stack[top + 1..locals_end].to_vec(),
+                evaluation_stack: self.stack[locals_end..end].to_vec(),
+            });
+
+            end = top - 4;
+            top = self.stack[top] as usize;
+        }
+
+        frames.push(CallFrame {
+            return_address: 0,
+            return_offset: 0,
+            info: FrameInfo::default(),
+            locals: Vec::new(),
+            evaluation_stack: self.stack[0..end].to_vec(),
+        });
+
+        frames.reverse();
+        frames
+    }
+
+    // the inverse of get_frames, this rebuilds the stack exactly as
+    // a series of calls would have left it
+    pub fn from_frames(frames: &[CallFrame]) -> Stack {
+        let mut stack = Stack {
+            top_of_frame: 0,
+            stack: Vec::new(),
+        };
+
+        for (i, frame) in frames.iter().enumerate() {
+            if i > 0 {
+                stack.stack.push((frame.return_address & 0xFFFF) as u16);
+                stack.stack.push((frame.return_address >> 16) as u16);
+                stack.stack.push(frame.return_offset);
+                stack.stack.push(frame.info.to_word());
+                stack.switch_to_new_frame();
+            }
+
+            stack.stack.extend_from_slice(&frame.locals);
+            stack.stack.extend_from_slice(&frame.evaluation_stack);
+        }
+
+        stack
+    }
+}
+
+// checks that data looks like a story file we can run - ZMachine::new()
+// does this itself, but it's here for checking a story without running it
+pub fn validate_story(data: &[u8]) -> Result<(), String> {
+    Header::validate(data)
+}
+
+// checks the story adds up to the checksum in its header, which tells us
+// whether it's the exact build we think it is ( or says why it can't )
+pub fn verify_story(data: &[u8]) -> Result<Checksum, String> {
+    Header::checksum(data)
 }
 
 pub struct ZMachine<T: ZInterface> {
@@ -161,15 +410,15 @@ pub struct ZMachine<T: ZInterface> {
     // interface with in the ZMachine ), but almost everyone and
     // implements it this way because its straightforward
     // and mirrors "actual" stack frames.
-    pub call_stack: Stack,
+    pub(crate) call_stack: Stack,
 
     // the header, which actually reads the first 64 bytes in memory
-    // everyone has access to it, its mostly configuration stuff
+    // the whole crate has access to it, its mostly configuration stuff
     // and version info
-    pub header: Header,
+    pub(crate) header: Header,
 
     // the interface to the GUI, so ZMachine can print/take input
-    pub zinterface: Rc<T>,
+    pub(crate) zinterface: Rc<T>,
 
     // ALL of the memory, this represents the entire state of the machine
     // this is loaded in at first , then modified by save files, then
@@ -187,24 +436,67 @@ pub struct ZMachine<T: ZInterface> {
     //
     // the machine owns a reference to the memory, and typically
     // is the only person who asks for a mutable reference
-    pub memory: Rc<RefCell<Vec<u8>>>,
+    pub(crate) memory: Rc<RefCell<Vec<u8>>>,
+
+    // a pristine copy of the story as it was loaded - save files
+    // only store the difference between this and dynamic memory
+    pub(crate) original_memory: Vec<u8>,
+
+    // the font the story last asked for with set_font - we only have the one,
+    // but the story gets told which one it had before
+    pub(crate) font: u16,
+
+    // the characters zscii stands for
+    pub(crate) charset: Charset,
+
+    // which window is selected, and where the upper window is
+    pub(crate) screen: Screen,
+
+    // version 6's windows, which take the place of screen
+    pub(crate) graphics: Graphics,
+
+    // the pictures and sounds from the story's blorb file, if it came in one
+    pub(crate) resources: Option<Rc<Blorb>>,
+
+    // which output streams are on
+    pub(crate) streams: Streams,
+
+    // the routine to call when the sound that's playing finishes
+    pub(crate) sound_interrupt: Option<SoundInterrupt>,
 
     // the stack pointer/program counter, technically this can be 0-512k,
     // closest representation is u32
     //
     // note that this is one of the very few 'u32' things here
-    pub ip: u32,
+    pub(crate) ip: u32,
 
     //we use XorShiftRng because there are no real security concerns here
-    pub random_generator: RandomGen<XorShiftRng>,
+    pub(crate) random_generator: RandomGen<XorShiftRng>,
 
     // are we still running? keep processing.
-    pub state: MachineState,
+    pub(crate) state: MachineState,
+
+    // how many undo states to keep ( 0 turns undo off ), and the states
+    // themselves, oldest first
+    pub(crate) undo_depth: usize,
+    pub(crate) undo_states: VecDeque<UndoState>,
+
+    // whatever is on the lower window's last line so far ( usually the
+    // prompt ), and the copy of it undo leaves for sread to print again
+    pub(crate) line: String,
+    pub(crate) undone_prompt: Option<String>,
+
+    // makes the verify opcode always succeed, for stories that have been
+    // patched without fixing up their checksum
+    pub(crate) force_verify: bool,
 }
 
 impl<T: ZInterface> ZMachine<T> {
-    //creates a new zmachine from the data given
-    pub fn new(data: Vec<u8>, interface: T) -> ZMachine<T> {
+    //creates a new zmachine from the data given, or says why it can't
+    pub fn new(data: Vec<u8>, interface: T) -> Result<ZMachine<T>, String> {
+        // anything that fails this would panic somewhere further down
+        validate_story(&data)?;
+
         // we have to create an immutably reference
         // counted mutable reference in order to
         //
@@ -223,6 +515,7 @@ impl<T: ZInterface> ZMachine<T> {
         // using immutable calls in the child, and mutable calls
         // only in the parent
 
+        let original_memory = data.clone();
         let memory = Rc::new(RefCell::new(data));
 
         // we are going to give the reference to the header,
@@ -239,16 +532,34 @@ impl<T: ZInterface> ZMachine<T> {
         // note that pc_start is a u16, but our pointer is a u32. this is because
 
         let pc_start = header.pc_start as u32;
+        let screen = Screen::new(header.version);
         let interface = Rc::new(interface);
+        let graphics = Graphics::new(&interface.capabilities());
 
-        let machine = ZMachine::<T> {
+        let charset = Charset::create(
+            &MemoryView {
+                memory: memory.clone(),
+                pointer: 0,
+            },
+            &header,
+        );
+
+        let mut machine = ZMachine::<T> {
             call_stack: Stack {
                 top_of_frame: 0,
                 stack: Vec::new(),
             },
-            header: header,
+            header,
             ip: pc_start,
-            memory: memory,
+            font: 1,
+            charset,
+            screen,
+            graphics,
+            resources: None,
+            streams: Streams::new(),
+            sound_interrupt: None,
+            memory,
+            original_memory,
             zinterface: interface,
             random_generator: RandomGen {
                 generator: XorShiftRng::from_seed([
@@ -262,10 +573,120 @@ impl<T: ZInterface> ZMachine<T> {
                 randoms_predictable_next: 1,
             },
             state: MachineState::Running,
+            undo_depth: DEFAULT_UNDO_DEPTH,
+            undo_states: VecDeque::new(),
+            line: String::new(),
+            undone_prompt: None,
+            force_verify: false,
         };
 
+        machine.write_capabilities();
+        machine.call_main_routine();
+
+        // the screen starts out unsplit - in versions 1-3 that still leaves
+        // the status line at the top
+        machine.zinterface.split_window(machine.screen.top, 0);
+
         //does nothing in desktop
-        machine
+        Ok(machine)
+    }
+
+    // everything the story prints goes through here, so it ends up in
+    // whichever window is selected
+    pub(crate) fn print(&mut self, text: &str) {
+        if let Some(stream) = self.streams.memory.last_mut() {
+            let mut memory = self.memory.borrow_mut();
+
+            for ch in text.chars() {
+                // anything zscii doesn't have becomes a question mark
+                let zscii = ZString::encode_zscii(ch, &self.charset).unwrap_or('?' as u16);
+                let address = (stream.table + 2 + stream.length as u32) as usize;
+
+                // the table is the game's to size, if it's too close to
+                // the end of memory the rest is lost
+                if address < memory.len() {
+                    memory[address] = zscii as u8;
+                }
+
+                stream.length = stream.length.wrapping_add(1);
+            }
+
+            return;
+        }
+
+        self.update_transcript();
+
+        if self.header.version == 6 {
+            self.print_to_graphics(text);
+            return;
+        }
+
+        if self.streams.screen {
+            match self.screen.current {
+                Window::Lower => {
+                    match text.rfind('\n') {
+                        Some(index) => self.line = text[index + 1..].to_string(),
+                        None => self.line.push_str(text),
+                    }
+
+                    self.zinterface.print_to_main(text);
+                }
+                Window::Upper => {
+                    for ((line, column), piece) in self.screen.advance_cursor(text) {
+                        self.zinterface.print_to_upper(line, column, &piece);
+                    }
+                }
+            }
+        }
+
+        // the upper window is things like maps and quote boxes, which
+        // don't belong in a transcript
+        if self.streams.transcript && self.screen.current == Window::Lower {
+            self.zinterface.print_to_transcript(text);
+        }
+    }
+
+    // version 6 draws everything, but window 0 is still the story's main
+    // text, so it goes to the interface as text as well - and each window
+    // says for itself whether it belongs in the transcript
+    fn print_to_graphics(&mut self, text: &str) {
+        let window = self.graphics.current;
+
+        if self.streams.screen {
+            for command in self.graphics.print(text) {
+                self.zinterface.draw(&command);
+            }
+
+            if window == 0 {
+                self.zinterface.print_to_main(text);
+            }
+        }
+
+        if self.streams.transcript && self.graphics.windows[window].attributes & TRANSCRIPT != 0 {
+            self.zinterface.print_to_transcript(text);
+        }
+    }
+
+    // the game can start and stop the transcript by flipping the header
+    // bit itself, so we check it whenever there's something to write
+    pub(crate) fn update_transcript(&mut self) {
+        let wanted = self.header.transcripting();
+
+        if wanted == self.streams.transcript {
+            return;
+        }
+
+        if wanted {
+            self.streams.transcript = self.zinterface.start_transcript();
+
+            // there's nowhere to write it, so the game gets told it's off
+            if !self.streams.transcript {
+                self.header.set_transcripting(false);
+            }
+        } else {
+            self.zinterface.stop_transcript();
+            self.streams.transcript = false;
+        }
     }
 
     //anyone can read this, just not mut/set it
@@ -273,9 +694,325 @@ impl<T: ZInterface> ZMachine<T> {
         self.ip
     }
 
+    // the rest of the machine is internal, but these are the bits of it
+    // anything running a story might want to look at ( or change ) between steps
+
+    pub fn interface(&self) -> &Rc<T> {
+        &self.zinterface
+    }
+
+    pub fn state(&self) -> &MachineState {
+        &self.state
+    }
+
+    // what the story's header says, laid out for reading
+    pub fn header_status(&self) -> String {
+        self.header.get_status()
+    }
+
+    // a byte of memory, or None if the address is past the end of it
+    pub fn read_memory(&self, address: u32) -> Option<u8> {
+        self.memory.borrow().get(address as usize).cloned()
+    }
+
+    // global variables are numbered from 0 here ( they're variables 16-255
+    // to the story ) - version 3 keeps the score and turns in 1 and 2
+    pub fn read_global(&self, number: u8) -> Option<u16> {
+        let address = self.header.global_vars_table_location as u32 + number as u32 * 2;
+
+        match (self.read_memory(address), self.read_memory(address + 1)) {
+            (Some(upper), Some(lower)) => Some((upper as u16) << 8 | lower as u16),
+            _ => None,
+        }
+    }
+
+    // how many turns undo can go back, 0 turns it off
+    pub fn set_undo_depth(&mut self, depth: usize) {
+        self.undo_depth = depth;
+    }
+
+    // makes the verify opcode always succeed
+    pub fn set_force_verify(&mut self, force_verify: bool) {
+        self.force_verify = force_verify;
+    }
+
+    // writes dynamic memory and the stack out as a quetzal save file;
+    // pc is where execution should pick back up after a restore
+    pub(crate) fn create_save(&self, pc: u32) -> Vec<u8> {
+        let dynamic_length = self.header.static_memory_start_location as usize;
+        let memory = self.memory.borrow();
+
+        quetzal::write_save(
+            &memory,
+            &self.original_memory,
+            dynamic_length,
+            &self.call_stack.get_frames(),
+            pc,
+        )
+    }
+
+    // loads a quetzal save file over dynamic memory and the stack, and
+    // returns where to carry on from - the pc the save was made at, less
+    // back ( the length of the instruction restoring it ). if anything is
+    // wrong with the file, the machine is left untouched
+    pub(crate) fn restore_save(&mut self, data: &[u8], back: u32) -> Result<u32, String> {
+        let dynamic_length = self.header.static_memory_start_location as usize;
+        let state = quetzal::read_save(data, &self.original_memory, dynamic_length)?;
+
+        let ip = match state.pc.checked_sub(back) {
+            Some(ip) if (ip as usize) < self.memory.borrow().len() => ip,
+            _ => return Err(format!("the save's pc ({:x}) isn't in the story", state.pc)),
+        };
+
+        {
+            let mut memory = self.memory.borrow_mut();
+
+            // the transcript and fixed pitch bits of flags 2 belong to
+            // the player, not the save file, so they survive a restore
+            let preserved = memory[0x11] & 0b11;
+
+            memory[..dynamic_length].copy_from_slice(&state.dynamic_memory);
+            memory[0x11] = (memory[0x11] & !0b11) | preserved;
+        }
+
+        self.call_stack = Stack::from_frames(&state.frames);
+        self.write_capabilities();
+
+        Ok(ip)
+    }
+
+    // tells the story what the interface can do, through the header - the
+    // interface might have changed since last time ( a terminal can be resized )
+    fn write_capabilities(&mut self) {
+        let capabilities = self.zinterface.capabilities();
+        self.header.write_capabilities(&capabilities);
+    }
+
+    // hands the machine ( and the interface ) the pictures and sounds the
+    // story came with - this has to happen before it starts running, since
+    // the story can ask about them straight away
+    pub fn load_resources(&mut self, resources: Blorb) {
+        let resources = Rc::new(resources);
+
+        self.graphics.load_pictures(&resources);
+        self.zinterface.load_resources(&resources);
+        self.resources = Some(resources);
+    }
+
+    // if the sound the story is waiting on has finished, its routine gets
+    // called before the next instruction - as if the instruction had been a
+    // call, but with nothing to store the result in
+    fn check_sound_interrupt(&mut self) {
+        let interrupt = match self.sound_interrupt {
+            Some(x) if !self.zinterface.sound_playing(x.number) => x,
+            _ => return,
+        };
+
+        self.sound_interrupt = None;
+
+        let address = self.unpack_routine_address(interrupt.routine);
+
+        // a routine that isn't there would only stop the story for
+        // something it can't have meant
+        let num_locals = match self.get_memory_view().read_at(address) {
+            Ok(x) if address != 0 => x,
+            _ => return,
+        };
+
+        let frame_info = FrameInfo {
+            locals: num_locals,
+            args_supplied: 0,
+            discard_result: true,
+        };
+
+        // returning comes straight back here, to the instruction we were
+        // about to run
+        let ip = self.ip;
+
+        for word in [
+            (ip & 0xFFFF) as u16,
+            (ip >> 16) as u16,
+            0,
+            frame_info.to_word(),
+        ]
+        .iter()
+        {
+            self.call_stack.stack.push(*word);
+        }
+
+        self.call_stack.switch_to_new_frame();
+
+        // only version 5 and up have these, so the locals all start at 0
+        for _ in 0..num_locals {
+            self.call_stack.stack.push(0);
+        }
+
+        self.ip = address + 1;
+    }
+
+    // in version 6 the story starts with a call to its main routine ( which
+    // it can never return from ), rather than just at an address - the
+    // frame is laid out like call would, with nowhere to go back to
+    fn call_main_routine(&mut self) {
+        if self.header.version != 6 {
+            return;
+        }
+
+        let address = self.unpack_routine_address(self.header.pc_start);
+
+        // validate_story made sure the routine is there
+        let num_locals = self.get_memory_view().read_at(address).unwrap_or(0);
+
+        let frame_info = FrameInfo {
+            locals: num_locals,
+            args_supplied: 0,
+            discard_result: true,
+        };
+
+        for word in [0, 0, 0, frame_info.to_word()].iter() {
+            self.call_stack.stack.push(*word);
+        }
+
+        self.call_stack.switch_to_new_frame();
+
+        for _ in 0..num_locals {
+            self.call_stack.stack.push(0);
+        }
+
+        self.ip = address + 1;
+    }
+
+    // what the verify opcode answers - whether the story as loaded adds up
+    // to the checksum in its header
+    pub(crate) fn verify(&self) -> bool {
+        self.force_verify
+            || Header::checksum(&self.original_memory)
+                .map(|checksum| checksum.matches())
+                .unwrap_or(false)
+    }
+
+    // starts the story over in place - dynamic memory goes back to how it was
+    // loaded, and the stack and program counter go back to the start
+    pub(crate) fn restart(&mut self) {
+        let dynamic_length = self.header.static_memory_start_location as usize;
+
+        {
+            let mut memory = self.memory.borrow_mut();
+
+            // like restoring, the transcript and fixed pitch bits belong
+            // to the player, so they survive
+            let preserved = memory[0x11] & 0b11;
+
+            memory[..dynamic_length].copy_from_slice(&self.original_memory[..dynamic_length]);
+            memory[0x11] = (memory[0x11] & !0b11) | preserved;
+        }
+
+        self.call_stack = Stack {
+            top_of_frame: 0,
+            stack: Vec::new(),
+        };
+
+        self.write_capabilities();
+
+        self.ip = self.header.pc_start as u32;
+        self.call_main_routine();
+        self.font = 1;
+
+        // whatever the game had redirected into memory is gone with it,
+        // and the screen starts over unsplit - the undo states are from the
+        // game before, so undoing can't take us back into it
+        self.streams.memory.clear();
+        self.undo_states.clear();
+        self.sound_interrupt = None;
+        self.screen = Screen::new(self.header.version);
+        self.graphics = Graphics::new(&self.zinterface.capabilities());
+
+        if let Some(ref resources) = self.resources {
+            self.graphics.load_pictures(resources);
+        }
+        self.zinterface.split_window(self.screen.top, 0);
+        self.zinterface.buffer_mode(true);
+
+        self.state = MachineState::Running;
+    }
+
+    // keeps a copy of dynamic memory and the stack - pc is where to pick
+    // back up from, like a save
+    //
+    // returns false if undo is turned off
+    pub(crate) fn save_undo(&mut self, pc: u32) -> bool {
+        if self.undo_depth == 0 {
+            return false;
+        }
+
+        while self.undo_states.len() >= self.undo_depth {
+            self.undo_states.pop_front();
+        }
+
+        let dynamic_length = self.header.static_memory_start_location as usize;
+
+        self.undo_states.push_back(UndoState {
+            dynamic_memory: self.memory.borrow()[..dynamic_length].to_vec(),
+            call_stack: self.call_stack.clone(),
+            pc,
+        });
+
+        true
+    }
+
+    // puts back the last undo state, and hands back its pc less back ( see
+    // restore_save ) - a state that can't go back that far is left alone
+    pub(crate) fn restore_undo(&mut self, back: u32) -> Option<u32> {
+        let ip = self.undo_states.back()?.pc.checked_sub(back)?;
+        let state = self.undo_states.pop_back()?;
+
+        {
+            let mut memory = self.memory.borrow_mut();
+
+            // see restore_save
+            let preserved = memory[0x11] & 0b11;
+
+            memory[..state.dynamic_memory.len()].copy_from_slice(&state.dynamic_memory);
+            memory[0x11] = (memory[0x11] & !0b11) | preserved;
+        }
+
+        self.call_stack = state.call_stack;
+
+        Some(ip)
+    }
+
+    // the interpreter's own undo, for versions without save_undo - sread keeps
+    // an undo state from right before it asks for a command, so the last
+    // one is this turn, and the one before that is the turn to go back to
+    fn undo_last_turn(&mut self) {
+        // the prompt is the game's, so we only know it from what it printed
+        let prompt = self.line.clone();
+
+        if self.undo_states.len() < 2 {
+            self.zinterface
+                .print_to_main("[There's nothing to undo.]\n");
+        } else {
+            self.undo_states.pop_back();
+            self.zinterface.print_to_main("[Previous turn undone.]\n");
+        }
+
+        // these point at the sread itself, so it runs again ( and keeps
+        // another undo state, for the turn before ) - with nothing to undo
+        // that's just this turn's sread over again
+        if let Some(pc) = self.restore_undo(0) {
+            self.ip = pc;
+            self.line.clear();
+            self.undone_prompt = Some(prompt);
+            self.state = MachineState::Running;
+        } else {
+            // undo is off, so the same read is still waiting
+            self.zinterface.print_to_main(&prompt);
+        }
+    }
+
     //actually executes the instruction
-    fn execute_instruction(&mut self, op_code: &mut OpCode<T>) {
-        op_code.execute(self);
+    fn execute_instruction(&mut self, op_code: &mut OpCode<T>) -> InstructionResult<()> {
+        op_code.execute(self)?;
 
         // technically, store and branch cannot happen at the same time
         // i will not make any enforcement here because the zmachine makes no such
@@ -288,8 +1025,8 @@ impl<T: ZInterface> ZMachine<T> {
             // after execute
 
             let view = self.get_frame_view();
-            let destination = view.read_at_head(op_code.read_bytes);
-            self.store_variable(destination, op_code.result);
+            let destination = view.read_at_head(op_code.read_bytes)?;
+            self.store_variable(destination, op_code.result)?;
             op_code.read_bytes += 1
         }
 
@@ -299,20 +1036,43 @@ impl<T: ZInterface> ZMachine<T> {
 
         match op_code.branch {
             true => {
-                self.handle_branch(op_code);
+                self.handle_branch(op_code)?;
             }
             false => {
                 // println!("code does not branch");
                 self.ip += op_code.read_bytes;
             }
         }
+
+        Ok(())
     }
 
     pub fn get_version(&self) -> u8 {
         self.header.version
     }
 
-    pub fn get_abbreviations_view(&self) -> MemoryView {
+    // packed addresses are how routines and strings are addressed past
+    // 64k - the scale ( and offset ) depends on the version
+    pub(crate) fn unpack_routine_address(&self, packed: u16) -> u32 {
+        self.unpack_address(packed, self.header.routines_offset)
+    }
+
+    pub(crate) fn unpack_string_address(&self, packed: u16) -> u32 {
+        self.unpack_address(packed, self.header.strings_offset)
+    }
+
+    fn unpack_address(&self, packed: u16, offset: u16) -> u32 {
+        let packed = packed as u32;
+
+        match self.header.version {
+            1..=3 => packed * 2,
+            4..=5 => packed * 4,
+            6..=7 => packed * 4 + (offset as u32) * 8,
+            _ => packed * 8,
+        }
+    }
+
+    pub(crate) fn get_abbreviations_view(&self) -> MemoryView {
         MemoryView {
             memory: self.memory.clone(),
 
@@ -323,16 +1083,62 @@ impl<T: ZInterface> ZMachine<T> {
         }
     }
 
-    pub fn get_dictionary_view(&self) -> MemoryView {
+    pub(crate) fn get_dictionary_view(&self) -> MemoryView {
         MemoryView {
             memory: self.memory.clone(),
             pointer: self.header.dictionary_location as u32,
         }
     }
 
+    // every word in the dictionary, as text - the cli uses these to finish
+    // words off for the player
+    //
+    // a dictionary that runs off the end of memory just has nothing to offer
+    pub fn dictionary_words(&self) -> Vec<String> {
+        self.read_dictionary_words().unwrap_or_default()
+    }
+
+    fn read_dictionary_words(&self) -> InstructionResult<Vec<String>> {
+        let view = self.get_dictionary_view();
+        let abbreviations_view = self.get_abbreviations_view();
+
+        // the separators come first, then the entry length and count
+        let separators = view.read_at_head(0)? as u32;
+        let entry_length = view.read_at_head(separators + 1)? as u32;
+
+        // a negative count only means the entries aren't sorted
+        let entries = (view.read_u16_at_head(separators + 2)? as i16).unsigned_abs() as u32;
+
+        (0..entries)
+            .map(|i| {
+                let offset = separators + 4 + i * entry_length;
+                let word = ZString::create(offset, &view, &abbreviations_view, &self.charset)?;
+
+                Ok(word.to_string())
+            })
+            .collect()
+    }
+
+    // the release and serial number, which tell stories ( and releases of
+    // the same story ) apart - it's only made of letters, numbers and
+    // dashes, so it can go in a file name
+    pub fn story_id(&self) -> String {
+        let id = quetzal::StoryId::from_memory(&self.original_memory);
+        let serial: String = id
+            .serial
+            .iter()
+            .map(|&x| match x {
+                b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' => x as char,
+                _ => '-',
+            })
+            .collect();
+
+        format!("{}-{}", id.release, serial)
+    }
+
     // gets a view into the current program
     // stack
-    pub fn get_frame_view(&self) -> MemoryView {
+    pub(crate) fn get_frame_view(&self) -> MemoryView {
         MemoryView {
             memory: self.memory.clone(),
 
@@ -343,7 +1149,7 @@ impl<T: ZInterface> ZMachine<T> {
         }
     }
 
-    pub fn get_global_variables_view(&self) -> GlobalVariablesView {
+    pub(crate) fn get_global_variables_view(&self) -> GlobalVariablesView {
         GlobalVariablesView {
             view: MemoryView {
                 memory: self.memory.clone(),
@@ -362,7 +1168,7 @@ impl<T: ZInterface> ZMachine<T> {
     }
 
     // the memory view for the whole env
-    pub fn get_memory_view(&self) -> MemoryView {
+    pub(crate) fn get_memory_view(&self) -> MemoryView {
         MemoryView {
             memory: self.memory.clone(),
             // the start of memory
@@ -373,14 +1179,21 @@ impl<T: ZInterface> ZMachine<T> {
     // object id is a u16 because in future versions, there can be up
     // to 65k objects. id rather standardize that ahead of time because
     // it will be all over the instruction set
-    pub fn get_object_view(&self, object_id: u16) -> ObjectView {
-        // we will have to change the values for this in the future when we support
-        // newer versions of the ZMachine ( particularly version 4 )
+    pub(crate) fn get_object_view(&self, object_id: u16) -> InstructionResult<ObjectView> {
+        // objects grew in version 4 - the attributes went from 4 bytes to 6,
+        // and the relatives from a byte to a word, so there can be more of
+        // them. there are more properties too, so there are more defaults
+        // before the objects start
+        let (object_length, property_defaults_length, attributes_length, related_obj_length) =
+            match self.header.version {
+                1..=3 => (9, 62, 4, 1),
+                _ => (14, 126, 6, 2),
+            };
 
-        // we should really be getting this from the header - ill save it 
-        // for when we move to version 4+ (that's when this changes)
-        let object_length = 9;
-        let property_defaults_length = 62;
+        // objects are numbered from 1, and have to fit in memory
+        if object_id == 0 || (related_obj_length == 1 && object_id > 255) {
+            return Err(ErrorKind::InvalidObject { object: object_id });
+        }
 
         // calculate offset and object location
         let offset = (object_id as u32 - 1) * object_length;
@@ -388,9 +1201,12 @@ impl<T: ZInterface> ZMachine<T> {
         let object_location =
             self.header.object_table_location as u32 + property_defaults_length as u32 + offset;
 
-        ObjectView {
-            object_id: object_id,
-            attributes_length: 4,
+        self.check_address(object_location + object_length - 1)?;
+
+        Ok(ObjectView {
+            object_id,
+            version: self.header.version,
+            attributes_length,
             defaults_view: MemoryView {
                 memory: self.memory.clone(),
                 pointer: self.header.object_table_location as u32,
@@ -405,20 +1221,25 @@ impl<T: ZInterface> ZMachine<T> {
                 // the size of properties ahead of time
                 pointer: object_location,
             },
-            // 3 relatives, 1 byte each
-            related_obj_length: 1,
-        }
+            // 3 relatives, parent, sibling and child
+            related_obj_length,
+        })
+    }
+
+    // makes sure an address the story handed us is actually in memory
+    pub(crate) fn check_address(&self, address: u32) -> InstructionResult<()> {
+        self.get_memory_view().check_address(address)
     }
 
     // handle a branch opcode - this happens after instructions are executed
-    pub fn handle_branch(&mut self, op_code: &mut OpCode<T>) {
+    pub(crate) fn handle_branch(&mut self, op_code: &mut OpCode<T>) -> InstructionResult<()> {
         let view = self.get_frame_view();
         let condition = op_code.result;
         let true_mask = 0b10000000;
-        let branch_on_true = (view.read_at_head(op_code.read_bytes) & true_mask) != 0;
+        let branch_on_true = (view.read_at_head(op_code.read_bytes)? & true_mask) != 0;
 
         let two_bits_mask = 0b01000000;
-        let one_bit = (view.read_at_head(op_code.read_bytes) & two_bits_mask) != 0;
+        let one_bit = (view.read_at_head(op_code.read_bytes)? & two_bits_mask) != 0;
 
         // we branch when the value is non-zero;
         // this is helpful for get child and other branches which
@@ -440,12 +1261,12 @@ impl<T: ZInterface> ZMachine<T> {
                     // it can be from 0 to 63
                     (
                         true,
-                        (view.read_at_head(op_code.read_bytes) & 0b00111111) as i16,
+                        (view.read_at_head(op_code.read_bytes)? & 0b00111111) as i16,
                     )
                 }
                 false => {
                     let mut fourteen_bit =
-                        view.read_u16_at_head(op_code.read_bytes) & 0b0011111111111111;
+                        view.read_u16_at_head(op_code.read_bytes)? & 0b0011111111111111;
 
                     //println!( "fourteen bit is:{:b}", fourteen_bit );
 
@@ -481,13 +1302,13 @@ impl<T: ZInterface> ZMachine<T> {
                 (true, 0) => {
                     let mut rfalse = OpCode::form_rfalse();
                     //println!("returning from branch false");
-                    self.execute_instruction(&mut rfalse);
+                    self.execute_instruction(&mut rfalse)?;
                 }
 
                 (true, 1) => {
                     let mut rtrue = OpCode::form_rtrue();
                     //println!("returning from branch true");
-                    self.execute_instruction(&mut rtrue);
+                    self.execute_instruction(&mut rtrue)?;
                 }
 
                 // branch address is defined as "address after branch data",
@@ -513,10 +1334,37 @@ impl<T: ZInterface> ZMachine<T> {
 
             //print!("branch failed, moving to : ");
         }
+
+        Ok(())
     }
 
     //grabs the next instruction and executes it
-    pub fn next_instruction(&mut self) {
+    //
+    // if anything goes wrong, the error says where, and the machine is
+    // stopped - there's no picking up from a broken instruction
+    pub(crate) fn next_instruction(&mut self) -> Result<(), ZMachineError> {
+        self.check_sound_interrupt();
+
+        let ip = self.ip;
+        let mut name = "unknown";
+
+        let result = self.decode_and_execute(&mut name);
+
+        result.map_err(|kind| {
+            self.state = MachineState::Stopped;
+
+            ZMachineError {
+                kind,
+                pc: ip,
+                opcode: name,
+            }
+        })
+    }
+
+    fn decode_and_execute(&mut self, name: &mut &'static str) -> InstructionResult<()> {
+        // the instruction (and at least one operand byte) has to be in memory
+        self.check_address(self.ip + 1)?;
+
         // a non-mutable memory view,
         // reads from the same memory as zmachine
         let view = self.get_frame_view();
@@ -527,15 +1375,16 @@ impl<T: ZInterface> ZMachine<T> {
         //
         // note that not all instructions use the top two bytes
 
-        let word = view.peek_at_instruction();
-        let mut op_code = OpCode::form_opcode(word);
+        let word = view.peek_at_instruction()?;
+        let mut op_code = OpCode::form_opcode(word, self.header.version);
 
         op_code.ip = self.ip;
         // println!( "ip: {:x}", op_code.ip );
 
         {
             let code_ref = &mut op_code;
-            OpCode::assign_instruction(code_ref);
+            OpCode::assign_instruction(code_ref)?;
+            *name = return_name(code_ref);
         }
 
         // we get a mutable reference to the call stack
@@ -547,10 +1396,10 @@ impl<T: ZInterface> ZMachine<T> {
             let stack = &mut self.call_stack;
 
             // have the view.
-            op_code.read_variables(view, globals, stack);
+            op_code.read_variables(view, globals, stack)?;
         }
 
-        self.execute_instruction(&mut op_code);
+        self.execute_instruction(&mut op_code)
     }
 
     // this JUST reads a variable, but does not modify the stack in any way
@@ -559,7 +1408,7 @@ impl<T: ZInterface> ZMachine<T> {
 
     // i thought this would be non-mutating at first but as it turns out, the
     // stack (or stack pointer) is mutated by reading the stack pointer
-    pub fn read_variable(&mut self, address: u8) -> u16 {
+    pub(crate) fn read_variable(&mut self, address: u8) -> InstructionResult<u16> {
         match address {
             // so it turns out that the stack does "pop" the value
             // in most z-machine interpreters, but what is different is
@@ -573,10 +1422,7 @@ impl<T: ZInterface> ZMachine<T> {
             // w/ a pointer
 
             // 0, its the stack, pop it and return
-            0 => match self.call_stack.stack.pop() {
-                Some(value) => value,
-                None => panic!("stack underflow!"),
-            },
+            0 => self.call_stack.pop(),
             // 1 to 15, its a local
             i @ 0x01..=0x0f => self.call_stack.get_local_variable(i),
             // 16 to 255, it's a global variable.
@@ -589,46 +1435,120 @@ impl<T: ZInterface> ZMachine<T> {
 
     // the machine always stores variables during or at the end of instruction calls,
     // and accesses variables before processing the call;
-    pub fn store_variable(&mut self, address: u8, value: u16) {
+    pub(crate) fn store_variable(&mut self, address: u8, value: u16) -> InstructionResult<()> {
         // println!( "storing: {} at {}", value, address );
 
         match address {
             0 => self.call_stack.stack.push(value),
-            index @ 0x01..=0x0f => self.call_stack.store_local_variable(index, value),
+            index @ 0x01..=0x0f => self.call_stack.store_local_variable(index, value)?,
             global @ 0x10..=0xff => {
                 // offset by 16 to get the global "index"
                 let index = global - 0x10;
                 self.get_global_variables_view()
-                    .write_global(index as u16, value);
+                    .write_global(index as u16, value)?;
             }
         }
+
+        Ok(())
     }
-    // wait for input, and on input, hand it to whatever code/op was waiting
-    // for it
-    pub fn wait_for_input(&mut self, callback: Rc<dyn Fn(String)>) {
-        let mut buf = String::new();
-        let result = match self.zinterface.read_next_line(&mut buf) {
-            Some(_) => {
-                callback(buf.to_string());
-                true
-            }
+    // runs instructions until the machine can't go any further on its own -
+    // it's either waiting for input, or it has stopped/is restarting
+    pub fn run(&mut self) -> Result<&MachineState, ZMachineError> {
+        while let MachineState::Running = self.state {
+            self.next_instruction()?;
+        }
+
+        Ok(&self.state)
+    }
+
+    pub fn is_waiting_for_input(&self) -> bool {
+        match self.state {
+            MachineState::TakingInput { .. } => true,
             _ => false,
+        }
+    }
+
+    // hands a line of input to whatever code/op is waiting for it, and
+    // sets the machine running again. returns false if nothing was waiting
+    pub fn provide_input(&mut self, input: &str) -> bool {
+        let (callback, store, line) = match self.state {
+            MachineState::TakingInput {
+                ref callback,
+                store,
+                line,
+                ..
+            } => (callback.clone(), store, line),
+            _ => return false,
         };
 
-        if result {
-            self.state = MachineState::Running;
+        // games before version 5 have no undo of their own, so we catch
+        // the command before the game ever sees it
+        if line && self.header.version <= 4 && input.trim().eq_ignore_ascii_case("undo") {
+            self.undo_last_turn();
+            return true;
+        }
+
+        // the terminal echoed the player's newline, so the next thing
+        // printed starts a new line
+        if line {
+            self.line.clear();
+        }
+
+        // the player's commands go in the transcript too, since
+        // the screen only has them because the terminal echoed them
+        if line {
+            self.update_transcript();
+
+            if self.streams.transcript {
+                self.zinterface
+                    .print_to_transcript(&format!("{}\n", input.trim_end()));
+            }
+        }
+
+        let result = callback(input.to_string());
+
+        // the instruction already stored a placeholder here when it ran,
+        // so this overwrites it ( and can't fail where that didn't )
+        if let Some(variable) = store {
+            let _ = self.write_variable_in_place(variable, result);
+        }
+
+        self.state = MachineState::Running;
+
+        true
+    }
+
+    // wait for input from the interface, and on input, hand it to
+    // whatever code/op was waiting for it
+    pub fn wait_for_input(&mut self) {
+        let max_length = match self.state {
+            MachineState::TakingInput { max_length, .. } => max_length as usize,
+            _ => return,
+        };
+
+        let mut buf = String::new();
+
+        if self
+            .zinterface
+            .read_next_line(&mut buf, max_length)
+            .is_some()
+        {
+            self.provide_input(&buf);
         }
     }
 
     // this writes a variable in place - it really only specializes on the stack,
     // otherwise it wraps store_variable
-    pub fn write_variable_in_place(&mut self, address: u8, value: u16) {
+    pub(crate) fn write_variable_in_place(
+        &mut self,
+        address: u8,
+        value: u16,
+    ) -> InstructionResult<()> {
         match address {
-            0 => {
-                let last = self.call_stack.stack.len() - 1;
-                self.call_stack.stack[last] = value;
-            }
-            _ => self.store_variable(address, value),
+            0 => *self.call_stack.top_mut()? = value,
+            _ => self.store_variable(address, value)?,
         }
+
+        Ok(())
     }
 }
@@ -1,4 +1,7 @@
+use super::error::*;
+use super::header::Header;
 use super::memory_view::*;
+use std::cmp;
 use std::fmt;
 
 // ah, the ZString!
@@ -14,6 +17,7 @@ use std::fmt;
 // encode abbreviations (for further compression), menu clicks,
 // single clicks and double clicks! ( i don't think this is used output wise, however )
 
+#[derive(Clone, Copy)]
 pub enum Alphabet {
     A0,
     A1,
@@ -21,6 +25,15 @@ pub enum Alphabet {
 }
 
 impl Alphabet {
+    // where the alphabet is in the alphabet table
+    fn index(&self) -> usize {
+        match self {
+            &Alphabet::A0 => 0,
+            &Alphabet::A1 => 1,
+            &Alphabet::A2 => 2,
+        }
+    }
+
     fn next_alphabet(&self, shift_char: u8) -> Alphabet {
         match (self, shift_char) {
             (&Alphabet::A0, 4) => Alphabet::A1,
@@ -95,6 +108,117 @@ impl ZWord {
     }
 }
 
+// the characters a story's text is made of
+//
+// the alphabets say which zscii character each z-character stands for, and
+// the unicode table says what zscii 155 and up are ( there's a default table
+// of accented latin characters ). from version 5 a story can bring its own of
+// either, which is how games in other languages get their characters
+#[derive(Clone)]
+pub struct Charset {
+    // the rules for turning z-characters into text changed a little over
+    // the first few versions, so we keep it around
+    pub version: u8,
+    // z-characters 6 to 31 in A0, A1 and A2, as zscii
+    pub alphabets: [[u8; 26]; 3],
+    pub unicode: Vec<char>,
+}
+
+// the first two characters of A2 are never used, a 6 there starts a zscii
+// code, and a 7 is always a new line
+const DEFAULT_ALPHABETS: [&[u8; 26]; 3] = [
+    b"abcdefghijklmnopqrstuvwxyz",
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+    b" \r0123456789.,!?_#'\"/\\-:()",
+];
+
+// version 1 has a z-character for a new line, so it has room for a < in A2
+const V1_A2_ALPHABET: &[u8; 26] = b" 0123456789.,!?_#'\"/\\<-:()";
+
+// the default unicode table, for zscii 155 to 223
+const DEFAULT_UNICODE_TABLE: [char; 69] = [
+    'ä', 'ö', 'ü', 'Ä', 'Ö', 'Ü', 'ß', '»', '«', 'ë', 'ï', 'ÿ', 'Ë', 'Ï', 'á', 'é', 'í', 'ó', 'ú',
+    'ý', 'Á', 'É', 'Í', 'Ó', 'Ú', 'Ý', 'à', 'è', 'ì', 'ò', 'ù', 'À', 'È', 'Ì', 'Ò', 'Ù', 'â', 'ê',
+    'î', 'ô', 'û', 'Â', 'Ê', 'Î', 'Ô', 'Û', 'å', 'Å', 'ø', 'Ø', 'ã', 'ñ', 'õ', 'Ã', 'Ñ', 'Õ', 'æ',
+    'Æ', 'ç', 'Ç', 'þ', 'ð', 'Þ', 'Ð', '£', 'œ', 'Œ', '¡', '¿',
+];
+
+impl Charset {
+    pub fn default_table(version: u8) -> Charset {
+        Charset {
+            version,
+            alphabets: [
+                *DEFAULT_ALPHABETS[0],
+                *DEFAULT_ALPHABETS[1],
+                match version {
+                    1 => *V1_A2_ALPHABET,
+                    _ => *DEFAULT_ALPHABETS[2],
+                },
+            ],
+            unicode: DEFAULT_UNICODE_TABLE.to_vec(),
+        }
+    }
+
+    // reads the story's own tables, where it has them
+    pub fn create(view: &MemoryView, header: &Header) -> Charset {
+        let mut charset = Charset::default_table(header.version);
+
+        if header.version < 5 {
+            return charset;
+        }
+
+        let memory_length = view.memory.borrow().len() as u32;
+
+        // the alphabet table is just the three alphabets one after the other
+        let table = header.alphabet_location as u32;
+
+        if table != 0 && table + 78 <= memory_length {
+            for (i, alphabet) in charset.alphabets.iter_mut().enumerate() {
+                for (j, ch) in alphabet.iter_mut().enumerate() {
+                    // checked just above
+                    *ch = view.read_at(table + (i * 26 + j) as u32).unwrap_or(*ch);
+                }
+            }
+        }
+
+        if let Some(unicode) = Charset::read_unicode_table(view, header.extension_location) {
+            charset.unicode = unicode;
+        }
+
+        charset
+    }
+
+    // the header extension is a word saying how many words follow, and
+    // the third of those is the address of the unicode table. the table is a
+    // byte saying how many characters there are, then each character as a word
+    fn read_unicode_table(view: &MemoryView, extension_location: u16) -> Option<Vec<char>> {
+        let memory_length = view.memory.borrow().len() as u32;
+        let extension = extension_location as u32;
+
+        if extension == 0 || extension + 8 > memory_length || view.read_u16_at(extension).ok()? < 3
+        {
+            return None;
+        }
+
+        let table = view.read_u16_at(extension + 6).ok()? as u32;
+
+        if table == 0 || table >= memory_length {
+            return None;
+        }
+
+        // there's only room in zscii for 97 of them ( 155 to 251 )
+        let count = cmp::min(view.read_at(table).ok()? as u32, 97);
+        let count = cmp::min(count, (memory_length - table - 1) / 2);
+
+        (0..count)
+            .map(|i| {
+                let ch = view.read_u16_at(table + 1 + i * 2).ok()? as u32;
+                Some(std::char::from_u32(ch).unwrap_or('?'))
+            })
+            .collect()
+    }
+}
+
 pub struct ZString {
     // the "array" of ZSCII codes
     bytes: Vec<u8>,
@@ -106,7 +230,12 @@ pub struct ZString {
 
 impl ZString {
     // create a zstring, located at offset from memory view's pointer
-    pub fn create(offset: u32, view: &MemoryView, abbreviations_view: &MemoryView) -> ZString {
+    pub fn create(
+        offset: u32,
+        view: &MemoryView,
+        abbreviations_view: &MemoryView,
+        charset: &Charset,
+    ) -> InstructionResult<ZString> {
         // we legitimately don't know the length of this
         let mut bytes = Vec::new();
 
@@ -116,9 +245,16 @@ impl ZString {
         // println!("pointer: {:x}", view.pointer + pointer);
 
         let mask: u16 = 0x8000;
+        let memory_length = view.memory.borrow().len() as u32;
 
         loop {
-            let byte: u16 = view.read_u16_at_head(pointer);
+            // a string that never ends just stops at the end of memory,
+            // rather than reading off of it
+            if view.pointer + pointer + 1 >= memory_length {
+                break;
+            }
+
+            let byte: u16 = view.read_u16_at_head(pointer)?;
             bytes.push(byte);
 
             if mask & byte > 0 {
@@ -144,9 +280,14 @@ impl ZString {
 
         // println!("num of chars: {}", z_string.bytes.len());
 
-        ZString::decode_into_string(&z_string.bytes, &mut z_string.string, abbreviations_view);
+        ZString::decode_into_string(
+            &z_string.bytes,
+            &mut z_string.string,
+            abbreviations_view,
+            charset,
+        )?;
 
-        z_string
+        Ok(z_string)
     }
 
     // decodes the series of zhcars into destination;
@@ -155,10 +296,16 @@ impl ZString {
         zchars: &Vec<u8>,
         destination: &mut String,
         abbreviations_view: &MemoryView,
-    ) {
+        charset: &Charset,
+    ) -> InstructionResult<()> {
+        let version = charset.version;
+
         // we always start out with A0
         // this can shift for one character only, so we have to keep track of it
         let mut alphabet = Alphabet::A0;
+        // in versions 1 and 2 the alphabet can also be shifted until further
+        // notice ( a shift lock ), this is the one we go back to after each character
+        let mut locked_alphabet = Alphabet::A0;
         // this we also have to keep track of as its multi-char
         let mut printing_big_char = BigChar::None;
         // this we also have to keep track of as its multi-char (but is an abbreviation)
@@ -190,31 +337,50 @@ impl ZString {
                     let big_char = ((upper as u16) << 5 & 0b0000001111100000)
                         | ((lower as u16) & 0b0000000000011111);
 
-                    match ZString::decode_zscii(big_char) {
+                    match ZString::decode_zscii(big_char, charset) {
                         Some(x) => destination.push(x),
                         //literally, do nothing
                         _ => {}
                     }
 
                     printing_big_char = BigChar::None;
-                    alphabet = Alphabet::A0;
+                    alphabet = locked_alphabet;
+                }
+
+                // version 1 has no abbreviations, 1 is a new line instead
+                (1, _, _, &Abbreviation::None) if version == 1 => {
+                    destination.push('\n');
+                    alphabet = locked_alphabet;
                 }
 
                 //if we have a character between 1 and 3, start building an abbreviation
-                //if we aren't already.
-                (z @ 1..=3, _, _, &Abbreviation::None) => {
+                //if we aren't already. version 2 only has the one bank of them, 1
+                (z @ 1..=3, _, _, &Abbreviation::None) if version >= 3 || z == 1 => {
                     printing_abbreviation = Abbreviation::Partial { z: z }
                 }
 
                 //if we are building an abbreviation, the next # is the index
                 //find the abbreviation and print
                 (i, _, _, &Abbreviation::Partial { z }) => {
-                    let string =
-                        format!("{}", ZString::find_abbreviation(i, z, abbreviations_view));
+                    let string = format!(
+                        "{}",
+                        ZString::find_abbreviation(i, z, abbreviations_view, charset)?
+                    );
                     destination.push_str(&string);
                     printing_abbreviation = Abbreviation::None;
                     //im not sure if you are supposed to do anything w/alphabet here
-                    alphabet = Alphabet::A0;
+                    alphabet = locked_alphabet;
+                }
+
+                // in versions 1 and 2, 2 and 3 shift for the next char only ( the
+                // same way 4 and 5 do later on ), and 4 and 5 lock the shift
+                (z @ 2..=3, _, _, &Abbreviation::None) => {
+                    alphabet = locked_alphabet.next_alphabet(z + 2)
+                }
+
+                (z @ 4..=5, _, _, &Abbreviation::None) if version <= 2 => {
+                    locked_alphabet = locked_alphabet.next_alphabet(z);
+                    alphabet = locked_alphabet;
                 }
 
                 //if we have a character between 4 and 5, switch the alphabet
@@ -223,103 +389,37 @@ impl ZString {
 
                 //the default case, actually print the string
                 (x, _, _, _) => {
-                    destination.push(ZString::decode_char(x, &alphabet));
-                    alphabet = Alphabet::A0;
+                    destination.push(ZString::decode_char(x, &alphabet, charset));
+                    alphabet = locked_alphabet;
                 }
             }
         }
+
+        Ok(())
     }
 
     // chars are runes in rust, sortof, kind of
-    // in version 5 and up, this table can change based on the header,
-    // and we will have to deal with that
     //
-    // ( i think its mostly for translation purposes )
-    pub fn decode_char(ch: u8, alphabet: &Alphabet) -> char {
+    // z-characters 6 to 31 are looked up in the alphabet's table, which
+    // in version 5 and up can be the story's own ( see Charset ) - the
+    // tables are in zscii, so we then look that up like any other
+    pub fn decode_char(ch: u8, alphabet: &Alphabet, charset: &Charset) -> char {
         match (ch, alphabet) {
-            (0x6, &Alphabet::A0) => 'a',
-            (0x7, &Alphabet::A0) => 'b',
-            (0x8, &Alphabet::A0) => 'c',
-            (0x9, &Alphabet::A0) => 'd',
-            (0xA, &Alphabet::A0) => 'e',
-            (0xB, &Alphabet::A0) => 'f',
-            (0xC, &Alphabet::A0) => 'g',
-            (0xD, &Alphabet::A0) => 'h',
-            (0xE, &Alphabet::A0) => 'i',
-            (0xF, &Alphabet::A0) => 'j',
-            (0x10, &Alphabet::A0) => 'k',
-            (0x11, &Alphabet::A0) => 'l',
-            (0x12, &Alphabet::A0) => 'm',
-            (0x13, &Alphabet::A0) => 'n',
-            (0x14, &Alphabet::A0) => 'o',
-            (0x15, &Alphabet::A0) => 'p',
-            (0x16, &Alphabet::A0) => 'q',
-            (0x17, &Alphabet::A0) => 'r',
-            (0x18, &Alphabet::A0) => 's',
-            (0x19, &Alphabet::A0) => 't',
-            (0x1A, &Alphabet::A0) => 'u',
-            (0x1B, &Alphabet::A0) => 'v',
-            (0x1C, &Alphabet::A0) => 'w',
-            (0x1D, &Alphabet::A0) => 'x',
-            (0x1E, &Alphabet::A0) => 'y',
-            (0x1F, &Alphabet::A0) => 'z',
-            (0x6, &Alphabet::A1) => 'A',
-            (0x7, &Alphabet::A1) => 'B',
-            (0x8, &Alphabet::A1) => 'C',
-            (0x9, &Alphabet::A1) => 'D',
-            (0xA, &Alphabet::A1) => 'E',
-            (0xB, &Alphabet::A1) => 'F',
-            (0xC, &Alphabet::A1) => 'G',
-            (0xD, &Alphabet::A1) => 'H',
-            (0xE, &Alphabet::A1) => 'I',
-            (0xF, &Alphabet::A1) => 'J',
-            (0x10, &Alphabet::A1) => 'K',
-            (0x11, &Alphabet::A1) => 'L',
-            (0x12, &Alphabet::A1) => 'M',
-            (0x13, &Alphabet::A1) => 'N',
-            (0x14, &Alphabet::A1) => 'O',
-            (0x15, &Alphabet::A1) => 'P',
-            (0x16, &Alphabet::A1) => 'Q',
-            (0x17, &Alphabet::A1) => 'R',
-            (0x18, &Alphabet::A1) => 'S',
-            (0x19, &Alphabet::A1) => 'T',
-            (0x1A, &Alphabet::A1) => 'U',
-            (0x1B, &Alphabet::A1) => 'V',
-            (0x1C, &Alphabet::A1) => 'W',
-            (0x1D, &Alphabet::A1) => 'X',
-            (0x1E, &Alphabet::A1) => 'Y',
-            (0x1F, &Alphabet::A1) => 'Z',
-            (0x7, &Alphabet::A2) => '\n',
-            (0x8, &Alphabet::A2) => '0',
-            (0x9, &Alphabet::A2) => '1',
-            (0xA, &Alphabet::A2) => '2',
-            (0xB, &Alphabet::A2) => '3',
-            (0xC, &Alphabet::A2) => '4',
-            (0xD, &Alphabet::A2) => '5',
-            (0xE, &Alphabet::A2) => '6',
-            (0xF, &Alphabet::A2) => '7',
-            (0x10, &Alphabet::A2) => '8',
-            (0x11, &Alphabet::A2) => '9',
-            (0x12, &Alphabet::A2) => '.',
-            (0x13, &Alphabet::A2) => ',',
-            (0x14, &Alphabet::A2) => '!',
-            (0x15, &Alphabet::A2) => '?',
-            (0x16, &Alphabet::A2) => '_',
-            (0x17, &Alphabet::A2) => '#',
-            (0x18, &Alphabet::A2) => '\'',
-            (0x19, &Alphabet::A2) => '"',
-            (0x1A, &Alphabet::A2) => '/',
-            (0x1B, &Alphabet::A2) => '\\',
-            (0x1C, &Alphabet::A2) => '-',
-            (0x1D, &Alphabet::A2) => ':',
-            (0x1E, &Alphabet::A2) => '(',
-            (0x1F, &Alphabet::A2) => ')',
             (0, _) => ' ',
+            // this is always a new line, whatever the table says ( except
+            // in version 1, which has its own )
+            (0x7, &Alphabet::A2) if charset.version >= 2 => '\n',
+            (6..=31, _) => {
+                let zscii = charset.alphabets[alphabet.index()][ch as usize - 6];
+                ZString::decode_zscii(zscii as u16, charset).unwrap_or('?')
+            }
             _ => panic!("could not match character:{}, alphabet:{}", ch, alphabet),
         }
     }
 
-    pub fn encode_word(word: &str, version: u8) -> ZWord {
+    // encodes a word ( in zscii ) the way the dictionary has it - cut off or
+    // padded out to a fixed number of z-characters
+    pub fn encode_word(word: &[u8], version: u8, charset: &Charset) -> ZWord {
         let len = match version {
             1..=3 => 6,
             4..=8 => 9,
@@ -328,266 +428,60 @@ impl ZString {
 
         let mut cache: Vec<u8> = Vec::with_capacity(len);
 
-        for ch in word.chars() {
-            if cache.len() == len {
-                break;
-            }
-
-            // this is a specific case where the last character added used a shift character,
-            // so we "jump" the exact equals
-
-            // the problem is, we need the length to be exactly what the dictionary expects
-            // or else the lookup tables
-            //
-            // instead of add that logic to each shift character, we just pop the last two
-            // characters off in that case
+        // versions 1 and 2 use 4 and 5 to lock the shift, so a shift for one
+        // character is 2 or 3 instead
+        let (shift_a1, shift_a2) = match version {
+            1..=2 => (0x2, 0x3),
+            _ => (0x4, 0x5),
+        };
 
-            if cache.len() > len {
-                cache.pop();
-                cache.pop();
+        for &zscii in word.iter() {
+            // characters can take more than one z-character, so this can go
+            // over - we cut it back down to size after
+            if cache.len() >= len {
                 break;
             }
 
-            match ch {
-                'a' => cache.push(0x6),
-                'b' => cache.push(0x7),
-                'c' => cache.push(0x8),
-                'd' => cache.push(0x9),
-                'e' => cache.push(0xA),
-                'f' => cache.push(0xB),
-                'g' => cache.push(0xC),
-                'h' => cache.push(0xD),
-                'i' => cache.push(0xE),
-                'j' => cache.push(0xF),
-                'k' => cache.push(0x10),
-                'l' => cache.push(0x11),
-                'm' => cache.push(0x12),
-                'n' => cache.push(0x13),
-                'o' => cache.push(0x14),
-                'p' => cache.push(0x15),
-                'q' => cache.push(0x16),
-                'r' => cache.push(0x17),
-                's' => cache.push(0x18),
-                't' => cache.push(0x19),
-                'u' => cache.push(0x1A),
-                'v' => cache.push(0x1B),
-                'w' => cache.push(0x1C),
-                'x' => cache.push(0x1D),
-                'y' => cache.push(0x1E),
-                'z' => cache.push(0x1F),
-                'A' => {
-                    cache.push(0x4);
-                    cache.push(0x6);
-                }
-                'B' => {
-                    cache.push(0x4);
-                    cache.push(0x7);
-                }
-                'C' => {
-                    cache.push(0x4);
-                    cache.push(0x8);
-                }
-                'D' => {
-                    cache.push(0x4);
-                    cache.push(0x9);
-                }
-                'E' => {
-                    cache.push(0x4);
-                    cache.push(0xA);
-                }
-                'F' => {
-                    cache.push(0x4);
-                    cache.push(0xB);
-                }
-                'G' => {
-                    cache.push(0x4);
-                    cache.push(0xC);
-                }
-                'H' => {
-                    cache.push(0x4);
-                    cache.push(0xD);
-                }
-                'I' => {
-                    cache.push(0x4);
-                    cache.push(0xE);
-                }
-                'J' => {
-                    cache.push(0x4);
-                    cache.push(0xF);
-                }
-                'K' => {
-                    cache.push(0x4);
-                    cache.push(0x10);
-                }
-                'L' => {
-                    cache.push(0x4);
-                    cache.push(0x11);
-                }
-                'M' => {
-                    cache.push(0x4);
-                    cache.push(0x12);
-                }
-                'N' => {
-                    cache.push(0x4);
-                    cache.push(0x13);
-                }
-                'O' => {
-                    cache.push(0x4);
-                    cache.push(0x14);
-                }
-                'P' => {
-                    cache.push(0x4);
-                    cache.push(0x15);
-                }
-                'Q' => {
-                    cache.push(0x4);
-                    cache.push(0x16);
-                }
-                'R' => {
-                    cache.push(0x4);
-                    cache.push(0x17);
-                }
-                'S' => {
-                    cache.push(0x4);
-                    cache.push(0x18);
-                }
-                'T' => {
-                    cache.push(0x4);
-                    cache.push(0x19);
-                }
-                'U' => {
-                    cache.push(0x4);
-                    cache.push(0x1A);
-                }
-                'V' => {
-                    cache.push(0x4);
-                    cache.push(0x1B);
-                }
-                'W' => {
-                    cache.push(0x4);
-                    cache.push(0x1C);
-                }
-                'X' => {
-                    cache.push(0x4);
-                    cache.push(0x1D);
-                }
-                'Y' => {
-                    cache.push(0x4);
-                    cache.push(0x1E);
-                }
-                'Z' => {
-                    cache.push(0x4);
-                    cache.push(0x1F);
-                }
-                '\n' => {
-                    cache.push(0x5);
+            // a2's first character is never looked up ( 6 starts a zscii
+            // code instead ), and from version 2 its second is always a new line
+            let position = |alphabet: usize| {
+                charset.alphabets[alphabet]
+                    .iter()
+                    .position(|&x| x == zscii)
+                    .filter(|&i| alphabet != 2 || i > 1 || (i == 1 && version == 1))
+                    .map(|i| i as u8 + 6)
+            };
+
+            match (zscii, position(0), position(1), position(2)) {
+                (b' ', _, _, _) => cache.push(0x0),
+                // version 1 has a z-character of its own for a new line
+                (13, _, _, _) if version == 1 => cache.push(0x1),
+                (13, _, _, _) => {
+                    cache.push(shift_a2);
                     cache.push(0x7);
                 }
-                '0' => {
-                    cache.push(0x5);
-                    cache.push(0x8);
-                }
-                '1' => {
-                    cache.push(0x5);
-                    cache.push(0x9);
-                }
-                '2' => {
-                    cache.push(0x5);
-                    cache.push(0xA);
-                }
-                '3' => {
-                    cache.push(0x5);
-                    cache.push(0xB);
-                }
-                '4' => {
-                    cache.push(0x5);
-                    cache.push(0xC);
-                }
-                '5' => {
-                    cache.push(0x5);
-                    cache.push(0xD);
-                }
-                '6' => {
-                    cache.push(0x5);
-                    cache.push(0xE);
-                }
-                '7' => {
-                    cache.push(0x5);
-                    cache.push(0xF);
-                }
-                '8' => {
-                    cache.push(0x5);
-                    cache.push(0x10);
+                (_, Some(z), _, _) => cache.push(z),
+                (_, _, Some(z), _) => {
+                    cache.push(shift_a1);
+                    cache.push(z);
                 }
-                '9' => {
-                    cache.push(0x5);
-                    cache.push(0x11);
+                (_, _, _, Some(z)) => {
+                    cache.push(shift_a2);
+                    cache.push(z);
                 }
-                '.' => {
-                    cache.push(0x5);
-                    cache.push(0x12);
-                }
-                ',' => {
-                    cache.push(0x5);
-                    cache.push(0x13);
-                }
-                '!' => {
-                    cache.push(0x5);
-                    cache.push(0x14);
-                }
-                '?' => {
-                    cache.push(0x5);
-                    cache.push(0x15);
-                }
-                '_' => {
-                    cache.push(0x5);
-                    cache.push(0x16);
-                }
-                '#' => {
-                    cache.push(0x5);
-                    cache.push(0x17);
-                }
-                '\'' => {
-                    cache.push(0x5);
-                    cache.push(0x18);
-                }
-                '"' => {
-                    cache.push(0x5);
-                    cache.push(0x19);
-                }
-                '/' => {
-                    cache.push(0x5);
-                    cache.push(0x1A);
-                }
-                '\\' => {
-                    cache.push(0x5);
-                    cache.push(0x1B);
-                }
-                '-' => {
-                    cache.push(0x5);
-                    cache.push(0x1C);
-                }
-                ':' => {
-                    cache.push(0x5);
-                    cache.push(0x1D);
-                }
-                '(' => {
-                    cache.push(0x5);
-                    cache.push(0x1E);
-                }
-                ')' => {
-                    cache.push(0x5);
-                    cache.push(0x1F);
-                }
-                // this wont be a panic because im kind of interested
-                // to see if anything would be encoded thats no,t
-                // in the above list
+                // anything that isn't in an alphabet is spelled out as its
+                // zscii code, 5 bits at a time, after a 6 in A2
                 _ => {
-                    println!("character not supported:{}", ch);
+                    cache.push(shift_a2);
+                    cache.push(0x6);
+                    cache.push(zscii >> 5);
+                    cache.push(zscii & 0x1F);
                 }
             }
         }
 
+        cache.truncate(len);
+
         // pad the remainder of the string out with the z-character 5 (shift)
         if cache.len() < len {
             let remainder = len - cache.len();
@@ -655,7 +549,20 @@ impl ZString {
     //
     // the real benefit is the non-latin characters, of course
 
-    pub fn decode_zscii(ch: u16) -> Option<char> {
+    // the other way around - the zscii code for a character, if there is one
+    pub fn encode_zscii(ch: char, charset: &Charset) -> Option<u16> {
+        match ch {
+            '\n' => Some(13),
+            c @ ' '..='~' => Some(c as u16),
+            c => charset
+                .unicode
+                .iter()
+                .position(|&x| x == c)
+                .map(|i| 155 + i as u16),
+        }
+    }
+
+    pub fn decode_zscii(ch: u16, charset: &Charset) -> Option<char> {
         match ch {
             // ascii
             0 => None,
@@ -663,82 +570,19 @@ impl ZString {
             11 => Some(' '),
             13 => Some('\n'),
             c @ 32..=126 => Some((c as u8) as char),
-            155 => Some('ä'),
-            156 => Some('ö'),
-            157 => Some('ü'),
-            158 => Some('Ä'),
-            159 => Some('Ö'),
-            160 => Some('Ü'),
-            161 => Some('ß'),
-            162 => Some('»'),
-            163 => Some('«'),
-            164 => Some('ë'),
-            165 => Some('ï'),
-            166 => Some('ÿ'),
-            167 => Some('Ë'),
-            168 => Some('Ï'),
-            169 => Some('á'),
-            170 => Some('é'),
-            171 => Some('í'),
-            172 => Some('ó'),
-            173 => Some('ú'),
-            174 => Some('ý'),
-            175 => Some('Á'),
-            176 => Some('É'),
-            177 => Some('Í'),
-            178 => Some('Ó'),
-            179 => Some('Ú'),
-            180 => Some('Ý'),
-            181 => Some('à'),
-            182 => Some('è'),
-            183 => Some('ì'),
-            184 => Some('ò'),
-            185 => Some('ù'),
-            186 => Some('À'),
-            187 => Some('È'),
-            188 => Some('Ì'),
-            189 => Some('Ò'),
-            190 => Some('Ù'),
-            191 => Some('â'),
-            192 => Some('ê'),
-            193 => Some('î'),
-            194 => Some('ô'),
-            195 => Some('û'),
-            196 => Some('Â'),
-            197 => Some('Ê'),
-            198 => Some('Î'),
-            199 => Some('Ô'),
-            200 => Some('Û'),
-            201 => Some('å'),
-            202 => Some('Å'),
-            203 => Some('ø'),
-            204 => Some('Ø'),
-            205 => Some('ã'),
-            206 => Some('ñ'),
-            207 => Some('õ'),
-            208 => Some('Ã'),
-            209 => Some('Ñ'),
-            210 => Some('Õ'),
-            211 => Some('æ'),
-            212 => Some('Æ'),
-            213 => Some('ç'),
-            214 => Some('Ç'),
-            215 => Some('þ'),
-            216 => Some('ð'),
-            217 => Some('Þ'),
-            218 => Some('Ð'),
-            219 => Some('£'),
-            220 => Some('œ'),
-            221 => Some('Œ'),
-            222 => Some('¡'),
-            223 => Some('¿'),
+            c @ 155..=251 => charset.unicode.get(c as usize - 155).cloned(),
             _ => None,
         }
     }
 
-    pub fn find_abbreviation(i: u8, z: u8, view: &MemoryView) -> ZString {
+    pub fn find_abbreviation(
+        i: u8,
+        z: u8,
+        view: &MemoryView,
+        charset: &Charset,
+    ) -> InstructionResult<ZString> {
         let address_offset = (32 * (z - 1) + i) * 2;
-        let packed_address = view.read_u16_at_head(address_offset as u32);
+        let packed_address = view.read_u16_at_head(address_offset as u32)?;
         let address = packed_address as u32 * 2;
 
         let mut new_view = view.clone();
@@ -749,7 +593,7 @@ impl ZString {
         // it would be fine if they did, anyway ( i'm certainly not preventing it,
         // and can't see any reason why it would fail )
 
-        ZString::create(0, &new_view, view)
+        ZString::create(0, &new_view, view, charset)
     }
 }
 
@@ -758,3 +602,256 @@ impl fmt::Display for ZString {
         write!(f, "{}", self.string)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn write_u16(data: &mut [u8], at: usize, value: u16) {
+        data[at] = (value >> 8) as u8;
+        data[at + 1] = value as u8;
+    }
+
+    fn view(data: Vec<u8>) -> MemoryView {
+        MemoryView {
+            memory: Rc::new(RefCell::new(data)),
+            pointer: 0,
+        }
+    }
+
+    // a version 5 story with a header extension at 0x40, pointing at a
+    // unicode table at 0x50 with the given characters in it
+    fn unicode_story(characters: &[char]) -> MemoryView {
+        let mut data = vec![0; 0x100];
+        data[0] = 5;
+        write_u16(&mut data, 0x36, 0x40);
+        write_u16(&mut data, 0x40, 3);
+        write_u16(&mut data, 0x46, 0x50);
+
+        data[0x50] = characters.len() as u8;
+
+        for (i, &ch) in characters.iter().enumerate() {
+            write_u16(&mut data, 0x51 + i * 2, ch as u16);
+        }
+
+        view(data)
+    }
+
+    fn charset(view: &MemoryView) -> Charset {
+        Charset::create(view, &Header::create(view.memory.clone()))
+    }
+
+    // a version 5 story with its alphabets at 0x60 - A0 backwards, and
+    // an ä in A2 where the # usually is
+    fn alphabet_story() -> MemoryView {
+        let mut data = vec![0; 0x100];
+        data[0] = 5;
+        write_u16(&mut data, 0x34, 0x60);
+
+        let mut a0 = *DEFAULT_ALPHABETS[0];
+        a0.reverse();
+        let mut a2 = *DEFAULT_ALPHABETS[2];
+        a2[17] = 155;
+
+        data[0x60..0x7A].copy_from_slice(&a0);
+        data[0x7A..0x94].copy_from_slice(DEFAULT_ALPHABETS[1]);
+        data[0x94..0xAE].copy_from_slice(&a2);
+
+        view(data)
+    }
+
+    // encodes a word, and decodes it again as a string in memory
+    fn round_trip(word: &[u8], charset: &Charset) -> String {
+        let encoded = ZString::encode_word(word, charset.version, charset).encoded_as_vec();
+
+        ZString::create(0, &view(encoded), &view(vec![]), charset)
+            .unwrap()
+            .to_string()
+    }
+
+    // decodes z-characters, with "the" as the first abbreviation
+    fn decode(zchars: &[u8], version: u8) -> String {
+        let charset = Charset::default_table(version);
+
+        let mut data = vec![0; 0x40];
+        write_u16(&mut data, 0x20, 0x30 / 2);
+        data[0x30..0x34]
+            .copy_from_slice(&ZString::encode_word(b"the", 3, &charset).encoded_as_vec());
+
+        let abbreviations = MemoryView {
+            memory: Rc::new(RefCell::new(data)),
+            pointer: 0x20,
+        };
+
+        let mut text = String::new();
+        ZString::decode_into_string(&zchars.to_vec(), &mut text, &abbreviations, &charset).unwrap();
+        text
+    }
+
+    #[test]
+    fn zscii_is_ascii_with_a_few_extras() {
+        let charset = Charset::default_table(5);
+
+        for ch in " azAZ09~".chars() {
+            assert_eq!(ZString::encode_zscii(ch, &charset), Some(ch as u16));
+            assert_eq!(ZString::decode_zscii(ch as u16, &charset), Some(ch));
+        }
+
+        // new lines are a carriage return, and 11 is a sentence space
+        assert_eq!(ZString::encode_zscii('\n', &charset), Some(13));
+        assert_eq!(ZString::decode_zscii(13, &charset), Some('\n'));
+        assert_eq!(ZString::decode_zscii(11, &charset), Some(' '));
+
+        assert_eq!(ZString::decode_zscii(0, &charset), None);
+        assert_eq!(ZString::decode_zscii(127, &charset), None);
+        assert_eq!(ZString::decode_zscii(252, &charset), None);
+    }
+
+    #[test]
+    fn the_default_unicode_table_is_accented_latin() {
+        let charset = Charset::default_table(5);
+
+        assert_eq!(ZString::encode_zscii('ä', &charset), Some(155));
+        assert_eq!(ZString::encode_zscii('¿', &charset), Some(223));
+        assert_eq!(ZString::encode_zscii('€', &charset), None);
+
+        assert_eq!(ZString::decode_zscii(155, &charset), Some('ä'));
+        assert_eq!(ZString::decode_zscii(223, &charset), Some('¿'));
+        assert_eq!(ZString::decode_zscii(224, &charset), None);
+    }
+
+    #[test]
+    fn stories_can_bring_their_own_unicode_table() {
+        let charset = charset(&unicode_story(&['€', 'Ж']));
+
+        assert_eq!(charset.unicode, vec!['€', 'Ж']);
+        assert_eq!(ZString::encode_zscii('Ж', &charset), Some(156));
+        assert_eq!(ZString::encode_zscii('ä', &charset), None);
+        assert_eq!(ZString::decode_zscii(155, &charset), Some('€'));
+        assert_eq!(ZString::decode_zscii(157, &charset), None);
+
+        // and a big char ( a zscii code after a 6 in A2 ) goes through it
+        let mut text = String::new();
+        ZString::decode_into_string(&vec![5, 6, 4, 28], &mut text, &view(vec![]), &charset)
+            .unwrap();
+        assert_eq!(text, "Ж");
+    }
+
+    #[test]
+    fn a_table_before_version_5_is_ignored() {
+        let story = unicode_story(&['€']);
+        story.write_at(0, 4).unwrap();
+
+        assert_eq!(charset(&story).unicode, DEFAULT_UNICODE_TABLE.to_vec());
+    }
+
+    #[test]
+    fn stories_can_bring_their_own_alphabets() {
+        let charset = charset(&alphabet_story());
+
+        assert_eq!(ZString::decode_char(6, &Alphabet::A0, &charset), 'z');
+        assert_eq!(ZString::decode_char(31, &Alphabet::A0, &charset), 'a');
+        assert_eq!(ZString::decode_char(6, &Alphabet::A1, &charset), 'A');
+        assert_eq!(ZString::decode_char(23, &Alphabet::A2, &charset), 'ä');
+
+        // 7 in A2 is a new line, whatever the table has there
+        assert_eq!(ZString::decode_char(7, &Alphabet::A2, &charset), '\n');
+    }
+
+    #[test]
+    fn words_are_encoded_with_the_story_alphabets() {
+        let charset = charset(&alphabet_story());
+
+        let zebra = ZString::encode_word(b"zebra", 5, &charset);
+        assert_eq!(zebra.decoded_as_vec(), vec![6, 27, 30, 14, 31, 5, 5, 5, 5]);
+
+        // an ä only needs a shift now, rather than its whole zscii code
+        let umlaut = ZString::encode_word(&[155], 5, &charset);
+        assert_eq!(umlaut.decoded_as_vec(), vec![5, 23, 5, 5, 5, 5, 5, 5, 5]);
+
+        assert_eq!(round_trip(b"zebra", &charset), "zebra");
+        assert_eq!(round_trip(&[b'x', 155, b'#'], &charset), "xä#");
+    }
+
+    #[test]
+    fn alphabets_off_the_end_of_memory_are_ignored() {
+        let story = alphabet_story();
+        story.write_at(0x35, 0xD0).unwrap();
+
+        assert_eq!(charset(&story).alphabets[0], *DEFAULT_ALPHABETS[0]);
+
+        // and so are ones before version 5
+        let story = alphabet_story();
+        story.write_at(0, 4).unwrap();
+
+        assert_eq!(charset(&story).alphabets[0], *DEFAULT_ALPHABETS[0]);
+    }
+
+    #[test]
+    fn words_round_trip_in_every_version() {
+        for version in 1..=8 {
+            let charset = Charset::default_table(version);
+
+            assert_eq!(round_trip(b"lamp", &charset), "lamp");
+            assert_eq!(round_trip(b"Go 2", &charset), "Go 2");
+            assert_eq!(round_trip(&[b'a', 13, b'b'], &charset), "a\nb");
+        }
+
+        // dictionary words are cut off at 6 z-characters, and then 9
+        assert_eq!(round_trip(b"lantern", &Charset::default_table(3)), "lanter");
+        assert_eq!(
+            round_trip(b"lanterns!", &Charset::default_table(4)),
+            "lanterns"
+        );
+    }
+
+    #[test]
+    fn version_1_has_a_new_line_instead_of_abbreviations() {
+        assert_eq!(decode(&[6, 1, 7], 1), "a\nb");
+        assert_eq!(decode(&[6, 1, 0, 7], 2), "atheb");
+        assert_eq!(decode(&[6, 1, 0, 7], 3), "atheb");
+
+        // so its a2 has room for a <, and the digits start a z-character sooner
+        assert_eq!(decode(&[3, 7, 3, 27], 1), "0<");
+        assert_eq!(decode(&[5, 7, 5, 8], 3), "\n0");
+
+        let v1 = Charset::default_table(1);
+        let v3 = Charset::default_table(3);
+        assert_eq!(
+            ZString::encode_word(&[13], 1, &v1).decoded_as_vec()[..2],
+            [1, 5]
+        );
+        assert_eq!(
+            ZString::encode_word(&[13], 3, &v3).decoded_as_vec()[..2],
+            [5, 7]
+        );
+    }
+
+    #[test]
+    fn versions_1_and_2_can_lock_the_shift() {
+        // 4 and 5 stay shifted until the next lock, and 2 and 3 are for one
+        // character, from whichever alphabet is locked
+        assert_eq!(decode(&[4, 6, 7, 5, 6], 2), "ABa");
+        assert_eq!(decode(&[2, 6, 7], 2), "Ab");
+        assert_eq!(decode(&[4, 6, 2, 8, 6], 1), "A1A");
+
+        // from version 3 they're all for one character, and 2 and 3 are
+        // abbreviations
+        assert_eq!(decode(&[4, 6, 7, 5, 8], 3), "Ab0");
+        assert_eq!(decode(&[2, 6, 4, 6], 1), "AA");
+
+        let v2 = Charset::default_table(2);
+        let v3 = Charset::default_table(3);
+        assert_eq!(
+            ZString::encode_word(b"A0", 2, &v2).decoded_as_vec()[..4],
+            [2, 6, 3, 8]
+        );
+        assert_eq!(
+            ZString::encode_word(b"A0", 3, &v3).decoded_as_vec()[..4],
+            [4, 6, 5, 8]
+        );
+    }
+}